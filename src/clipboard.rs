@@ -0,0 +1,185 @@
+//! Copying and pasting drawing objects.
+//!
+//! The [`Clipboard`] keeps an [`EntitySnapshot`] of everything that was
+//! copied, so pasting still works after the originals have been deleted.
+
+use crate::{
+    geometry::{self, BoundingBox},
+    history::EntitySnapshot,
+    shapes::{Shape, ShapePart},
+};
+use arcs::{
+    algorithms::Translate,
+    components::{DrawingObject, Layer, Selected},
+    specs::prelude::*,
+    Point,
+};
+
+/// The objects which were last copied.
+#[derive(Debug, Default, Clone)]
+pub struct Clipboard {
+    objects: Vec<EntitySnapshot>,
+}
+
+impl Clipboard {
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Replace the clipboard's contents with the selected drawing objects and
+    /// shapes, returning how many were copied.
+    pub fn copy_selection(&mut self, world: &World) -> usize {
+        let entities = world.entities();
+        let selected = world.read_storage::<Selected>();
+        let drawing_objects = world.read_storage::<DrawingObject>();
+        let shapes = world.read_storage::<Shape>();
+        let parts = world.read_storage::<ShapePart>();
+
+        self.objects = (&entities, &selected, !&parts)
+            .join()
+            .filter(|(entity, _, _)| drawing_objects.contains(*entity) || shapes.contains(*entity))
+            .map(|(entity, _, _)| EntitySnapshot::capture(world, entity))
+            .collect();
+
+        self.objects.len()
+    }
+
+    /// Create a copy of everything on the clipboard, centred on `location`.
+    ///
+    /// Objects whose layer has since been deleted go on the `fallback_layer`
+    /// instead. The pasted objects become the new selection.
+    pub fn paste(&self, world: &mut World, location: Point, fallback_layer: Entity) -> Vec<Entity> {
+        let displacement = match self.bounds() {
+            Some(bounds) => location - bounds.center(),
+            None => return Vec::new(),
+        };
+
+        world.write_storage::<Selected>().clear();
+        let mut pasted = Vec::with_capacity(self.objects.len());
+
+        for snapshot in &self.objects {
+            let mut snapshot = snapshot.clone();
+
+            if let Some(object) = snapshot.object.as_mut() {
+                object.geometry.translate(displacement);
+                object.layer = existing_layer(world, object.layer, fallback_layer);
+            }
+            if let Some(shape) = snapshot.shape.as_mut() {
+                shape.geometry.translate(displacement);
+                shape.layer = existing_layer(world, shape.layer, fallback_layer);
+            }
+
+            let entity = snapshot.restore(world);
+            if let Err(e) = world.write_storage().insert(entity, Selected) {
+                log::warn!("Unable to select {:?}: {}", entity, e);
+            }
+            pasted.push(entity);
+        }
+
+        pasted
+    }
+
+    /// The area covered by everything on the clipboard.
+    fn bounds(&self) -> Option<BoundingBox> {
+        let objects = self
+            .objects
+            .iter()
+            .filter_map(|snapshot| snapshot.object.as_ref())
+            .filter_map(|object| geometry::bounding_box(&object.geometry));
+        let shapes = self
+            .objects
+            .iter()
+            .filter_map(|snapshot| snapshot.shape.as_ref())
+            .filter_map(|shape| shape.geometry.bounding_box());
+
+        objects
+            .chain(shapes)
+            .fold(None, |bounds: Option<BoundingBox>, next| match bounds {
+                Some(bounds) => Some(bounds.union(&next)),
+                None => Some(next),
+            })
+    }
+}
+
+/// The `layer` if it still exists, otherwise the `fallback`.
+fn existing_layer(world: &World, layer: Entity, fallback: Entity) -> Entity {
+    if world.is_alive(layer) && world.read_storage::<Layer>().contains(layer) {
+        layer
+    } else {
+        fallback
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arcs::{
+        components::{Geometry, Name},
+        primitives::Line,
+    };
+
+    fn add_layer(world: &mut World, name: &str) -> Entity {
+        Layer::create(world.create_entity(), Name::new(name), Layer::default())
+    }
+
+    fn add_line(world: &mut World, layer: Entity, start: Point, end: Point) -> Entity {
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(start, end)),
+                layer,
+            })
+            .with(Selected)
+            .build()
+    }
+
+    #[test]
+    fn pasting_centres_the_copy_on_the_cursor() {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let layer = add_layer(&mut world, "default");
+        let original = add_line(
+            &mut world,
+            layer,
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+        );
+        let mut clipboard = Clipboard::default();
+
+        assert_eq!(clipboard.copy_selection(&world), 1);
+        let pasted = clipboard.paste(&mut world, Point::new(100.0, 100.0), layer);
+
+        assert_eq!(pasted.len(), 1);
+        assert_ne!(pasted[0], original);
+        let drawing_objects = world.read_storage::<DrawingObject>();
+        assert_eq!(
+            drawing_objects.get(pasted[0]).unwrap().geometry,
+            Geometry::Line(Line::new(Point::new(95.0, 100.0), Point::new(105.0, 100.0)))
+        );
+        let selected = world.read_storage::<Selected>();
+        assert!(selected.contains(pasted[0]));
+        assert!(!selected.contains(original));
+    }
+
+    #[test]
+    fn objects_from_deleted_layers_go_on_the_fallback_layer() {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let layer = add_layer(&mut world, "doomed");
+        let fallback = add_layer(&mut world, "default");
+        add_line(
+            &mut world,
+            layer,
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+        );
+        let mut clipboard = Clipboard::default();
+        clipboard.copy_selection(&world);
+        world.delete_entity(layer).unwrap();
+
+        let pasted = clipboard.paste(&mut world, Point::new(0.0, 0.0), fallback);
+
+        let drawing_objects = world.read_storage::<DrawingObject>();
+        assert_eq!(drawing_objects.get(pasted[0]).unwrap().layer, fallback);
+    }
+}
@@ -0,0 +1,448 @@
+//! The commands which can be typed into the console.
+//!
+//! Each [`Command`] has a name, some aliases, and an [`Action`] saying what
+//! it does. The [`Keymap`](crate::keymap::Keymap) can also bind commands to
+//! key presses.
+
+use crate::keyboard_event_args::{KeyboardEventArgs, VirtualKeyCode};
+use crate::modes::add_circle_mode::CircleConstruction;
+use crate::modes::add_ellipse_mode::EllipseKind;
+use crate::modes::add_polygon_mode::PolygonKind;
+use crate::modes::add_rectangle_mode::RectangleConstruction;
+use crate::modes::{
+    AddArcMode, AddCircleMode, AddEllipseMode, AddLineMode, AddPointMode, AddPolygonMode,
+    AddPolylineMode, AddRectangleMode, ApplicationContext, Idle, State, Transition,
+};
+use crate::msg::ButtonType;
+use crate::svg::Crop;
+use std::fmt::{self, Display, Formatter};
+
+/// What happens when a [`Command`] is run.
+#[derive(Debug, Copy, Clone)]
+pub enum Action {
+    /// Switch to a new [`State`].
+    ChangeState(fn() -> Box<dyn State>),
+    /// Do the same thing as clicking on a toolbar button.
+    Button(ButtonType),
+    /// Pass a key press straight to the current [`State`], for things only
+    /// it knows how to do.
+    Key(VirtualKeyCode),
+}
+
+impl Action {
+    /// Run the [`Action`] from inside a [`State`].
+    pub fn run(self, state: &mut dyn State, ctx: &mut dyn ApplicationContext) -> Transition {
+        match self {
+            Action::ChangeState(new_state) => {
+                state.on_cancelled(ctx);
+                Transition::ChangeState(new_state())
+            }
+            Action::Button(button) => state.on_button_clicked(ctx, &button),
+            Action::Key(key) => state.on_key_pressed(ctx, &KeyboardEventArgs::pressing(key)),
+        }
+    }
+}
+
+/// Something the user can type into the console.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub description: &'static str,
+    pub action: Action,
+    /// Variations picked by the command's first argument (e.g. `ZOOM E`).
+    /// The argument may be any prefix of an option's name.
+    pub options: Vec<(&'static str, Action)>,
+}
+
+impl Command {
+    pub fn new(name: &'static str, description: &'static str, action: Action) -> Command {
+        Command {
+            name,
+            aliases: &[],
+            description,
+            action,
+            options: Vec::new(),
+        }
+    }
+
+    pub fn with_aliases(self, aliases: &'static [&'static str]) -> Command {
+        Command { aliases, ..self }
+    }
+
+    pub fn with_option(mut self, name: &'static str, action: Action) -> Command {
+        self.options.push((name, action));
+        self
+    }
+
+    fn is_called(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A resource containing every known [`Command`].
+#[derive(Debug, Clone)]
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl CommandRegistry {
+    pub fn empty() -> CommandRegistry {
+        CommandRegistry {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    /// Find a command by its name or one of its aliases, ignoring case.
+    pub fn find(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.is_called(name))
+    }
+
+    /// Figure out which [`Action`] a line of console input refers to.
+    pub fn parse(&self, line: &str) -> Result<Action, CommandError> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or(CommandError::Empty)?;
+        let command = self
+            .find(name)
+            .ok_or_else(|| CommandError::UnknownCommand(name.to_string()))?;
+
+        let argument = match words.next() {
+            Some(argument) => argument.to_uppercase(),
+            None => return Ok(command.action),
+        };
+
+        command
+            .options
+            .iter()
+            .find(|(option, _)| option.starts_with(&argument))
+            .map(|&(_, action)| action)
+            .ok_or_else(|| CommandError::UnknownOption {
+                command: command.name,
+                option: argument,
+            })
+    }
+
+    /// The names of all commands starting with `prefix`, in alphabetical
+    /// order.
+    pub fn complete(&self, prefix: &str) -> Vec<&'static str> {
+        let prefix = prefix.trim().to_uppercase();
+        let mut names: Vec<_> = self
+            .commands
+            .iter()
+            .map(|command| command.name)
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        names.sort();
+
+        names
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> CommandRegistry {
+        let mut registry = CommandRegistry::empty();
+
+        registry.register(
+            Command::new("ARC", "Draw an arc", Action::ChangeState(arc_mode)).with_aliases(&["A"]),
+        );
+        registry.register(
+            Command::new(
+                "CIRCLE",
+                "Draw a circle by its centre and radius, a diameter (2P) or three points (3P)",
+                Action::ChangeState(circle_mode),
+            )
+            .with_aliases(&["C"])
+            .with_option("RADIUS", Action::ChangeState(circle_mode))
+            .with_option("DIAMETER", Action::ChangeState(two_point_circle_mode))
+            .with_option("2P", Action::ChangeState(two_point_circle_mode))
+            .with_option("3P", Action::ChangeState(three_point_circle_mode)),
+        );
+        registry.register(
+            Command::new(
+                "ELLIPSE",
+                "Draw an ellipse, or part of one (ARC)",
+                Action::ChangeState(ellipse_mode),
+            )
+            .with_aliases(&["EL"])
+            .with_option("ARC", Action::ChangeState(elliptical_arc_mode)),
+        );
+        registry.register(
+            Command::new("LINE", "Draw lines", Action::ChangeState(line_mode)).with_aliases(&["L"]),
+        );
+        registry.register(
+            Command::new(
+                "PLINE",
+                "Draw connected line segments",
+                Action::ChangeState(polyline_mode),
+            )
+            .with_aliases(&["PL", "POLYLINE"]),
+        );
+        registry.register(
+            Command::new("POINT", "Draw points", Action::ChangeState(point_mode))
+                .with_aliases(&["P", "PO"]),
+        );
+        registry.register(
+            Command::new(
+                "RECTANGLE",
+                "Draw a rectangle from two corners, or its centre (C) and a corner",
+                Action::ChangeState(rectangle_mode),
+            )
+            .with_aliases(&["REC", "RECT"])
+            .with_option("CORNERS", Action::ChangeState(rectangle_mode))
+            .with_option("CENTRE", Action::ChangeState(centred_rectangle_mode)),
+        );
+        registry.register(
+            Command::new(
+                "POLYGON",
+                "Draw a regular polygon, inscribed in (I) or circumscribed about (C) a circle",
+                Action::ChangeState(polygon_mode),
+            )
+            .with_aliases(&["POL"])
+            .with_option("INSCRIBED", Action::ChangeState(polygon_mode))
+            .with_option(
+                "CIRCUMSCRIBED",
+                Action::ChangeState(circumscribed_polygon_mode),
+            ),
+        );
+        registry.register(
+            Command::new("SELECT", "Go back to selecting", Action::ChangeState(idle))
+                .with_aliases(&["S"]),
+        );
+        registry.register(
+            Command::new(
+                "DELETE",
+                "Erase the selection",
+                Action::Button(ButtonType::Erase),
+            )
+            .with_aliases(&["DEL", "ERASE", "E"]),
+        );
+        registry.register(Command::new(
+            "COPYCLIP",
+            "Copy the selection to the clipboard",
+            Action::Button(ButtonType::Copy),
+        ));
+        registry.register(Command::new(
+            "PASTECLIP",
+            "Paste the clipboard at the cursor",
+            Action::Button(ButtonType::Paste),
+        ));
+        registry.register(
+            Command::new(
+                "PROPERTIES",
+                "Show a summary of the selection",
+                Action::Button(ButtonType::Properties),
+            )
+            .with_aliases(&["PR", "PROPS"]),
+        );
+        registry.register(
+            Command::new(
+                "ZOOM",
+                "Zoom to the drawing's extents (E) or the selection (S)",
+                Action::Button(ButtonType::ZoomExtents),
+            )
+            .with_aliases(&["Z"])
+            .with_option("EXTENTS", Action::Button(ButtonType::ZoomExtents))
+            .with_option("SELECTION", Action::Button(ButtonType::ZoomSelection)),
+        );
+        registry.register(
+            Command::new(
+                "UNDO",
+                "Undo the last change",
+                Action::Button(ButtonType::Undo),
+            )
+            .with_aliases(&["U"]),
+        );
+        registry.register(Command::new(
+            "REDO",
+            "Redo the last undone change",
+            Action::Button(ButtonType::Redo),
+        ));
+        registry.register(Command::new(
+            "SAVE",
+            "Download the drawing",
+            Action::Button(ButtonType::Save),
+        ));
+        registry.register(
+            Command::new(
+                "EXPORT",
+                "Export to DXF, or to SVG (viewport or extents)",
+                Action::Button(ButtonType::ExportDxf),
+            )
+            .with_option("DXF", Action::Button(ButtonType::ExportDxf))
+            .with_option("SVG", Action::Button(ButtonType::ExportSvg(Crop::Viewport)))
+            .with_option(
+                "EXTENTS",
+                Action::Button(ButtonType::ExportSvg(Crop::Extents)),
+            ),
+        );
+
+        registry
+    }
+}
+
+fn arc_mode() -> Box<dyn State> {
+    Box::new(AddArcMode::default())
+}
+
+fn circle_mode() -> Box<dyn State> {
+    Box::new(AddCircleMode::default())
+}
+
+fn two_point_circle_mode() -> Box<dyn State> {
+    Box::new(AddCircleMode::new(CircleConstruction::TwoPoint))
+}
+
+fn three_point_circle_mode() -> Box<dyn State> {
+    Box::new(AddCircleMode::new(CircleConstruction::ThreePoint))
+}
+
+fn line_mode() -> Box<dyn State> {
+    Box::new(AddLineMode::default())
+}
+
+fn polyline_mode() -> Box<dyn State> {
+    Box::new(AddPolylineMode::default())
+}
+
+fn ellipse_mode() -> Box<dyn State> {
+    Box::new(AddEllipseMode::default())
+}
+
+fn elliptical_arc_mode() -> Box<dyn State> {
+    Box::new(AddEllipseMode::new(EllipseKind::Arc))
+}
+
+fn rectangle_mode() -> Box<dyn State> {
+    Box::new(AddRectangleMode::default())
+}
+
+fn centred_rectangle_mode() -> Box<dyn State> {
+    Box::new(AddRectangleMode::new(RectangleConstruction::Centre))
+}
+
+fn polygon_mode() -> Box<dyn State> {
+    Box::new(AddPolygonMode::default())
+}
+
+fn circumscribed_polygon_mode() -> Box<dyn State> {
+    Box::new(AddPolygonMode::new(PolygonKind::Circumscribed))
+}
+
+fn point_mode() -> Box<dyn State> {
+    Box::new(AddPointMode::default())
+}
+
+fn idle() -> Box<dyn State> {
+    Box::new(Idle::default())
+}
+
+/// The reasons a line of console input couldn't be understood.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Empty,
+    UnknownCommand(String),
+    UnknownOption {
+        command: &'static str,
+        option: String,
+    },
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "No command was entered"),
+            CommandError::UnknownCommand(name) => write!(f, "Unknown command \"{}\"", name),
+            CommandError::UnknownOption { command, option } => {
+                write!(f, "{} doesn't have a \"{}\" option", command, option)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn button(action: Action) -> ButtonType {
+        match action {
+            Action::Button(button) => button,
+            other => panic!("Expected a button, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn aliases_are_case_insensitive() {
+        let registry = CommandRegistry::default();
+
+        let got = registry.find("po").unwrap();
+
+        assert_eq!(got.name, "POINT");
+    }
+
+    #[test]
+    fn pick_an_option_by_its_prefix() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(
+            button(registry.parse("ZOOM").unwrap()),
+            ButtonType::ZoomExtents
+        );
+        assert_eq!(
+            button(registry.parse("zoom s").unwrap()),
+            ButtonType::ZoomSelection
+        );
+        assert_eq!(
+            button(registry.parse("export ext").unwrap()),
+            ButtonType::ExportSvg(Crop::Extents)
+        );
+    }
+
+    #[test]
+    fn bad_input() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(registry.parse("  ").unwrap_err(), CommandError::Empty);
+        assert_eq!(
+            registry.parse("FOO").unwrap_err(),
+            CommandError::UnknownCommand(String::from("FOO"))
+        );
+        assert_eq!(
+            registry.parse("ZOOM X").unwrap_err(),
+            CommandError::UnknownOption {
+                command: "ZOOM",
+                option: String::from("X")
+            }
+        );
+    }
+
+    #[test]
+    fn complete_a_partial_name() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(registry.complete("re"), vec!["RECTANGLE", "REDO"]);
+        assert_eq!(registry.complete("s"), vec!["SAVE", "SELECT"]);
+        assert!(registry.complete("q").is_empty());
+    }
+
+    #[test]
+    fn line_switches_to_line_mode() {
+        let registry = CommandRegistry::default();
+
+        match registry.parse("LINE").unwrap() {
+            Action::ChangeState(new_state) => {
+                assert!(Transition::ChangeState(new_state()).changes_to::<AddLineMode>())
+            }
+            other => panic!("LINE shouldn't do {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Components used by this application on top of the ones from `arcs`.
+
+use crate::shapes::{Shape, ShapePart};
+use arcs::specs::prelude::*;
+
+/// Marks the temporary [`DrawingObject`]s a drawing mode uses to show the
+/// user what they'll get.
+///
+/// Previews aren't part of the drawing yet, so they can't be snapped to or
+/// picked.
+///
+/// [`DrawingObject`]: arcs::components::DrawingObject
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Preview;
+
+impl Component for Preview {
+    type Storage = NullStorage<Self>;
+}
+
+/// Register our components alongside the ones from `arcs`.
+pub fn register(world: &mut World) {
+    arcs::components::register(world);
+    world.register::<Preview>();
+    world.register::<Shape>();
+    world.register::<ShapePart>();
+}
@@ -0,0 +1,29 @@
+//! The menu shown when right-clicking on the canvas.
+//!
+//! The current [`State`](crate::modes::State) decides what goes in the menu
+//! via [`State::context_menu()`](crate::modes::State::context_menu), and
+//! picking an item runs its [`Action`].
+
+use crate::commands::Action;
+use arcs::{euclid::Point2D, CanvasSpace};
+
+/// Something which can be picked from the context menu.
+#[derive(Debug, Copy, Clone)]
+pub struct MenuItem {
+    pub label: &'static str,
+    pub action: Action,
+}
+
+impl MenuItem {
+    pub fn new(label: &'static str, action: Action) -> MenuItem {
+        MenuItem { label, action }
+    }
+}
+
+/// A context menu which is currently open.
+#[derive(Debug, Clone)]
+pub struct ContextMenu {
+    /// Where the menu was opened, relative to the canvas's top-left corner.
+    pub position: Point2D<f64, CanvasSpace>,
+    pub items: Vec<MenuItem>,
+}
@@ -0,0 +1,394 @@
+//! Typing in coordinates instead of clicking on the canvas.
+//!
+//! Three forms are accepted:
+//!
+//! - `x,y` - an absolute location on the drawing
+//! - `@dx,dy` - an offset from the previous point
+//! - `@length<angle` - a distance and angle (in degrees, anticlockwise from
+//!   the x-axis) from the previous point
+//!
+//! The text being typed lives in the [`CoordinateEntry`] resource so the UI
+//! can show it.
+//!
+//! Typed coordinates are exact, so the [`TypedLocation`] resource stops the
+//! grid snap from moving them until the mouse moves again.
+
+use crate::keyboard_event_args::{KeyboardEventArgs, VirtualKeyCode};
+use arcs::{components::CursorPosition, euclid::Angle, specs::prelude::*, Point, Vector};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+/// A coordinate the user typed in.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Coordinate {
+    Absolute(Point),
+    Relative(Vector),
+    Polar { length: f64, angle: Angle<f64> },
+}
+
+impl Coordinate {
+    /// Figure out where this [`Coordinate`] is on the drawing, given the
+    /// previous point.
+    pub fn resolve(self, previous: Point) -> Point {
+        match self {
+            Coordinate::Absolute(location) => location,
+            Coordinate::Relative(offset) => previous + offset,
+            Coordinate::Polar { length, angle } => {
+                let (sin, cos) = angle.sin_cos();
+                previous + Vector::new(cos, sin) * length
+            }
+        }
+    }
+}
+
+impl FromStr for Coordinate {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        let (relative, rest) = if s.starts_with('@') {
+            (true, &s[1..])
+        } else {
+            (false, s)
+        };
+
+        if let Some(ix) = rest.find('<') {
+            if !relative {
+                return Err(ParseError::PolarMustBeRelative);
+            }
+            let length = number(&rest[..ix])?;
+            let angle = number(&rest[ix + 1..])?;
+            return Ok(Coordinate::Polar {
+                length,
+                angle: Angle::degrees(angle),
+            });
+        }
+
+        let ix = rest.find(',').ok_or(ParseError::MissingComma)?;
+        let x = number(&rest[..ix])?;
+        let y = number(&rest[ix + 1..])?;
+
+        if relative {
+            Ok(Coordinate::Relative(Vector::new(x, y)))
+        } else {
+            Ok(Coordinate::Absolute(Point::new(x, y)))
+        }
+    }
+}
+
+fn number(text: &str) -> Result<f64, ParseError> {
+    let text = text.trim();
+    text.parse()
+        .map_err(|_| ParseError::InvalidNumber(text.to_string()))
+}
+
+/// The reasons typed text might not be a valid [`Coordinate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Empty,
+    InvalidNumber(String),
+    MissingComma,
+    PolarMustBeRelative,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Nothing was entered"),
+            ParseError::InvalidNumber(text) => write!(f, "\"{}\" isn't a number", text),
+            ParseError::MissingComma => write!(f, "Expected \"x,y\" or \"@dx,dy\""),
+            ParseError::PolarMustBeRelative => {
+                write!(f, "Polar coordinates must start with \"@\"")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A resource holding the text the user is typing, if they are typing.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CoordinateEntry {
+    pub text: Option<String>,
+}
+
+/// A resource holding the location the user last typed in, until the mouse
+/// moves.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct TypedLocation {
+    pub location: Option<Point>,
+}
+
+/// What happened when a key was pressed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyOutcome {
+    /// The key has nothing to do with coordinate entry.
+    Ignored,
+    /// The text was changed.
+    Edited,
+    /// The user gave up on typing a coordinate.
+    Cancelled,
+    /// The user pressed enter on a valid [`Coordinate`].
+    Committed(Coordinate),
+}
+
+/// Update the [`CoordinateEntry`] in response to a key press.
+///
+/// Typing a digit, `@`, `-` or `.` opens the entry, enter or space commits
+/// it and escape closes it. Invalid text is left open so the user can fix it.
+pub fn on_key_pressed(world: &mut World, args: &KeyboardEventArgs) -> KeyOutcome {
+    let key = match args.key {
+        Some(key) if !args.control_pressed => key,
+        _ => return KeyOutcome::Ignored,
+    };
+    let mut entry = world
+        .entry::<CoordinateEntry>()
+        .or_insert_with(CoordinateEntry::default);
+
+    let mut text = match entry.text.take() {
+        Some(text) => text,
+        None => match character(key) {
+            Some(c) if c.is_ascii_digit() || "@-.".contains(c) => {
+                entry.text = Some(c.to_string());
+                return KeyOutcome::Edited;
+            }
+            _ => return KeyOutcome::Ignored,
+        },
+    };
+
+    let outcome = match key {
+        VirtualKeyCode::Escape => return KeyOutcome::Cancelled,
+        VirtualKeyCode::Enter | VirtualKeyCode::Space => match text.parse() {
+            Ok(coordinate) => return KeyOutcome::Committed(coordinate),
+            Err(e) => {
+                log::warn!("Unable to parse \"{}\": {}", text, e);
+                KeyOutcome::Edited
+            }
+        },
+        VirtualKeyCode::Backspace => {
+            text.pop();
+            KeyOutcome::Edited
+        }
+        other => match character(other) {
+            Some(c) => {
+                text.push(c);
+                KeyOutcome::Edited
+            }
+            None => KeyOutcome::Ignored,
+        },
+    };
+
+    if !text.is_empty() {
+        entry.text = Some(text);
+    }
+
+    outcome
+}
+
+/// The text being typed in, if there is any.
+pub fn current_text(world: &World) -> Option<String> {
+    world
+        .try_fetch::<CoordinateEntry>()
+        .and_then(|entry| entry.text.clone())
+}
+
+/// Throw away anything which was being typed.
+pub fn close(world: &mut World) {
+    if let Some(mut entry) = world.try_fetch_mut::<CoordinateEntry>() {
+        entry.text = None;
+    }
+}
+
+/// Put the cursor at exactly `location`, ignoring the grid snap until
+/// [`forget_typed_location()`] is called.
+pub fn set_typed_location(world: &mut World, location: Point) {
+    world.insert(TypedLocation {
+        location: Some(location),
+    });
+    world.write_resource::<CursorPosition>().location = location;
+}
+
+/// The mouse has moved so the cursor can go back to following it.
+pub fn forget_typed_location(world: &World) {
+    if let Some(mut typed) = world.try_fetch_mut::<TypedLocation>() {
+        typed.location = None;
+    }
+}
+
+/// Moves the [`CursorPosition`] back onto the [`TypedLocation`], undoing the
+/// grid snap. This runs after every other snap.
+#[derive(Debug, Default)]
+pub struct ApplyTypedLocation;
+
+impl<'a> System<'a> for ApplyTypedLocation {
+    type SystemData = (Option<Read<'a, TypedLocation>>, Write<'a, CursorPosition>);
+
+    fn run(&mut self, (typed, mut cursor_position): Self::SystemData) {
+        if let Some(location) = typed.and_then(|typed| typed.location) {
+            cursor_position.location = location;
+        }
+    }
+}
+
+fn character(key: VirtualKeyCode) -> Option<char> {
+    let c = match key {
+        VirtualKeyCode::Key0 => '0',
+        VirtualKeyCode::Key1 => '1',
+        VirtualKeyCode::Key2 => '2',
+        VirtualKeyCode::Key3 => '3',
+        VirtualKeyCode::Key4 => '4',
+        VirtualKeyCode::Key5 => '5',
+        VirtualKeyCode::Key6 => '6',
+        VirtualKeyCode::Key7 => '7',
+        VirtualKeyCode::Key8 => '8',
+        VirtualKeyCode::Key9 => '9',
+        VirtualKeyCode::Comma => ',',
+        VirtualKeyCode::Period => '.',
+        VirtualKeyCode::Minus => '-',
+        VirtualKeyCode::At => '@',
+        VirtualKeyCode::LessThan => '<',
+        _ => return None,
+    };
+
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_the_different_forms() {
+        let inputs = vec![
+            ("1.5,-2", Coordinate::Absolute(Point::new(1.5, -2.0))),
+            (" @ 3 , 4 ", Coordinate::Relative(Vector::new(3.0, 4.0))),
+            (
+                "@10<90",
+                Coordinate::Polar {
+                    length: 10.0,
+                    angle: Angle::degrees(90.0),
+                },
+            ),
+        ];
+
+        for (src, should_be) in inputs {
+            let got: Coordinate = src.parse().unwrap();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn invalid_input() {
+        let inputs = vec![
+            ("", ParseError::Empty),
+            ("12", ParseError::MissingComma),
+            ("1,x", ParseError::InvalidNumber(String::from("x"))),
+            ("10<45", ParseError::PolarMustBeRelative),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = src.parse::<Coordinate>().unwrap_err();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn polar_coordinates_are_relative_to_the_previous_point() {
+        let coordinate = Coordinate::Polar {
+            length: 2.0,
+            angle: Angle::degrees(90.0),
+        };
+
+        let got = coordinate.resolve(Point::new(1.0, 1.0));
+
+        assert!((got - Point::new(1.0, 3.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn type_a_coordinate_and_press_enter() {
+        let mut world = World::new();
+        let keys = [
+            VirtualKeyCode::At,
+            VirtualKeyCode::Key5,
+            VirtualKeyCode::Comma,
+            VirtualKeyCode::Minus,
+            VirtualKeyCode::Key1,
+        ];
+
+        for &key in &keys {
+            let got = on_key_pressed(&mut world, &KeyboardEventArgs::pressing(key));
+            assert_eq!(got, KeyOutcome::Edited);
+        }
+        assert_eq!(current_text(&world), Some(String::from("@5,-1")));
+
+        let got = on_key_pressed(
+            &mut world,
+            &KeyboardEventArgs::pressing(VirtualKeyCode::Enter),
+        );
+
+        assert_eq!(
+            got,
+            KeyOutcome::Committed(Coordinate::Relative(Vector::new(5.0, -1.0)))
+        );
+        assert_eq!(current_text(&world), None);
+    }
+
+    #[test]
+    fn space_commits_like_enter() {
+        let mut world = World::new();
+        let keys = [
+            VirtualKeyCode::Key2,
+            VirtualKeyCode::Comma,
+            VirtualKeyCode::Key3,
+        ];
+        for &key in &keys {
+            on_key_pressed(&mut world, &KeyboardEventArgs::pressing(key));
+        }
+
+        let got = on_key_pressed(
+            &mut world,
+            &KeyboardEventArgs::pressing(VirtualKeyCode::Space),
+        );
+
+        assert_eq!(
+            got,
+            KeyOutcome::Committed(Coordinate::Absolute(Point::new(2.0, 3.0)))
+        );
+        assert_eq!(current_text(&world), None);
+    }
+
+    #[test]
+    fn letters_dont_open_the_entry() {
+        let mut world = World::new();
+
+        let got = on_key_pressed(&mut world, &KeyboardEventArgs::pressing(VirtualKeyCode::A));
+
+        assert_eq!(got, KeyOutcome::Ignored);
+        assert_eq!(current_text(&world), None);
+    }
+
+    #[test]
+    fn typed_locations_arent_moved_until_the_mouse_moves() {
+        let mut world = World::new();
+        world.insert(CursorPosition::default());
+        let typed = Point::new(1.25, 3.5);
+        let snapped = Point::new(0.0, 0.0);
+
+        set_typed_location(&mut world, typed);
+        // pretend the grid snapped the cursor
+        world.write_resource::<CursorPosition>().location = snapped;
+        RunNow::run_now(&mut ApplyTypedLocation, &world);
+        assert_eq!(world.read_resource::<CursorPosition>().location, typed);
+
+        forget_typed_location(&world);
+        world.write_resource::<CursorPosition>().location = snapped;
+        RunNow::run_now(&mut ApplyTypedLocation, &world);
+        assert_eq!(world.read_resource::<CursorPosition>().location, snapped);
+    }
+}
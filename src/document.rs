@@ -0,0 +1,861 @@
+//! Saving and loading drawings as JSON.
+//!
+//! The [`Document`] is a plain-old-data copy of everything needed to rebuild
+//! a drawing. It deliberately doesn't reuse the `arcs` types so the file
+//! format won't change underneath us when `arcs` does.
+
+use crate::{
+    components::Preview,
+    dxf::DxfError,
+    ellipse::Ellipse,
+    geometry::{self, BoundingBox},
+    modes::{MAX_PIXELS_PER_DRAWING_UNIT, MIN_PIXELS_PER_DRAWING_UNIT},
+    polyline::Polyline,
+    shapes::{Shape, ShapeGeometry, ShapePart},
+};
+use arcs::{
+    components::{
+        layer::LayerType, Dimension, DrawingObject, Geometry, Layer, LineStyle, Name, PointStyle,
+        Viewport,
+    },
+    euclid::{Angle, Length, Scale},
+    piet::Color,
+    primitives::{Arc, Line},
+    specs::prelude::*,
+    Point, Vector,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fmt};
+
+/// The version of the file format written by [`Document::to_json()`].
+///
+/// Version 2 added polylines and ellipses.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Upgrades a document from version `n + 1` to version `n + 2`, where `n` is
+/// the migration's index in this list.
+const MIGRATIONS: &[fn(Value) -> Result<Value, DocumentError>] = &[version_1_to_2];
+
+#[derive(Debug)]
+pub enum DocumentError {
+    Json(serde_json::Error),
+    MissingVersion,
+    UnsupportedVersion(u64),
+    UnknownLayer(usize),
+    Dxf(DxfError),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Json(e) => write!(f, "Invalid document: {}", e),
+            DocumentError::MissingVersion => write!(f, "The document has no version number"),
+            DocumentError::UnsupportedVersion(v) => {
+                write!(f, "Unable to load version {} documents", v)
+            }
+            DocumentError::UnknownLayer(id) => {
+                write!(
+                    f,
+                    "An object refers to a layer ({}) which doesn't exist",
+                    id
+                )
+            }
+            DocumentError::Dxf(e) => write!(f, "Invalid DXF file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(e: serde_json::Error) -> DocumentError {
+        DocumentError::Json(e)
+    }
+}
+
+impl From<DxfError> for DocumentError {
+    fn from(e: DxfError) -> DocumentError {
+        DocumentError::Dxf(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub viewport: ViewportDoc,
+    pub layers: Vec<LayerDoc>,
+    pub objects: Vec<ObjectDoc>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointDoc {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewportDoc {
+    pub centre: PointDoc,
+    pub pixels_per_drawing_unit: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerDoc {
+    /// The identifier [`ObjectDoc::layer`] uses to refer to this layer.
+    pub id: usize,
+    pub name: String,
+    pub z_level: usize,
+    pub visible: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub point_style: Option<PointStyleDoc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_style: Option<LineStyleDoc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDoc {
+    pub layer: usize,
+    pub geometry: GeometryDoc,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub point_style: Option<PointStyleDoc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_style: Option<LineStyleDoc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GeometryDoc {
+    Point {
+        location: PointDoc,
+    },
+    Line {
+        start: PointDoc,
+        end: PointDoc,
+    },
+    Arc {
+        centre: PointDoc,
+        radius: f64,
+        /// The start angle, in radians.
+        start_angle: f64,
+        /// The sweep angle, in radians. Positive values are anticlockwise.
+        sweep_angle: f64,
+    },
+    Polyline {
+        vertices: Vec<PointDoc>,
+        /// Is there a segment from the last vertex back to the first?
+        closed: bool,
+    },
+    Ellipse {
+        centre: PointDoc,
+        /// The end of the major axis, relative to the centre.
+        major_axis: PointDoc,
+        /// The length of the minor axis relative to the major axis.
+        ratio: f64,
+        /// The parameter the ellipse starts at, in radians.
+        start_parameter: f64,
+        /// The parameter the ellipse ends at, in radians. A full ellipse goes
+        /// from `0` to `2π`.
+        end_parameter: f64,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "units", content = "value", rename_all = "snake_case")]
+pub enum DimensionDoc {
+    Pixels(f64),
+    DrawingUnits(f64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointStyleDoc {
+    /// A CSS-style `#rrggbbaa` colour.
+    pub colour: String,
+    pub radius: DimensionDoc,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineStyleDoc {
+    /// A CSS-style `#rrggbbaa` colour.
+    pub stroke: String,
+    pub width: DimensionDoc,
+}
+
+impl Document {
+    /// Copy the user's layers and objects out of the [`World`].
+    ///
+    /// Anything on a [`LayerType::System`] layer (e.g. the grid) is skipped.
+    pub fn from_world(world: &World, viewport: Entity) -> Document {
+        let entities = world.entities();
+        let layers = world.read_storage::<Layer>();
+        let names = world.read_storage::<Name>();
+        let drawing_objects = world.read_storage::<DrawingObject>();
+        let shapes = world.read_storage::<Shape>();
+        let parts = world.read_storage::<ShapePart>();
+        let previews = world.read_storage::<Preview>();
+        let point_styles = world.read_storage::<PointStyle>();
+        let line_styles = world.read_storage::<LineStyle>();
+        let viewports = world.read_storage::<Viewport>();
+
+        let mut layer_ids = HashMap::new();
+        let mut layer_docs = Vec::new();
+
+        for (entity, layer, name) in (&entities, &layers, names.maybe()).join() {
+            if is_system(layer) {
+                continue;
+            }
+
+            let id = layer_docs.len();
+            layer_ids.insert(entity, id);
+            layer_docs.push(LayerDoc {
+                id,
+                name: name.map(|n| n.to_string()).unwrap_or_default(),
+                z_level: layer.z_level,
+                visible: layer.visible,
+                point_style: point_styles.get(entity).map(PointStyleDoc::from),
+                line_style: line_styles.get(entity).map(LineStyleDoc::from),
+            });
+        }
+
+        let mut objects = Vec::new();
+
+        // a shape's parts get recreated from the shape itself, and anything
+        // still being drawn isn't part of the drawing yet
+        let plain_objects = (&entities, &drawing_objects, !&parts, !&previews)
+            .join()
+            .filter_map(|(entity, drawing_object, _, _)| {
+                GeometryDoc::from_geometry(&drawing_object.geometry)
+                    .map(|geometry| (entity, drawing_object.layer, geometry))
+            });
+        let shape_objects = (&entities, &shapes, !&previews)
+            .join()
+            .map(|(entity, shape, _)| {
+                (
+                    entity,
+                    shape.layer,
+                    GeometryDoc::from_shape(&shape.geometry),
+                )
+            });
+
+        for (entity, layer, geometry) in plain_objects.chain(shape_objects) {
+            let layer = match layer_ids.get(&layer) {
+                Some(&id) => id,
+                None => continue,
+            };
+
+            objects.push(ObjectDoc {
+                layer,
+                geometry,
+                point_style: point_styles.get(entity).map(PointStyleDoc::from),
+                line_style: line_styles.get(entity).map(LineStyleDoc::from),
+            });
+        }
+
+        let viewport = viewports
+            .get(viewport)
+            .map(ViewportDoc::from)
+            .unwrap_or_else(|| ViewportDoc {
+                centre: PointDoc { x: 0.0, y: 0.0 },
+                pixels_per_drawing_unit: 1.0,
+            });
+
+        Document {
+            version: CURRENT_VERSION,
+            viewport,
+            layers: layer_docs,
+            objects,
+        }
+    }
+
+    /// Replace the user's layers and objects in the [`World`] with the
+    /// contents of this [`Document`], returning the new layer entities in the
+    /// order they appear in [`Document::layers`].
+    pub fn load_into(
+        &self,
+        world: &mut World,
+        viewport: Entity,
+    ) -> Result<Vec<Entity>, DocumentError> {
+        // check the references before we start destroying things
+        let known_layers: Vec<usize> = self.layers.iter().map(|l| l.id).collect();
+        if let Some(bad) = self
+            .objects
+            .iter()
+            .find(|obj| !known_layers.contains(&obj.layer))
+        {
+            return Err(DocumentError::UnknownLayer(bad.layer));
+        }
+
+        clear_user_entities(world);
+
+        let mut layer_entities = HashMap::new();
+        let mut created_layers = Vec::new();
+
+        for layer_doc in &self.layers {
+            let mut builder = world.create_entity();
+            if let Some(style) = &layer_doc.point_style {
+                builder = builder.with(PointStyle::from(style));
+            }
+            if let Some(style) = &layer_doc.line_style {
+                builder = builder.with(LineStyle::from(style));
+            }
+
+            let entity = Layer::create(
+                builder,
+                Name::new(layer_doc.name.clone()),
+                Layer {
+                    z_level: layer_doc.z_level,
+                    visible: layer_doc.visible,
+                    ..Layer::default()
+                },
+            );
+            layer_entities.insert(layer_doc.id, entity);
+            created_layers.push(entity);
+        }
+
+        for object in &self.objects {
+            let layer = layer_entities[&object.layer];
+            let mut builder = world.create_entity();
+            if let Some(geometry) = object.geometry.to_geometry() {
+                builder = builder.with(DrawingObject { geometry, layer });
+            }
+            if let Some(geometry) = object.geometry.to_shape() {
+                builder = builder.with(Shape { geometry, layer });
+            }
+            if let Some(style) = &object.point_style {
+                builder = builder.with(PointStyle::from(style));
+            }
+            if let Some(style) = &object.line_style {
+                builder = builder.with(LineStyle::from(style));
+            }
+            builder.build();
+        }
+
+        if let Some(vp) = world.write_storage::<Viewport>().get_mut(viewport) {
+            let centre: Point = self.viewport.centre.into();
+            if centre.x.is_finite() && centre.y.is_finite() {
+                vp.centre = centre;
+            }
+            vp.pixels_per_drawing_unit =
+                Scale::new(sanitise_scale(self.viewport.pixels_per_drawing_unit));
+        }
+
+        Ok(created_layers)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Serializing a Document can't fail")
+    }
+
+    /// Parse a [`Document`], upgrading it from older versions if necessary.
+    pub fn from_json(json: &str) -> Result<Document, DocumentError> {
+        let value: Value = serde_json::from_str(json)?;
+        let value = migrate(value)?;
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// The zoom level from a file, clamped to something the viewport can cope
+/// with. Nonsense values (zero, negative or NaN) give the default zoom.
+fn sanitise_scale(pixels_per_drawing_unit: f64) -> f64 {
+    if pixels_per_drawing_unit.is_finite() && pixels_per_drawing_unit > 0.0 {
+        pixels_per_drawing_unit.clamp(MIN_PIXELS_PER_DRAWING_UNIT, MAX_PIXELS_PER_DRAWING_UNIT)
+    } else {
+        1.0
+    }
+}
+
+/// Run a JSON document through each of the [`MIGRATIONS`] needed to bring it
+/// up to [`CURRENT_VERSION`].
+fn migrate(mut value: Value) -> Result<Value, DocumentError> {
+    let mut version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(DocumentError::MissingVersion)?;
+
+    if version == 0 || version > u64::from(CURRENT_VERSION) {
+        return Err(DocumentError::UnsupportedVersion(version));
+    }
+
+    while version < u64::from(CURRENT_VERSION) {
+        let upgrade = MIGRATIONS[version as usize - 1];
+        value = upgrade(value)?;
+        version += 1;
+        value["version"] = Value::from(version);
+    }
+
+    Ok(value)
+}
+
+/// Version 2 only added polylines and ellipses, so any version 1 document is
+/// already valid.
+fn version_1_to_2(value: Value) -> Result<Value, DocumentError> {
+    Ok(value)
+}
+
+fn is_system(layer: &Layer) -> bool {
+    matches!(layer.layer_type, LayerType::System)
+}
+
+/// Delete every non-system [`Layer`] and the objects on it.
+fn clear_user_entities(world: &mut World) {
+    let doomed: Vec<Entity> = {
+        let entities = world.entities();
+        let layers = world.read_storage::<Layer>();
+        let drawing_objects = world.read_storage::<DrawingObject>();
+        let shapes = world.read_storage::<Shape>();
+
+        let user_layer = |entity: Entity| layers.get(entity).map_or(false, |l| !is_system(l));
+
+        let user_layers = (&entities, &layers)
+            .join()
+            .map(|(entity, _)| entity)
+            .filter(|&entity| user_layer(entity));
+        let user_objects = (&entities, &drawing_objects)
+            .join()
+            .filter(|(_, obj)| user_layer(obj.layer))
+            .map(|(entity, _)| entity);
+        let user_shapes = (&entities, &shapes)
+            .join()
+            .filter(|(_, shape)| user_layer(shape.layer))
+            .map(|(entity, _)| entity);
+
+        user_layers.chain(user_objects).chain(user_shapes).collect()
+    };
+
+    if let Err(e) = world.delete_entities(&doomed) {
+        log::warn!("Unable to clear the drawing: {}", e);
+    }
+}
+
+impl From<Point> for PointDoc {
+    fn from(p: Point) -> PointDoc {
+        PointDoc { x: p.x, y: p.y }
+    }
+}
+
+impl From<PointDoc> for Point {
+    fn from(p: PointDoc) -> Point {
+        Point::new(p.x, p.y)
+    }
+}
+
+impl From<&Viewport> for ViewportDoc {
+    fn from(viewport: &Viewport) -> ViewportDoc {
+        ViewportDoc {
+            centre: viewport.centre.into(),
+            pixels_per_drawing_unit: viewport.pixels_per_drawing_unit.get(),
+        }
+    }
+}
+
+impl GeometryDoc {
+    pub fn from_geometry(geometry: &Geometry) -> Option<GeometryDoc> {
+        match geometry {
+            Geometry::Point(p) => Some(GeometryDoc::Point {
+                location: (*p).into(),
+            }),
+            Geometry::Line(line) => Some(GeometryDoc::Line {
+                start: line.start.into(),
+                end: line.end.into(),
+            }),
+            Geometry::Arc(arc) => Some(GeometryDoc::Arc {
+                centre: arc.centre().into(),
+                radius: arc.radius(),
+                start_angle: arc.start_angle().radians,
+                sweep_angle: arc.sweep_angle().radians,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_shape(geometry: &ShapeGeometry) -> GeometryDoc {
+        match geometry {
+            ShapeGeometry::Polyline(polyline) => GeometryDoc::Polyline {
+                vertices: polyline.vertices.iter().map(|&v| v.into()).collect(),
+                closed: polyline.closed,
+            },
+            ShapeGeometry::Ellipse(ellipse) => GeometryDoc::Ellipse {
+                centre: ellipse.centre.into(),
+                major_axis: PointDoc {
+                    x: ellipse.major_axis.x,
+                    y: ellipse.major_axis.y,
+                },
+                ratio: ellipse.ratio,
+                start_parameter: ellipse.start_parameter.radians,
+                end_parameter: ellipse.end_parameter.radians,
+            },
+        }
+    }
+
+    /// The [`Geometry`] for a [`DrawingObject`], or `None` if this needs to
+    /// be a [`Shape`] (see [`GeometryDoc::to_shape()`]).
+    pub fn to_geometry(&self) -> Option<Geometry> {
+        match *self {
+            GeometryDoc::Point { location } => Some(Geometry::Point(location.into())),
+            GeometryDoc::Line { start, end } => {
+                Some(Geometry::Line(Line::new(start.into(), end.into())))
+            }
+            GeometryDoc::Arc {
+                centre,
+                radius,
+                start_angle,
+                sweep_angle,
+            } => Some(Geometry::Arc(Arc::from_centre_radius(
+                centre.into(),
+                radius,
+                Angle::radians(start_angle),
+                Angle::radians(sweep_angle),
+            ))),
+            GeometryDoc::Polyline { .. } | GeometryDoc::Ellipse { .. } => None,
+        }
+    }
+
+    /// The [`ShapeGeometry`] for a [`Shape`], or `None` if this can be a
+    /// normal [`DrawingObject`].
+    pub fn to_shape(&self) -> Option<ShapeGeometry> {
+        match self {
+            GeometryDoc::Polyline { vertices, closed } => Some(ShapeGeometry::Polyline(
+                Polyline::new(vertices.iter().map(|&v| v.into()).collect(), *closed),
+            )),
+            GeometryDoc::Ellipse {
+                centre,
+                major_axis,
+                ratio,
+                start_parameter,
+                end_parameter,
+            } => Some(ShapeGeometry::Ellipse(
+                Ellipse::new(
+                    (*centre).into(),
+                    Vector::new(major_axis.x, major_axis.y),
+                    *ratio,
+                )
+                .with_parameters(
+                    Angle::radians(*start_parameter),
+                    Angle::radians(*end_parameter),
+                ),
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        match (self.to_geometry(), self.to_shape()) {
+            (Some(geometry), _) => geometry::bounding_box(&geometry),
+            (_, Some(shape)) => shape.bounding_box(),
+            (None, None) => None,
+        }
+    }
+}
+
+impl From<&Dimension> for DimensionDoc {
+    fn from(dimension: &Dimension) -> DimensionDoc {
+        match dimension {
+            Dimension::Pixels(px) => DimensionDoc::Pixels(*px),
+            Dimension::DrawingUnits(length) => DimensionDoc::DrawingUnits(length.get()),
+        }
+    }
+}
+
+impl From<DimensionDoc> for Dimension {
+    fn from(dimension: DimensionDoc) -> Dimension {
+        match dimension {
+            DimensionDoc::Pixels(px) => Dimension::Pixels(px),
+            DimensionDoc::DrawingUnits(units) => Dimension::DrawingUnits(Length::new(units)),
+        }
+    }
+}
+
+impl From<&PointStyle> for PointStyleDoc {
+    fn from(style: &PointStyle) -> PointStyleDoc {
+        PointStyleDoc {
+            colour: colour_to_hex(&style.colour),
+            radius: DimensionDoc::from(&style.radius),
+        }
+    }
+}
+
+impl From<&PointStyleDoc> for PointStyle {
+    fn from(style: &PointStyleDoc) -> PointStyle {
+        let default = PointStyle::default();
+
+        PointStyle {
+            colour: colour_from_hex(&style.colour).unwrap_or(default.colour),
+            radius: style.radius.into(),
+        }
+    }
+}
+
+impl From<&LineStyle> for LineStyleDoc {
+    fn from(style: &LineStyle) -> LineStyleDoc {
+        LineStyleDoc {
+            stroke: colour_to_hex(&style.stroke),
+            width: DimensionDoc::from(&style.width),
+        }
+    }
+}
+
+impl From<&LineStyleDoc> for LineStyle {
+    fn from(style: &LineStyleDoc) -> LineStyle {
+        let default = LineStyle::default();
+
+        LineStyle {
+            stroke: colour_from_hex(&style.stroke).unwrap_or(default.stroke),
+            width: style.width.into(),
+        }
+    }
+}
+
+/// Format a [`Color`] as `#rrggbbaa`.
+pub fn colour_to_hex(colour: &Color) -> String {
+    format!("#{:08x}", colour.as_rgba_u32())
+}
+
+/// Parse a `#rrggbb` or `#rrggbbaa` colour.
+pub fn colour_from_hex(hex: &str) -> Option<Color> {
+    let digits = hex.strip_prefix('#')?;
+    let value = u32::from_str_radix(digits, 16).ok()?;
+
+    match digits.len() {
+        6 => Some(Color::from_rgba32_u32((value << 8) | 0xff)),
+        8 => Some(Color::from_rgba32_u32(value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arcs::components::Viewport;
+
+    fn empty_world() -> (World, Entity) {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let viewport = world
+            .create_entity()
+            .with(Viewport {
+                centre: Point::new(10.0, -5.0),
+                pixels_per_drawing_unit: Scale::new(2.5),
+            })
+            .build();
+
+        (world, viewport)
+    }
+
+    #[test]
+    fn round_trip_a_drawing() {
+        let (mut world, viewport) = empty_world();
+        let layer = Layer::create(
+            world.create_entity().with(LineStyle {
+                stroke: Color::rgb8(0x12, 0x34, 0x56),
+                width: Dimension::Pixels(2.0),
+            }),
+            Name::new("walls"),
+            Layer {
+                z_level: 3,
+                ..Layer::default()
+            },
+        );
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(Point::new(0.0, 0.0), Point::new(1.0, 1.0))),
+                layer,
+            })
+            .build();
+
+        let original = Document::from_world(&world, viewport);
+        let json = original.to_json();
+        let (mut other_world, other_viewport) = empty_world();
+        let layers = Document::from_json(&json)
+            .unwrap()
+            .load_into(&mut other_world, other_viewport)
+            .unwrap();
+        let round_tripped = Document::from_world(&other_world, other_viewport);
+
+        assert_eq!(round_tripped, original);
+        assert_eq!(layers.len(), 1);
+        let drawing_objects = other_world.read_storage::<DrawingObject>();
+        let obj = (&drawing_objects).join().next().unwrap();
+        assert_eq!(obj.layer, layers[0]);
+    }
+
+    #[test]
+    fn round_trip_a_polyline() {
+        let (mut world, viewport) = empty_world();
+        let layer = Layer::create(world.create_entity(), Name::new("walls"), Layer::default());
+        let geometry = ShapeGeometry::Polyline(Polyline::new(
+            vec![
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 5.0),
+            ],
+            true,
+        ));
+        let shape = world
+            .create_entity()
+            .with(Shape {
+                geometry: geometry.clone(),
+                layer,
+            })
+            .build();
+        // the lines which draw it mustn't be saved as well
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(Point::new(0.0, 0.0), Point::new(10.0, 0.0))),
+                layer,
+            })
+            .with(ShapePart { shape })
+            .build();
+
+        let original = Document::from_world(&world, viewport);
+        let (mut other_world, other_viewport) = empty_world();
+        Document::from_json(&original.to_json())
+            .unwrap()
+            .load_into(&mut other_world, other_viewport)
+            .unwrap();
+
+        assert_eq!(original.objects.len(), 1);
+        assert_eq!(Document::from_world(&other_world, other_viewport), original);
+        let shapes = other_world.read_storage::<Shape>();
+        let loaded = (&shapes).join().next().unwrap();
+        assert_eq!(loaded.geometry, geometry);
+        assert_eq!(
+            other_world.read_storage::<DrawingObject>().join().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn previews_are_not_saved() {
+        let (mut world, viewport) = empty_world();
+        let layer = Layer::create(world.create_entity(), Name::new("walls"), Layer::default());
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(Point::new(0.0, 0.0), Point::new(1.0, 1.0))),
+                layer,
+            })
+            .build();
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(Point::new(1.0, 1.0), Point::new(2.0, 0.0))),
+                layer,
+            })
+            .with(Preview)
+            .build();
+        world
+            .create_entity()
+            .with(Shape {
+                geometry: ShapeGeometry::Polyline(Polyline::new(
+                    vec![Point::new(0.0, 0.0), Point::new(5.0, 5.0)],
+                    false,
+                )),
+                layer,
+            })
+            .with(Preview)
+            .build();
+
+        let doc = Document::from_world(&world, viewport);
+
+        assert_eq!(doc.objects.len(), 1);
+        assert_eq!(
+            doc.objects[0].geometry,
+            GeometryDoc::Line {
+                start: Point::new(0.0, 0.0).into(),
+                end: Point::new(1.0, 1.0).into(),
+            }
+        );
+    }
+
+    #[test]
+    fn elliptical_arcs_keep_their_parameters() {
+        let arc = Ellipse::new(Point::new(1.0, 2.0), Vector::new(3.0, 4.0), 0.25)
+            .with_parameters(Angle::frac_pi_2(), Angle::pi());
+        let geometry = ShapeGeometry::Ellipse(arc);
+
+        let doc = GeometryDoc::from_shape(&geometry);
+
+        assert_eq!(doc.to_shape(), Some(geometry));
+        assert_eq!(doc.to_geometry(), None);
+    }
+
+    #[test]
+    fn version_1_documents_are_upgraded() {
+        let json = r#"{
+            "version": 1,
+            "viewport": {"centre": {"x": 0.0, "y": 0.0}, "pixels_per_drawing_unit": 1.0},
+            "layers": [{"id": 0, "name": "default", "z_level": 0, "visible": true}],
+            "objects": [
+                {"layer": 0, "geometry": {"type": "point", "location": {"x": 1.0, "y": 2.0}}}
+            ]
+        }"#;
+
+        let got = Document::from_json(json).unwrap();
+
+        assert_eq!(got.version, CURRENT_VERSION);
+        assert_eq!(got.objects.len(), 1);
+    }
+
+    #[test]
+    fn documents_from_the_future_are_rejected() {
+        let json = r#"{"version": 999, "viewport": {}, "layers": [], "objects": []}"#;
+
+        let got = Document::from_json(json);
+
+        assert!(matches!(got, Err(DocumentError::UnsupportedVersion(999))));
+    }
+
+    #[test]
+    fn objects_must_refer_to_a_known_layer() {
+        let (mut world, viewport) = empty_world();
+        let doc = Document {
+            version: CURRENT_VERSION,
+            viewport: ViewportDoc {
+                centre: PointDoc { x: 0.0, y: 0.0 },
+                pixels_per_drawing_unit: 1.0,
+            },
+            layers: Vec::new(),
+            objects: vec![ObjectDoc {
+                layer: 7,
+                geometry: GeometryDoc::Point {
+                    location: PointDoc { x: 0.0, y: 0.0 },
+                },
+                point_style: None,
+                line_style: None,
+            }],
+        };
+
+        let got = doc.load_into(&mut world, viewport);
+
+        assert!(matches!(got, Err(DocumentError::UnknownLayer(7))));
+    }
+
+    #[test]
+    fn nonsense_zoom_levels_are_fixed_on_load() {
+        let inputs = vec![
+            (0.0, 1.0),
+            (-3.0, 1.0),
+            (f64::NAN, 1.0),
+            (f64::INFINITY, 1.0),
+            (1e9, MAX_PIXELS_PER_DRAWING_UNIT),
+            (1e-9, MIN_PIXELS_PER_DRAWING_UNIT),
+            (2.5, 2.5),
+        ];
+
+        for (input, expected) in inputs {
+            assert_eq!(sanitise_scale(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_hex_colours() {
+        let colour = colour_from_hex("#ff8000").unwrap();
+
+        assert_eq!(colour.as_rgba_u32(), 0xff8000ff);
+        assert_eq!(colour_to_hex(&colour), "#ff8000ff");
+        assert!(colour_from_hex("ff8000").is_none());
+    }
+}
@@ -0,0 +1,1135 @@
+//! Reading and writing ASCII DXF files.
+//!
+//! DXF is converted to and from a [`Document`] so loading a DXF file goes
+//! through exactly the same path as opening one of our own drawings. Only
+//! the `POINT`, `LINE`, `ARC`, `CIRCLE`, `POLYLINE` and `ELLIPSE` entities
+//! are understood, anything else is skipped with a warning. Circles are read
+//! as arcs which sweep all the way around, and those arcs are written back
+//! as circles. Polylines are always treated as straight segments, so any
+//! bulges are ignored.
+//!
+//! Files are written as AutoCAD R12 (`AC1009`), which most programs can
+//! read. R12 has no `ELLIPSE` entity, so ellipses are written as the
+//! polylines they are drawn with. R12 also predates true colour (group code
+//! 420), so colours are written as the nearest AutoCAD Colour Index (group
+//! code 62) and the exact colour is kept as extended data under the
+//! [`APP_NAME`] application. DXF doesn't have the concept of a z-order
+//! either, so layers are written from back to front and the exact `z_level`
+//! is stored as extended data too. True colours from newer files are still
+//! understood when reading.
+//!
+//! Colour 7 is "foreground", white on a dark background and black on a light
+//! one. Our canvas is light, so it is treated as black.
+
+use crate::{
+    document::{
+        colour_from_hex, DimensionDoc, Document, DocumentError, GeometryDoc, LayerDoc,
+        LineStyleDoc, ObjectDoc, PointDoc, PointStyleDoc, ViewportDoc, CURRENT_VERSION,
+    },
+    shapes::ShapeGeometry,
+};
+use std::{collections::HashMap, fmt, fmt::Write, iter::Peekable};
+
+/// The registered application name used for our extended data.
+pub const APP_NAME: &str = "ARCS";
+
+/// A handful of the AutoCAD Colour Index entries and their RGB values.
+const ACI_COLOURS: &[(i32, u32)] = &[
+    (1, 0xff0000),
+    (2, 0xffff00),
+    (3, 0x00ff00),
+    (4, 0x00ffff),
+    (5, 0x0000ff),
+    (6, 0xff00ff),
+    // the foreground colour, see the module docs
+    (7, 0x000000),
+    (8, 0x808080),
+    (9, 0xc0c0c0),
+];
+
+const DEFAULT_ACI: i32 = 7;
+const ACI_BY_LAYER: i32 = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DxfError {
+    /// The file ended part way through a group.
+    UnexpectedEof,
+    InvalidGroupCode {
+        line: usize,
+        text: String,
+    },
+    InvalidValue {
+        line: usize,
+        code: i32,
+        text: String,
+    },
+    /// A required group code was missing from an entity.
+    MissingGroup {
+        line: usize,
+        entity: String,
+        code: i32,
+    },
+}
+
+impl fmt::Display for DxfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DxfError::UnexpectedEof => write!(f, "The file ended unexpectedly"),
+            DxfError::InvalidGroupCode { line, text } => {
+                write!(f, "Invalid group code \"{}\" on line {}", text, line)
+            }
+            DxfError::InvalidValue { line, code, text } => write!(
+                f,
+                "Invalid value \"{}\" for group code {} on line {}",
+                text, code, line
+            ),
+            DxfError::MissingGroup { line, entity, code } => write!(
+                f,
+                "The {} on line {} is missing group code {}",
+                entity, line, code
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DxfError {}
+
+///////////////////////////////////////////////////////
+// Writing
+/// Serialize a [`Document`] as an ASCII DXF file.
+pub fn write(document: &Document) -> Result<String, DocumentError> {
+    let mut writer = Writer::default();
+
+    writer.pair(0, "SECTION");
+    writer.pair(2, "HEADER");
+    writer.pair(9, "$ACADVER");
+    writer.pair(1, "AC1009");
+    writer.pair(0, "ENDSEC");
+
+    let layer_names = unique_layer_names(&document.layers);
+    let mut layers: Vec<&LayerDoc> = document.layers.iter().collect();
+    layers.sort_by_key(|layer| layer.z_level);
+
+    writer.pair(0, "SECTION");
+    writer.pair(2, "TABLES");
+    writer.pair(0, "TABLE");
+    writer.pair(2, "APPID");
+    writer.pair(70, 1);
+    writer.pair(0, "APPID");
+    writer.pair(2, APP_NAME);
+    writer.pair(70, 0);
+    writer.pair(0, "ENDTAB");
+    writer.pair(0, "TABLE");
+    writer.pair(2, "LAYER");
+    writer.pair(70, layers.len());
+
+    for layer in layers {
+        let rgb = layer_colour(layer);
+        let aci = rgb.map(nearest_aci).unwrap_or(DEFAULT_ACI);
+
+        writer.pair(0, "LAYER");
+        writer.pair(2, &layer_names[&layer.id]);
+        writer.pair(70, 0);
+        // a negative colour number means the layer is turned off
+        writer.pair(62, if layer.visible { aci } else { -aci });
+        writer.pair(6, "CONTINUOUS");
+        writer.pair(1001, APP_NAME);
+        writer.pair(1071, layer.z_level);
+        if let Some(rgb) = rgb {
+            writer.pair(1000, rgb_to_xdata(rgb));
+        }
+    }
+
+    writer.pair(0, "ENDTAB");
+    writer.pair(0, "ENDSEC");
+
+    writer.pair(0, "SECTION");
+    writer.pair(2, "ENTITIES");
+
+    for object in &document.objects {
+        let layer_name = layer_names
+            .get(&object.layer)
+            .ok_or(DocumentError::UnknownLayer(object.layer))?;
+
+        match object.geometry {
+            GeometryDoc::Point { location } => {
+                writer.pair(0, "POINT");
+                writer.pair(8, layer_name);
+                writer.entity_colour(object);
+                writer.point(10, location);
+            }
+            GeometryDoc::Line { start, end } => {
+                writer.pair(0, "LINE");
+                writer.pair(8, layer_name);
+                writer.entity_colour(object);
+                writer.point(10, start);
+                writer.point(11, end);
+            }
+            GeometryDoc::Arc {
+                centre,
+                radius,
+                start_angle,
+                sweep_angle,
+            } => {
+                // DXF arcs always go anticlockwise
+                let (start, sweep) = if sweep_angle < 0.0 {
+                    (start_angle + sweep_angle, -sweep_angle)
+                } else {
+                    (start_angle, sweep_angle)
+                };
+
+                let full_circle = sweep >= std::f64::consts::PI * 2.0;
+
+                writer.pair(0, if full_circle { "CIRCLE" } else { "ARC" });
+                writer.pair(8, layer_name);
+                writer.entity_colour(object);
+                writer.point(10, centre);
+                writer.pair(40, radius);
+                if !full_circle {
+                    writer.pair(50, start.to_degrees());
+                    writer.pair(51, (start + sweep).to_degrees());
+                }
+            }
+            GeometryDoc::Polyline {
+                ref vertices,
+                closed,
+            } => {
+                writer.polyline(layer_name, object, vertices, closed);
+                continue;
+            }
+            GeometryDoc::Ellipse { .. } => {
+                if let Some(ShapeGeometry::Ellipse(ellipse)) = object.geometry.to_shape() {
+                    let closed = ellipse.is_closed();
+                    let mut vertices: Vec<PointDoc> =
+                        ellipse.vertices().into_iter().map(PointDoc::from).collect();
+                    if closed {
+                        // the polyline gets back to the start by itself
+                        vertices.pop();
+                    }
+                    writer.polyline(layer_name, object, &vertices, closed);
+                }
+                continue;
+            }
+        }
+
+        writer.colour_xdata(object);
+    }
+
+    writer.pair(0, "ENDSEC");
+    writer.pair(0, "EOF");
+
+    Ok(writer.buffer)
+}
+
+#[derive(Debug, Default)]
+struct Writer {
+    buffer: String,
+}
+
+impl Writer {
+    fn pair<V: fmt::Display>(&mut self, code: i32, value: V) {
+        writeln!(self.buffer, "{:>3}\n{}", code, value).unwrap();
+    }
+
+    fn point(&mut self, code: i32, point: PointDoc) {
+        self.pair(code, point.x);
+        self.pair(code + 10, point.y);
+        self.pair(code + 20, 0.0);
+    }
+
+    /// The nearest colour index. The exact colour goes in the extended data
+    /// at the end of the entity.
+    fn entity_colour(&mut self, object: &ObjectDoc) {
+        if let Some(rgb) = object_colour(object) {
+            self.pair(62, nearest_aci(rgb));
+        }
+    }
+
+    /// A `POLYLINE` followed by its `VERTEX` records and a `SEQEND`.
+    fn polyline(
+        &mut self,
+        layer_name: &str,
+        object: &ObjectDoc,
+        vertices: &[PointDoc],
+        closed: bool,
+    ) {
+        self.pair(0, "POLYLINE");
+        self.pair(8, layer_name);
+        self.entity_colour(object);
+        // "vertices follow"
+        self.pair(66, 1);
+        self.point(10, PointDoc { x: 0.0, y: 0.0 });
+        self.pair(70, closed as u8);
+        // the extended data belongs to the POLYLINE, not the SEQEND
+        self.colour_xdata(object);
+
+        for &vertex in vertices {
+            self.pair(0, "VERTEX");
+            self.pair(8, layer_name);
+            self.point(10, vertex);
+        }
+
+        self.pair(0, "SEQEND");
+        self.pair(8, layer_name);
+    }
+
+    /// The exact colour, which has to come after everything else.
+    fn colour_xdata(&mut self, object: &ObjectDoc) {
+        if let Some(rgb) = object_colour(object) {
+            self.pair(1001, APP_NAME);
+            self.pair(1000, rgb_to_xdata(rgb));
+        }
+    }
+}
+
+/// DXF needs each layer to have a unique, non-empty name.
+fn unique_layer_names(layers: &[LayerDoc]) -> HashMap<usize, String> {
+    let mut names = HashMap::new();
+    let mut taken = Vec::new();
+
+    for layer in layers {
+        let mut name = if layer.name.is_empty() {
+            format!("LAYER_{}", layer.id)
+        } else {
+            layer.name.clone()
+        };
+
+        if taken.contains(&name) {
+            name = format!("{}_{}", name, layer.id);
+        }
+
+        taken.push(name.clone());
+        names.insert(layer.id, name);
+    }
+
+    names
+}
+
+fn layer_colour(layer: &LayerDoc) -> Option<u32> {
+    layer
+        .line_style
+        .as_ref()
+        .map(|s| s.stroke.as_str())
+        .or_else(|| layer.point_style.as_ref().map(|s| s.colour.as_str()))
+        .and_then(hex_to_rgb)
+}
+
+fn object_colour(object: &ObjectDoc) -> Option<u32> {
+    object
+        .line_style
+        .as_ref()
+        .map(|s| s.stroke.as_str())
+        .or_else(|| object.point_style.as_ref().map(|s| s.colour.as_str()))
+        .and_then(hex_to_rgb)
+}
+
+fn hex_to_rgb(hex: &str) -> Option<u32> {
+    colour_from_hex(hex).map(|colour| colour.as_rgba_u32() >> 8)
+}
+
+fn rgb_to_hex(rgb: u32) -> String {
+    format!("#{:06x}ff", rgb & 0xff_ffff)
+}
+
+/// The exact colour, as stored in our extended data.
+fn rgb_to_xdata(rgb: u32) -> String {
+    format!("#{:06x}", rgb & 0xff_ffff)
+}
+
+fn nearest_aci(rgb: u32) -> i32 {
+    let channels = |c: u32| [(c >> 16) & 0xff, (c >> 8) & 0xff, c & 0xff];
+    let target = channels(rgb);
+
+    ACI_COLOURS
+        .iter()
+        .min_by_key(|(_, candidate)| {
+            channels(*candidate)
+                .iter()
+                .zip(target.iter())
+                .map(|(&a, &b)| (i64::from(a) - i64::from(b)).pow(2))
+                .sum::<i64>()
+        })
+        .map(|(aci, _)| *aci)
+        .unwrap_or(DEFAULT_ACI)
+}
+
+fn aci_to_rgb(aci: i32) -> Option<u32> {
+    ACI_COLOURS
+        .iter()
+        .find(|(index, _)| *index == aci)
+        .map(|(_, rgb)| *rgb)
+}
+
+///////////////////////////////////////////////////////
+// Reading
+/// Parse an ASCII DXF file.
+pub fn read(text: &str) -> Result<Document, DxfError> {
+    let pairs = parse_pairs(text)?;
+    let mut layers: Vec<LayerDoc> = Vec::new();
+    let mut layer_ids: HashMap<String, usize> = HashMap::new();
+    let mut objects = Vec::new();
+
+    for section in sections(&pairs)? {
+        match section.name {
+            "TABLES" => {
+                for record in section.records.iter().filter(|r| r.kind == "LAYER") {
+                    let layer = read_layer(record, layers.len())?;
+                    layer_ids.insert(layer.name.clone(), layer.id);
+                    layers.push(layer);
+                }
+            }
+            "ENTITIES" => {
+                let mut records = section.records.iter().peekable();
+
+                while let Some(record) = records.next() {
+                    let geometry = match record.kind {
+                        "POLYLINE" => read_polyline(record, &mut records)?,
+                        _ => read_geometry(record)?,
+                    };
+                    let geometry = match geometry {
+                        Some(g) => g,
+                        None => {
+                            log::warn!("Skipping unsupported DXF entity: {}", record.kind);
+                            continue;
+                        }
+                    };
+
+                    let layer_name = record.text(8).unwrap_or("0").to_string();
+                    let layer = *layer_ids.entry(layer_name.clone()).or_insert_with(|| {
+                        let id = layers.len();
+                        layers.push(default_layer(id, layer_name));
+                        id
+                    });
+
+                    objects.push(object_with_colour(layer, geometry, record.colour()?));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Document {
+        version: CURRENT_VERSION,
+        viewport: ViewportDoc {
+            centre: PointDoc { x: 0.0, y: 0.0 },
+            pixels_per_drawing_unit: 1.0,
+        },
+        layers,
+        objects,
+    })
+}
+
+fn default_layer(id: usize, name: String) -> LayerDoc {
+    LayerDoc {
+        id,
+        name,
+        z_level: id,
+        visible: true,
+        point_style: None,
+        line_style: None,
+    }
+}
+
+fn read_layer(record: &Record<'_>, id: usize) -> Result<LayerDoc, DxfError> {
+    let name = record.text(2).ok_or_else(|| record.missing(2))?.to_string();
+    let flags = record.integer(70)?.unwrap_or(0);
+    let aci = record.integer(62)?.unwrap_or(DEFAULT_ACI);
+    let frozen = flags & 1 != 0;
+    let z_level = match record.xdata_integer(APP_NAME, 1071)? {
+        Some(z) if z >= 0 => z as usize,
+        _ => id,
+    };
+
+    let mut layer = LayerDoc {
+        visible: aci >= 0 && !frozen,
+        z_level,
+        ..default_layer(id, name)
+    };
+
+    if let Some(rgb) = record.colour()?.or_else(|| aci_to_rgb(aci.abs())) {
+        layer.line_style = Some(LineStyleDoc {
+            stroke: rgb_to_hex(rgb),
+            width: DimensionDoc::Pixels(1.0),
+        });
+        layer.point_style = Some(PointStyleDoc {
+            colour: rgb_to_hex(rgb),
+            radius: DimensionDoc::Pixels(3.0),
+        });
+    }
+
+    Ok(layer)
+}
+
+fn read_geometry(record: &Record<'_>) -> Result<Option<GeometryDoc>, DxfError> {
+    let geometry = match record.kind {
+        "POINT" => GeometryDoc::Point {
+            location: record.point(10)?,
+        },
+        "LINE" => GeometryDoc::Line {
+            start: record.point(10)?,
+            end: record.point(11)?,
+        },
+        "ARC" => {
+            let start = record.required_number(50)?.to_radians();
+            let end = record.required_number(51)?.to_radians();
+            let mut sweep = (end - start).rem_euclid(std::f64::consts::PI * 2.0);
+            if sweep == 0.0 {
+                sweep = std::f64::consts::PI * 2.0;
+            }
+
+            GeometryDoc::Arc {
+                centre: record.point(10)?,
+                radius: record.required_number(40)?,
+                start_angle: start,
+                sweep_angle: sweep,
+            }
+        }
+        "CIRCLE" => GeometryDoc::Arc {
+            centre: record.point(10)?,
+            radius: record.required_number(40)?,
+            start_angle: 0.0,
+            sweep_angle: std::f64::consts::PI * 2.0,
+        },
+        "ELLIPSE" => GeometryDoc::Ellipse {
+            centre: record.point(10)?,
+            major_axis: record.point(11)?,
+            ratio: record.required_number(40)?,
+            start_parameter: record.number(41)?.unwrap_or(0.0),
+            end_parameter: record.number(42)?.unwrap_or(std::f64::consts::PI * 2.0),
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(geometry))
+}
+
+/// Read a `POLYLINE` and the `VERTEX` records which follow it, up to and
+/// including the `SEQEND`.
+fn read_polyline<'r, 'a: 'r, I>(
+    record: &Record<'a>,
+    records: &mut Peekable<I>,
+) -> Result<Option<GeometryDoc>, DxfError>
+where
+    I: Iterator<Item = &'r Record<'a>>,
+{
+    let flags = record.integer(70)?.unwrap_or(0);
+    let mut vertices = Vec::new();
+
+    while let Some(vertex) = records.next_if(|r| r.kind == "VERTEX") {
+        vertices.push(vertex.point(10)?);
+    }
+    records.next_if(|r| r.kind == "SEQEND");
+
+    // polyface meshes and polygon meshes aren't really polylines
+    if flags & (16 | 64) != 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(GeometryDoc::Polyline {
+        vertices,
+        closed: flags & 1 != 0,
+    }))
+}
+
+fn object_with_colour(layer: usize, geometry: GeometryDoc, rgb: Option<u32>) -> ObjectDoc {
+    let mut object = ObjectDoc {
+        layer,
+        geometry,
+        point_style: None,
+        line_style: None,
+    };
+
+    if let Some(rgb) = rgb {
+        match object.geometry {
+            GeometryDoc::Point { .. } => {
+                object.point_style = Some(PointStyleDoc {
+                    colour: rgb_to_hex(rgb),
+                    radius: DimensionDoc::Pixels(3.0),
+                })
+            }
+            _ => {
+                object.line_style = Some(LineStyleDoc {
+                    stroke: rgb_to_hex(rgb),
+                    width: DimensionDoc::Pixels(1.0),
+                })
+            }
+        }
+    }
+
+    object
+}
+
+/// A single group code/value pair.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Pair<'a> {
+    code: i32,
+    value: &'a str,
+    /// The line number the value was on.
+    line: usize,
+}
+
+fn parse_pairs(text: &str) -> Result<Vec<Pair<'_>>, DxfError> {
+    let mut lines = text.lines().enumerate();
+    let mut pairs = Vec::new();
+
+    while let Some((index, code)) = lines.next() {
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+
+        let code = code.parse().map_err(|_| DxfError::InvalidGroupCode {
+            line: index + 1,
+            text: code.to_string(),
+        })?;
+        let (index, value) = lines.next().ok_or(DxfError::UnexpectedEof)?;
+
+        pairs.push(Pair {
+            code,
+            value: value.trim(),
+            line: index + 1,
+        });
+    }
+
+    Ok(pairs)
+}
+
+#[derive(Debug)]
+struct Section<'a> {
+    name: &'a str,
+    records: Vec<Record<'a>>,
+}
+
+/// Everything from one `0` group code up to the next.
+#[derive(Debug)]
+struct Record<'a> {
+    kind: &'a str,
+    line: usize,
+    fields: &'a [Pair<'a>],
+}
+
+fn sections<'a>(pairs: &'a [Pair<'a>]) -> Result<Vec<Section<'a>>, DxfError> {
+    let mut sections = Vec::new();
+    let mut records = records(pairs).into_iter();
+
+    while let Some(record) = records.next() {
+        match record.kind {
+            "SECTION" => {
+                let name = record.text(2).ok_or_else(|| record.missing(2))?;
+                let contents = records
+                    .by_ref()
+                    .take_while(|r| r.kind != "ENDSEC")
+                    .collect();
+                sections.push(Section {
+                    name,
+                    records: contents,
+                });
+            }
+            "EOF" => break,
+            _ => {}
+        }
+    }
+
+    Ok(sections)
+}
+
+fn records<'a>(pairs: &'a [Pair<'a>]) -> Vec<Record<'a>> {
+    let starts: Vec<usize> = pairs
+        .iter()
+        .enumerate()
+        .filter(|(_, pair)| pair.code == 0)
+        .map(|(i, _)| i)
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let end = starts.get(n + 1).copied().unwrap_or(pairs.len());
+            Record {
+                kind: pairs[start].value,
+                line: pairs[start].line,
+                fields: &pairs[start + 1..end],
+            }
+        })
+        .collect()
+}
+
+impl<'a> Record<'a> {
+    /// The first value with a particular group code, ignoring extended data.
+    fn find(&self, code: i32) -> Option<&Pair<'a>> {
+        self.fields
+            .iter()
+            .take_while(|pair| pair.code != 1001)
+            .find(|pair| pair.code == code)
+    }
+
+    fn text(&self, code: i32) -> Option<&'a str> {
+        self.find(code).map(|pair| pair.value)
+    }
+
+    fn integer(&self, code: i32) -> Result<Option<i32>, DxfError> {
+        self.find(code).map(parse_value).transpose()
+    }
+
+    fn number(&self, code: i32) -> Result<Option<f64>, DxfError> {
+        self.find(code).map(parse_number).transpose()
+    }
+
+    fn required_number(&self, code: i32) -> Result<f64, DxfError> {
+        self.number(code)?.ok_or_else(|| self.missing(code))
+    }
+
+    fn point(&self, code: i32) -> Result<PointDoc, DxfError> {
+        Ok(PointDoc {
+            x: self.required_number(code)?,
+            y: self.required_number(code + 10)?,
+        })
+    }
+
+    /// The entity's true colour (from newer files or our own extended data),
+    /// or its colour index if it has one.
+    fn colour(&self) -> Result<Option<u32>, DxfError> {
+        if let Some(rgb) = self.find(420).map(parse_value::<u32>).transpose()? {
+            return Ok(Some(rgb));
+        }
+        if let Some(rgb) = self.xdata(APP_NAME).find(|pair| pair.code == 1000) {
+            return hex_to_rgb(rgb.value)
+                .map(Some)
+                .ok_or_else(|| invalid_value(rgb));
+        }
+
+        match self.integer(62)? {
+            Some(0) | Some(ACI_BY_LAYER) | None => Ok(None),
+            Some(aci) => Ok(aci_to_rgb(aci.abs())),
+        }
+    }
+
+    /// The extended data attached by an application.
+    fn xdata<'s>(&'s self, app: &'s str) -> impl Iterator<Item = &'s Pair<'a>> + 's {
+        self.fields
+            .iter()
+            .skip_while(move |pair| !(pair.code == 1001 && pair.value == app))
+            .skip(1)
+            .take_while(|pair| pair.code != 1001)
+    }
+
+    /// Look up an integer in the extended data attached by an application.
+    fn xdata_integer(&self, app: &str, code: i32) -> Result<Option<i32>, DxfError> {
+        self.xdata(app)
+            .find(|pair| pair.code == code)
+            .map(parse_value)
+            .transpose()
+    }
+
+    fn missing(&self, code: i32) -> DxfError {
+        DxfError::MissingGroup {
+            line: self.line,
+            entity: self.kind.to_string(),
+            code,
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(pair: &Pair<'_>) -> Result<T, DxfError> {
+    pair.value.parse().map_err(|_| invalid_value(pair))
+}
+
+/// Like [`parse_value()`], except `NaN` and infinity are rejected because
+/// nothing downstream can cope with them.
+fn parse_number(pair: &Pair<'_>) -> Result<f64, DxfError> {
+    let number: f64 = parse_value(pair)?;
+
+    if number.is_finite() {
+        Ok(number)
+    } else {
+        Err(invalid_value(pair))
+    }
+}
+
+fn invalid_value(pair: &Pair<'_>) -> DxfError {
+    DxfError::InvalidValue {
+        line: pair.line,
+        code: pair.code,
+        text: pair.value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: &str = include_str!("../tests/dxf/points.dxf");
+    const LINES_AND_ARCS: &str = include_str!("../tests/dxf/lines_and_arcs.dxf");
+    const LAYERS: &str = include_str!("../tests/dxf/layers.dxf");
+    const ENTITIES_ONLY: &str = include_str!("../tests/dxf/entities_only.dxf");
+    const CIRCLES: &str = include_str!("../tests/dxf/circles.dxf");
+
+    #[test]
+    fn read_points() {
+        let got = read(POINTS).unwrap();
+
+        assert_eq!(got.layers.len(), 1);
+        assert_eq!(got.layers[0].name, "0");
+        let locations: Vec<_> = got.objects.iter().map(|obj| &obj.geometry).collect();
+        assert_eq!(
+            locations,
+            vec![
+                &GeometryDoc::Point {
+                    location: PointDoc { x: 1.5, y: -2.0 }
+                },
+                &GeometryDoc::Point {
+                    location: PointDoc { x: 10.0, y: 20.0 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_lines_and_arcs() {
+        let got = read(LINES_AND_ARCS).unwrap();
+
+        assert_eq!(got.objects.len(), 3);
+        assert_eq!(
+            got.objects[0].geometry,
+            GeometryDoc::Line {
+                start: PointDoc { x: 0.0, y: 0.0 },
+                end: PointDoc { x: 100.0, y: 50.0 },
+            }
+        );
+        match got.objects[1].geometry {
+            GeometryDoc::Arc {
+                centre,
+                radius,
+                start_angle,
+                sweep_angle,
+            } => {
+                assert_eq!(centre, PointDoc { x: 50.0, y: 50.0 });
+                assert_eq!(radius, 25.0);
+                assert!((start_angle - 0.0).abs() < 1e-9);
+                assert!((sweep_angle - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+            }
+            ref other => panic!("Expected an arc, found {:?}", other),
+        }
+        // arcs which cross 0° still sweep anticlockwise
+        match got.objects[2].geometry {
+            GeometryDoc::Arc { sweep_angle, .. } => {
+                assert!((sweep_angle - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+            }
+            ref other => panic!("Expected an arc, found {:?}", other),
+        }
+        // the red line
+        assert_eq!(
+            got.objects[0]
+                .line_style
+                .as_ref()
+                .map(|s| s.stroke.as_str()),
+            Some("#ff0000ff")
+        );
+    }
+
+    #[test]
+    fn circles_are_read_as_full_arcs() {
+        let got = read(CIRCLES).unwrap();
+
+        assert_eq!(got.objects.len(), 2);
+        assert_eq!(
+            got.objects[0].geometry,
+            GeometryDoc::Arc {
+                centre: PointDoc { x: 10.0, y: 20.0 },
+                radius: 5.0,
+                start_angle: 0.0,
+                sweep_angle: std::f64::consts::PI * 2.0,
+            }
+        );
+        // the blue circle
+        assert_eq!(
+            got.objects[1]
+                .line_style
+                .as_ref()
+                .map(|s| s.stroke.as_str()),
+            Some("#0000ffff")
+        );
+    }
+
+    #[test]
+    fn full_arcs_are_written_as_circles() {
+        let text = write(&read(CIRCLES).unwrap()).unwrap();
+
+        assert_eq!(text.matches("\nCIRCLE\n").count(), 2);
+        assert!(!text.contains("\nARC\n"));
+        let geometry = |doc: &Document| -> Vec<GeometryDoc> {
+            doc.objects.iter().map(|obj| obj.geometry.clone()).collect()
+        };
+        assert_eq!(
+            geometry(&read(&text).unwrap()),
+            geometry(&read(CIRCLES).unwrap())
+        );
+    }
+
+    #[test]
+    fn read_layer_names_colours_and_order() {
+        let got = read(LAYERS).unwrap();
+
+        let summary: Vec<_> = got
+            .layers
+            .iter()
+            .map(|l| {
+                (
+                    l.name.as_str(),
+                    l.z_level,
+                    l.visible,
+                    l.line_style.as_ref().map(|s| s.stroke.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("background", 0, true, Some("#0000ffff")),
+                ("walls", 5, true, Some("#123456ff")),
+                ("hidden", 2, false, Some("#00ff00ff")),
+            ]
+        );
+        assert_eq!(got.objects[0].layer, 1);
+        assert_eq!(got.objects[1].layer, 2);
+    }
+
+    #[test]
+    fn entities_without_a_tables_section_get_their_own_layers() {
+        let got = read(ENTITIES_ONLY).unwrap();
+
+        let names: Vec<_> = got.layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+        assert_eq!(got.objects.len(), 3);
+        assert_eq!(got.objects[2].layer, 0);
+    }
+
+    #[test]
+    fn round_trip_through_dxf() {
+        let original = read(LAYERS).unwrap();
+
+        let got = read(&write(&original).unwrap()).unwrap();
+
+        // layers come back in z-order
+        let mut expected = original.layers.clone();
+        expected.sort_by_key(|l| l.z_level);
+        let summarise = |layers: &[LayerDoc]| -> Vec<(String, usize, bool, Option<String>)> {
+            layers
+                .iter()
+                .map(|l| {
+                    (
+                        l.name.clone(),
+                        l.z_level,
+                        l.visible,
+                        l.line_style.as_ref().map(|s| s.stroke.clone()),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(summarise(&got.layers), summarise(&expected));
+
+        let geometry = |doc: &Document| -> Vec<GeometryDoc> {
+            doc.objects.iter().map(|obj| obj.geometry.clone()).collect()
+        };
+        assert_eq!(geometry(&got), geometry(&original));
+    }
+
+    #[test]
+    fn clockwise_arcs_are_written_anticlockwise() {
+        let doc = Document {
+            version: CURRENT_VERSION,
+            viewport: ViewportDoc {
+                centre: PointDoc { x: 0.0, y: 0.0 },
+                pixels_per_drawing_unit: 1.0,
+            },
+            layers: vec![default_layer(0, String::from("0"))],
+            objects: vec![ObjectDoc {
+                layer: 0,
+                geometry: GeometryDoc::Arc {
+                    centre: PointDoc { x: 0.0, y: 0.0 },
+                    radius: 1.0,
+                    start_angle: std::f64::consts::FRAC_PI_2,
+                    sweep_angle: -std::f64::consts::FRAC_PI_2,
+                },
+                point_style: None,
+                line_style: None,
+            }],
+        };
+
+        let got = read(&write(&doc).unwrap()).unwrap();
+
+        match got.objects[0].geometry {
+            GeometryDoc::Arc {
+                start_angle,
+                sweep_angle,
+                ..
+            } => {
+                assert!(start_angle.abs() < 1e-9);
+                assert!((sweep_angle - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+            }
+            ref other => panic!("Expected an arc, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn written_files_are_valid_r12() {
+        let text = write(&read(LAYERS).unwrap()).unwrap();
+
+        assert!(text.contains("$ACADVER\n  1\nAC1009\n"));
+        // true colour needs AC1018 or later
+        assert!(!text.lines().any(|line| line.trim() == "420"));
+    }
+
+    #[test]
+    fn colour_seven_is_the_foreground() {
+        // a layer with no colour gets the default colour index, 7
+        let text = "  0\nSECTION\n  2\nTABLES\n  0\nLAYER\n  2\nplain\n  0\nENDSEC\n";
+
+        let got = read(text).unwrap();
+
+        assert_eq!(
+            got.layers[0].line_style.as_ref().map(|s| s.stroke.as_str()),
+            Some("#000000ff")
+        );
+        assert_eq!(nearest_aci(0x000000), 7);
+    }
+
+    #[test]
+    fn objects_on_unknown_layers_are_an_error() {
+        let mut doc = read(POINTS).unwrap();
+        doc.objects[0].layer = 42;
+
+        let got = write(&doc);
+
+        assert!(matches!(got, Err(DocumentError::UnknownLayer(42))));
+    }
+
+    #[test]
+    fn non_finite_numbers_are_rejected() {
+        let text = "  0\nSECTION\n  2\nENTITIES\n  0\nPOINT\n 10\nNaN\n 20\n0\n  0\nENDSEC\n";
+
+        let got = read(text);
+
+        assert_eq!(
+            got.unwrap_err(),
+            DxfError::InvalidValue {
+                line: 8,
+                code: 10,
+                text: String::from("NaN")
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_files_are_an_error() {
+        let got = read("  0\nSECTION\n  2\n");
+
+        assert_eq!(got.unwrap_err(), DxfError::UnexpectedEof);
+    }
+
+    #[test]
+    fn garbage_group_codes_are_reported() {
+        let got = read("  0\nSECTION\nnot a number\nENTITIES\n");
+
+        assert_eq!(
+            got.unwrap_err(),
+            DxfError::InvalidGroupCode {
+                line: 3,
+                text: String::from("not a number")
+            }
+        );
+    }
+
+    #[test]
+    fn polylines_survive_a_round_trip() {
+        let polyline = |closed| ObjectDoc {
+            layer: 0,
+            geometry: GeometryDoc::Polyline {
+                vertices: vec![
+                    PointDoc { x: 0.0, y: 0.0 },
+                    PointDoc { x: 10.0, y: 0.0 },
+                    PointDoc { x: 10.0, y: 5.0 },
+                ],
+                closed,
+            },
+            point_style: None,
+            line_style: Some(LineStyleDoc {
+                stroke: String::from("#123456ff"),
+                width: DimensionDoc::Pixels(1.0),
+            }),
+        };
+        let doc = Document {
+            version: CURRENT_VERSION,
+            viewport: ViewportDoc {
+                centre: PointDoc { x: 0.0, y: 0.0 },
+                pixels_per_drawing_unit: 1.0,
+            },
+            layers: vec![default_layer(0, String::from("0"))],
+            objects: vec![polyline(true), polyline(false)],
+        };
+
+        let text = write(&doc).unwrap();
+        let got = read(&text).unwrap();
+
+        assert_eq!(text.matches("SEQEND").count(), 2);
+        assert_eq!(got.objects, doc.objects);
+    }
+
+    #[test]
+    fn read_an_ellipse() {
+        let text = concat!(
+            "  0\nSECTION\n  2\nENTITIES\n",
+            "  0\nELLIPSE\n  8\n0\n 10\n1\n 20\n2\n 30\n0\n 11\n4\n 21\n0\n 31\n0\n 40\n0.5\n",
+            "  0\nENDSEC\n",
+        );
+
+        let got = read(text).unwrap();
+
+        assert_eq!(
+            got.objects[0].geometry,
+            GeometryDoc::Ellipse {
+                centre: PointDoc { x: 1.0, y: 2.0 },
+                major_axis: PointDoc { x: 4.0, y: 0.0 },
+                ratio: 0.5,
+                start_parameter: 0.0,
+                end_parameter: std::f64::consts::PI * 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn ellipses_are_written_as_closed_polylines() {
+        let doc = Document {
+            version: CURRENT_VERSION,
+            viewport: ViewportDoc {
+                centre: PointDoc { x: 0.0, y: 0.0 },
+                pixels_per_drawing_unit: 1.0,
+            },
+            layers: vec![default_layer(0, String::from("0"))],
+            objects: vec![ObjectDoc {
+                layer: 0,
+                geometry: GeometryDoc::Ellipse {
+                    centre: PointDoc { x: 0.0, y: 0.0 },
+                    major_axis: PointDoc { x: 4.0, y: 0.0 },
+                    ratio: 0.5,
+                    start_parameter: 0.0,
+                    end_parameter: std::f64::consts::PI * 2.0,
+                },
+                point_style: None,
+                line_style: None,
+            }],
+        };
+
+        let got = read(&write(&doc).unwrap()).unwrap();
+
+        match &got.objects[0].geometry {
+            GeometryDoc::Polyline { vertices, closed } => {
+                assert!(closed);
+                assert_eq!(vertices.len(), crate::ellipse::SEGMENTS_PER_TURN);
+                assert_eq!(vertices[0], PointDoc { x: 4.0, y: 0.0 });
+            }
+            other => panic!("Expected a polyline, found {:?}", other),
+        }
+    }
+}
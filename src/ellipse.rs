@@ -0,0 +1,264 @@
+//! Ellipses and elliptical arcs.
+//!
+//! `arcs` has no ellipse variant in its [`Geometry`] enum and we can't add
+//! one from here, so an [`Ellipse`] is stored in a [`Shape`] and drawn as a
+//! chain of [`Line`]s (see [`Ellipse::approximate()`]). The queries mirror
+//! the ones in [`crate::geometry`] so it can become a proper variant upstream.
+//!
+//! [`Geometry`]: arcs::components::Geometry
+//! [`Shape`]: crate::shapes::Shape
+
+use crate::geometry::{self, BoundingBox};
+use arcs::{euclid::Angle, primitives::Line, Point, Vector};
+use std::f64::consts::PI;
+
+/// How many line segments a full ellipse is drawn with. Partial arcs get
+/// proportionally fewer.
+pub const SEGMENTS_PER_TURN: usize = 72;
+
+/// An ellipse, or part of one.
+///
+/// Points on the ellipse are found from a parameter, `t`, using
+/// `centre + major_axis * cos(t) + minor_axis * sin(t)`. An elliptical arc
+/// goes anticlockwise from `start_parameter` to `end_parameter`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ellipse {
+    pub centre: Point,
+    /// The vector from the centre to one end of the major axis.
+    pub major_axis: Vector,
+    /// The length of the minor axis relative to the major axis, in `(0, 1]`.
+    pub ratio: f64,
+    pub start_parameter: Angle<f64>,
+    pub end_parameter: Angle<f64>,
+}
+
+impl Ellipse {
+    /// A full ellipse.
+    ///
+    /// If `ratio` is greater than one the minor axis is actually the longer
+    /// of the two, so the axes are swapped.
+    pub fn new(centre: Point, major_axis: Vector, ratio: f64) -> Ellipse {
+        let ratio = ratio.abs();
+        let (major_axis, ratio) = if ratio > 1.0 {
+            (perpendicular(major_axis) * ratio, 1.0 / ratio)
+        } else {
+            (major_axis, ratio)
+        };
+
+        Ellipse {
+            centre,
+            major_axis,
+            ratio,
+            start_parameter: Angle::zero(),
+            end_parameter: Angle::two_pi(),
+        }
+    }
+
+    /// Turn this into an elliptical arc between two parameters.
+    pub fn with_parameters(self, start: Angle<f64>, end: Angle<f64>) -> Ellipse {
+        Ellipse {
+            start_parameter: start,
+            end_parameter: end,
+            ..self
+        }
+    }
+
+    /// The vector from the centre to one end of the minor axis, a quarter
+    /// turn anticlockwise from the major axis.
+    pub fn minor_axis(&self) -> Vector {
+        perpendicular(self.major_axis) * self.ratio
+    }
+
+    /// Does this go all the way around?
+    pub fn is_closed(&self) -> bool {
+        (self.end_parameter - self.start_parameter).radians.abs() >= 2.0 * PI
+    }
+
+    /// How far the arc sweeps anticlockwise from its start parameter.
+    pub fn sweep(&self) -> Angle<f64> {
+        if self.is_closed() {
+            Angle::two_pi()
+        } else {
+            (self.end_parameter - self.start_parameter).positive()
+        }
+    }
+
+    pub fn point_at(&self, parameter: Angle<f64>) -> Point {
+        let (sin, cos) = parameter.sin_cos();
+        self.centre + self.major_axis * cos + self.minor_axis() * sin
+    }
+
+    pub fn start_point(&self) -> Point {
+        self.point_at(self.start_parameter)
+    }
+
+    pub fn end_point(&self) -> Point {
+        self.point_at(self.end_parameter)
+    }
+
+    /// The parameter for the point on the ellipse in the same direction from
+    /// the centre as `location`.
+    pub fn parameter_of(&self, location: Point) -> Angle<f64> {
+        let radial = location - self.centre;
+        let minor_axis = self.minor_axis();
+        let along_major = radial.dot(self.major_axis) / self.major_axis.square_length();
+        let along_minor = radial.dot(minor_axis) / minor_axis.square_length();
+
+        Angle::radians(along_minor.atan2(along_major)).positive()
+    }
+
+    /// Is `parameter` part of the arc?
+    pub fn contains_parameter(&self, parameter: Angle<f64>) -> bool {
+        self.is_closed()
+            || (parameter - self.start_parameter).positive().radians <= self.sweep().radians
+    }
+
+    /// The points the ellipse is drawn through, from the start to the end.
+    ///
+    /// A full ellipse finishes back where it started.
+    pub fn vertices(&self) -> Vec<Point> {
+        let sweep = self.sweep().radians;
+        let segments = (SEGMENTS_PER_TURN as f64 * sweep / (2.0 * PI))
+            .round()
+            .max(1.0) as usize;
+        let step = sweep / segments as f64;
+
+        (0..=segments)
+            .map(|i| self.point_at(self.start_parameter + Angle::radians(step * i as f64)))
+            .collect()
+    }
+
+    /// The line segments used to draw the ellipse.
+    pub fn approximate(&self) -> Vec<Line> {
+        self.vertices()
+            .windows(2)
+            .map(|pair| Line::new(pair[0], pair[1]))
+            .collect()
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        let mut extremes = vec![self.start_point(), self.end_point()];
+        let minor_axis = self.minor_axis();
+
+        // x and y are at their largest and smallest where their derivatives
+        // with respect to the parameter are zero
+        let x_extreme = Angle::radians(minor_axis.x.atan2(self.major_axis.x));
+        let y_extreme = Angle::radians(minor_axis.y.atan2(self.major_axis.y));
+
+        for &candidate in &[x_extreme, y_extreme] {
+            for &parameter in &[candidate, candidate + Angle::pi()] {
+                if self.contains_parameter(parameter) {
+                    extremes.push(self.point_at(parameter));
+                }
+            }
+        }
+
+        BoundingBox::from_points(&extremes)
+    }
+
+    /// How far `location` is from the closest point on the ellipse, measured
+    /// against the same lines it's drawn with.
+    pub fn distance_to(&self, location: Point) -> f64 {
+        self.approximate()
+            .iter()
+            .map(|line| (geometry::closest_point_on_line(line, location) - location).length())
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn translate(&mut self, displacement: Vector) {
+        self.centre += displacement;
+    }
+}
+
+/// The vector rotated a quarter turn anticlockwise.
+fn perpendicular(vector: Vector) -> Vector {
+    Vector::new(-vector.y, vector.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(left: Point, right: Point) {
+        assert!((left - right).length() < 1e-9, "{:?} != {:?}", left, right);
+    }
+
+    fn wide() -> Ellipse {
+        // major radius 4 along the x axis, minor radius 2 along y
+        Ellipse::new(Point::new(1.0, 1.0), Vector::new(4.0, 0.0), 0.5)
+    }
+
+    #[test]
+    fn points_on_the_axes() {
+        let ellipse = wide();
+
+        assert_close(ellipse.point_at(Angle::zero()), Point::new(5.0, 1.0));
+        assert_close(ellipse.point_at(Angle::frac_pi_2()), Point::new(1.0, 3.0));
+        assert_close(ellipse.point_at(Angle::pi()), Point::new(-3.0, 1.0));
+    }
+
+    #[test]
+    fn a_long_minor_axis_swaps_the_axes() {
+        let ellipse = Ellipse::new(Point::zero(), Vector::new(1.0, 0.0), 3.0);
+
+        assert_eq!(ellipse.major_axis, Vector::new(0.0, 3.0));
+        assert!((ellipse.ratio - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bounding_box_of_a_full_ellipse() {
+        let got = wide().bounding_box();
+
+        assert_close(got.min, Point::new(-3.0, -1.0));
+        assert_close(got.max, Point::new(5.0, 3.0));
+    }
+
+    #[test]
+    fn bounding_box_of_the_top_half() {
+        let arc = wide().with_parameters(Angle::zero(), Angle::pi());
+
+        let got = arc.bounding_box();
+
+        assert_close(got.min, Point::new(-3.0, 1.0));
+        assert_close(got.max, Point::new(5.0, 3.0));
+    }
+
+    #[test]
+    fn the_parameter_of_a_point_round_trips() {
+        let ellipse = Ellipse::new(Point::new(2.0, -1.0), Vector::new(3.0, 3.0), 0.25);
+        let parameter = Angle::radians(2.0);
+
+        let got = ellipse.parameter_of(ellipse.point_at(parameter));
+
+        assert!((got - parameter).radians.abs() < 1e-9);
+    }
+
+    #[test]
+    fn hit_testing_only_considers_the_swept_part() {
+        let bottom_half = wide().with_parameters(Angle::pi(), Angle::zero());
+
+        assert!(bottom_half.distance_to(Point::new(1.0, -1.0)) < 1e-9);
+        assert!((bottom_half.distance_to(Point::new(1.0, 3.0)) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn an_arc_is_drawn_from_its_start_to_its_end() {
+        let arc = wide().with_parameters(Angle::frac_pi_2(), Angle::pi());
+
+        let got = arc.approximate();
+
+        assert_eq!(got.len(), SEGMENTS_PER_TURN / 4);
+        assert_close(got[0].start, Point::new(1.0, 3.0));
+        assert_close(got[got.len() - 1].end, Point::new(-3.0, 1.0));
+    }
+
+    #[test]
+    fn translating_moves_the_centre() {
+        let mut ellipse = wide();
+
+        ellipse.translate(Vector::new(-1.0, 2.0));
+
+        assert_eq!(ellipse.centre, Point::new(0.0, 3.0));
+        assert_eq!(ellipse.major_axis, Vector::new(4.0, 0.0));
+    }
+}
@@ -0,0 +1,258 @@
+//! Geometric queries for the [`Geometry`] variants which appear on a drawing.
+
+use arcs::{
+    components::Geometry,
+    euclid::{Angle, Box2D, Vector2D},
+    primitives::{Arc, Line},
+    DrawingSpace, Point,
+};
+
+/// An axis-aligned bounding box in drawing units.
+pub type BoundingBox = Box2D<f64, DrawingSpace>;
+
+/// The smallest [`BoundingBox`] containing a piece of [`Geometry`].
+///
+/// Returns `None` for geometry which doesn't have a finite extent (e.g. the
+/// grid).
+pub fn bounding_box(geometry: &Geometry) -> Option<BoundingBox> {
+    match geometry {
+        Geometry::Point(point) => Some(BoundingBox::new(*point, *point)),
+        Geometry::Line(line) => Some(BoundingBox::from_points(&[line.start, line.end])),
+        Geometry::Arc(arc) => Some(arc_bounding_box(arc)),
+        _ => None,
+    }
+}
+
+/// How far `location` is from the closest point on a piece of [`Geometry`].
+pub fn distance_to(geometry: &Geometry, location: Point) -> Option<f64> {
+    match geometry {
+        Geometry::Point(point) => Some((*point - location).length()),
+        Geometry::Line(line) => Some(distance_to_line(line, location)),
+        Geometry::Arc(arc) => Some(distance_to_arc(arc, location)),
+        _ => None,
+    }
+}
+
+/// Does a piece of [`Geometry`] touch any part of a region?
+pub fn intersects_box(geometry: &Geometry, region: &BoundingBox) -> bool {
+    match geometry {
+        Geometry::Point(point) => box_contains(region, *point),
+        Geometry::Line(line) => {
+            box_contains(region, line.start)
+                || box_contains(region, line.end)
+                || box_edges(region)
+                    .iter()
+                    .any(|edge| lines_intersect(line, edge))
+        }
+        Geometry::Arc(arc) => {
+            box_contains(region, arc_start(arc))
+                || box_contains(region, arc_end(arc))
+                || box_edges(region)
+                    .iter()
+                    .any(|edge| arc_intersects_line(arc, edge))
+        }
+        _ => false,
+    }
+}
+
+/// Does a region completely enclose a piece of [`Geometry`]?
+pub fn inside_box(geometry: &Geometry, region: &BoundingBox) -> bool {
+    bounding_box(geometry).map_or(false, |bounds| {
+        box_contains(region, bounds.min) && box_contains(region, bounds.max)
+    })
+}
+
+/// Like [`BoundingBox::contains()`], except points on the edge count.
+fn box_contains(region: &BoundingBox, point: Point) -> bool {
+    region.min.x <= point.x
+        && point.x <= region.max.x
+        && region.min.y <= point.y
+        && point.y <= region.max.y
+}
+
+fn box_edges(region: &BoundingBox) -> [Line; 4] {
+    let bottom_left = region.min;
+    let bottom_right = Point::new(region.max.x, region.min.y);
+    let top_right = region.max;
+    let top_left = Point::new(region.min.x, region.max.y);
+
+    [
+        Line::new(bottom_left, bottom_right),
+        Line::new(bottom_right, top_right),
+        Line::new(top_right, top_left),
+        Line::new(top_left, bottom_left),
+    ]
+}
+
+fn lines_intersect(first: &Line, second: &Line) -> bool {
+    line_intersection(first, second).is_some()
+}
+
+fn arc_intersects_line(arc: &Arc, line: &Line) -> bool {
+    !line_arc_intersections(line, arc).is_empty()
+}
+
+/// Where two line segments cross, if they do.
+///
+/// Parallel lines are never considered to intersect, even when they overlap.
+pub fn line_intersection(first: &Line, second: &Line) -> Option<Point> {
+    let first_direction = first.end - first.start;
+    let second_direction = second.end - second.start;
+    let denominator = first_direction.cross(second_direction);
+
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let offset = second.start - first.start;
+    let t = offset.cross(second_direction) / denominator;
+    let u = offset.cross(first_direction) / denominator;
+
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some(first.start + first_direction * t)
+    } else {
+        None
+    }
+}
+
+/// The points where a line segment crosses an [`Arc`].
+pub fn line_arc_intersections(line: &Line, arc: &Arc) -> Vec<Point> {
+    // solve |start + direction * t - centre| = radius for t
+    let direction = line.end - line.start;
+    let from_centre = line.start - arc.centre();
+
+    let a = direction.square_length();
+    let b = 2.0 * from_centre.dot(direction);
+    let c = from_centre.square_length() - arc.radius() * arc.radius();
+    let discriminant = b * b - 4.0 * a * c;
+
+    if a == 0.0 || discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    let root = discriminant.sqrt();
+
+    [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+        .iter()
+        .filter(|t| (0.0..=1.0).contains(*t))
+        .map(|&t| line.start + direction * t)
+        .filter(|point| arc_contains_angle(arc, (*point - arc.centre()).angle_from_x_axis()))
+        .collect()
+}
+
+/// The point on `line` which is closest to `location`.
+pub fn closest_point_on_line(line: &Line, location: Point) -> Point {
+    let direction = line.end - line.start;
+    let length_squared = direction.square_length();
+
+    if length_squared == 0.0 {
+        return line.start;
+    }
+
+    let t = (location - line.start).dot(direction) / length_squared;
+    line.start + direction * t.max(0.0).min(1.0)
+}
+
+fn distance_to_line(line: &Line, location: Point) -> f64 {
+    (closest_point_on_line(line, location) - location).length()
+}
+
+fn distance_to_arc(arc: &Arc, location: Point) -> f64 {
+    let radial = location - arc.centre();
+
+    if arc_contains_angle(arc, radial.angle_from_x_axis()) {
+        (radial.length() - arc.radius()).abs()
+    } else {
+        let start = (arc_start(arc) - location).length();
+        let end = (arc_end(arc) - location).length();
+        start.min(end)
+    }
+}
+
+/// Does the [`Arc`] sweep through a particular angle?
+pub fn arc_contains_angle(arc: &Arc, angle: Angle<f64>) -> bool {
+    let sweep = arc.sweep_angle().radians;
+
+    if sweep.abs() >= Angle::<f64>::two_pi().radians {
+        return true;
+    }
+
+    if sweep >= 0.0 {
+        (angle - arc.start_angle()).positive().radians <= sweep
+    } else {
+        (arc.start_angle() - angle).positive().radians <= -sweep
+    }
+}
+
+/// The point on an [`Arc`]'s circle at a particular angle.
+pub fn point_on_arc(arc: &Arc, angle: Angle<f64>) -> Point {
+    arc.centre() + Vector2D::from_angle_and_length(angle, arc.radius())
+}
+
+pub fn arc_start(arc: &Arc) -> Point {
+    point_on_arc(arc, arc.start_angle())
+}
+
+pub fn arc_end(arc: &Arc) -> Point {
+    point_on_arc(arc, arc.start_angle() + arc.sweep_angle())
+}
+
+fn arc_bounding_box(arc: &Arc) -> BoundingBox {
+    let mut extremes = vec![arc_start(arc), arc_end(arc)];
+
+    // the arc may also pass through the top, bottom, left or right-most
+    // points on its circle
+    for quarter_turns in 0..4 {
+        let angle = Angle::frac_pi_2() * f64::from(quarter_turns);
+
+        if arc_contains_angle(arc, angle) {
+            extremes.push(point_on_arc(arc, angle));
+        }
+    }
+
+    BoundingBox::from_points(&extremes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region() -> BoundingBox {
+        BoundingBox::new(Point::new(0.0, 0.0), Point::new(10.0, 10.0))
+    }
+
+    #[test]
+    fn a_line_passing_through_the_region_touches_it() {
+        let line = Geometry::Line(Line::new(Point::new(-5.0, 5.0), Point::new(15.0, 5.0)));
+
+        assert!(intersects_box(&line, &region()));
+        assert!(!inside_box(&line, &region()));
+    }
+
+    #[test]
+    fn a_diagonal_line_can_miss_even_when_its_bounds_overlap() {
+        let line = Geometry::Line(Line::new(Point::new(8.0, 20.0), Point::new(20.0, 8.0)));
+
+        assert!(!intersects_box(&line, &region()));
+    }
+
+    #[test]
+    fn only_the_swept_part_of_an_arc_counts() {
+        // the left half of a circle which crosses the region's right edge
+        let arc =
+            Arc::from_centre_radius(Point::new(15.0, 5.0), 8.0, Angle::frac_pi_2(), Angle::pi());
+        let other_half =
+            Arc::from_centre_radius(Point::new(15.0, 5.0), 8.0, -Angle::frac_pi_2(), Angle::pi());
+
+        assert!(intersects_box(&Geometry::Arc(arc), &region()));
+        assert!(!intersects_box(&Geometry::Arc(other_half), &region()));
+    }
+
+    #[test]
+    fn geometry_entirely_inside_the_region() {
+        let line = Geometry::Line(Line::new(Point::new(1.0, 1.0), Point::new(9.0, 10.0)));
+
+        assert!(inside_box(&line, &region()));
+        assert!(intersects_box(&line, &region()));
+    }
+}
@@ -0,0 +1,550 @@
+//! Undo/redo support.
+//!
+//! Rather than having every [`crate::modes::State`] describe how to reverse
+//! its actions, the [`History`] keeps a snapshot of the drawing. When a state
+//! finishes an operation it asks for a checkpoint, and once the dispatcher has
+//! applied any pending commands the snapshot is diffed against the world to
+//! produce a reversible [`Transaction`].
+
+use crate::{
+    components::Preview,
+    shapes::{Shape, ShapePart},
+};
+use arcs::{
+    components::{layer::LayerType, DrawingObject, Layer, LineStyle, Name, PointStyle},
+    specs::{prelude::*, shred::FetchMut},
+};
+use std::collections::HashMap;
+
+/// Everything we need to bring a deleted entity back to life, or to undo
+/// changes to its properties.
+///
+/// Drawing objects have an `object`, shapes have a `shape` and layers have a
+/// `layer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntitySnapshot {
+    pub object: Option<DrawingObject>,
+    pub shape: Option<Shape>,
+    pub layer: Option<Layer>,
+    pub name: Option<Name>,
+    pub point_style: Option<PointStyle>,
+    pub line_style: Option<LineStyle>,
+}
+
+impl EntitySnapshot {
+    pub fn capture(world: &World, entity: Entity) -> EntitySnapshot {
+        EntitySnapshot {
+            object: world.read_storage::<DrawingObject>().get(entity).cloned(),
+            shape: world.read_storage::<Shape>().get(entity).cloned(),
+            layer: world.read_storage::<Layer>().get(entity).cloned(),
+            name: world.read_storage::<Name>().get(entity).cloned(),
+            point_style: world.read_storage::<PointStyle>().get(entity).cloned(),
+            line_style: world.read_storage::<LineStyle>().get(entity).cloned(),
+        }
+    }
+
+    pub fn restore(&self, world: &mut World) -> Entity {
+        let entity = world.create_entity().build();
+        self.apply(world, entity);
+        entity
+    }
+
+    /// Make an entity's components match the snapshot.
+    fn apply(&self, world: &World, entity: Entity) {
+        set_component(world, entity, &self.object);
+        set_component(world, entity, &self.shape);
+        set_component(world, entity, &self.layer);
+        set_component(world, entity, &self.name);
+        set_component(world, entity, &self.point_style);
+        set_component(world, entity, &self.line_style);
+    }
+}
+
+fn set_component<C: Component + Clone>(world: &World, entity: Entity, value: &Option<C>) {
+    let mut storage = world.write_storage::<C>();
+
+    match value {
+        Some(value) => {
+            if let Err(e) = storage.insert(entity, value.clone()) {
+                log::warn!("Unable to restore a component on {:?}: {}", entity, e);
+            }
+        }
+        None => {
+            storage.remove(entity);
+        }
+    }
+}
+
+/// A single reversible change to the drawing.
+#[derive(Debug, Clone)]
+pub enum Change {
+    Created {
+        entity: Entity,
+        snapshot: EntitySnapshot,
+    },
+    Deleted {
+        entity: Entity,
+        snapshot: EntitySnapshot,
+    },
+    Modified {
+        entity: Entity,
+        before: EntitySnapshot,
+        after: EntitySnapshot,
+    },
+}
+
+impl Change {
+    fn entity_mut(&mut self) -> &mut Entity {
+        match self {
+            Change::Created { entity, .. }
+            | Change::Deleted { entity, .. }
+            | Change::Modified { entity, .. } => entity,
+        }
+    }
+}
+
+/// The group of [`Change`]s which make up one undo step.
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    changes: Vec<Change>,
+}
+
+impl Transaction {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn replace_entity(&mut self, old: Entity, new: Entity) {
+        for change in &mut self.changes {
+            let entity = change.entity_mut();
+            if *entity == old {
+                *entity = new;
+            }
+        }
+    }
+}
+
+/// The undo and redo stacks.
+#[derive(Debug, Default)]
+pub struct History {
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+    baseline: HashMap<Entity, EntitySnapshot>,
+    checkpoint_requested: bool,
+}
+
+impl History {
+    /// Start tracking the drawing as it currently is, forgetting any previous
+    /// undo steps.
+    pub fn reset(&mut self, world: &World) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.baseline = snapshot(world);
+        self.checkpoint_requested = false;
+    }
+
+    /// Ask for the changes made since the last checkpoint to be recorded the
+    /// next time [`History::record_pending()`] is called.
+    pub fn request_checkpoint(&mut self) {
+        self.checkpoint_requested = true;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Record a checkpoint if one was requested, returning `true` if the
+    /// drawing changed.
+    pub fn record_pending(&mut self, world: &World) -> bool {
+        if self.checkpoint_requested {
+            self.checkpoint_requested = false;
+            self.record(world)
+        } else {
+            false
+        }
+    }
+
+    /// Save everything which changed since the last checkpoint as a single
+    /// undo step, returning `true` if there was anything to save.
+    pub fn record(&mut self, world: &World) -> bool {
+        let current = snapshot(world);
+        let transaction = diff(&self.baseline, &current);
+        self.baseline = current;
+
+        if transaction.is_empty() {
+            return false;
+        }
+
+        log::debug!("Recording {} changes", transaction.changes.len());
+        self.undo_stack.push(transaction);
+        self.redo_stack.clear();
+        true
+    }
+
+    /// Revert the most recent undo step.
+    pub fn undo(&mut self, world: &mut World) -> bool {
+        // make sure nothing made since the last checkpoint gets lost
+        self.record(world);
+
+        let mut transaction = match self.undo_stack.pop() {
+            Some(t) => t,
+            None => return false,
+        };
+        let mut renumbered = Vec::new();
+
+        for change in transaction.changes.iter().rev() {
+            match change {
+                Change::Created { entity, .. } => delete(world, *entity),
+                Change::Deleted { entity, snapshot } => {
+                    renumbered.push((*entity, snapshot.restore(world)));
+                }
+                Change::Modified { entity, before, .. } => before.apply(world, *entity),
+            }
+        }
+
+        for (old, new) in renumbered {
+            transaction.replace_entity(old, new);
+            self.replace_entity(old, new);
+        }
+        self.redo_stack.push(transaction);
+        self.baseline = snapshot(world);
+
+        true
+    }
+
+    /// Re-apply the most recently undone step.
+    pub fn redo(&mut self, world: &mut World) -> bool {
+        self.record(world);
+
+        let mut transaction = match self.redo_stack.pop() {
+            Some(t) => t,
+            None => return false,
+        };
+        let mut renumbered = Vec::new();
+
+        for change in &transaction.changes {
+            match change {
+                Change::Created { entity, snapshot } => {
+                    renumbered.push((*entity, snapshot.restore(world)));
+                }
+                Change::Deleted { entity, .. } => delete(world, *entity),
+                Change::Modified { entity, after, .. } => after.apply(world, *entity),
+            }
+        }
+
+        for (old, new) in renumbered {
+            transaction.replace_entity(old, new);
+            self.replace_entity(old, new);
+        }
+        self.undo_stack.push(transaction);
+        self.baseline = snapshot(world);
+
+        true
+    }
+
+    /// Entities which get deleted and recreated come back with a new ID, so
+    /// update every reference to the old one.
+    fn replace_entity(&mut self, old: Entity, new: Entity) {
+        for transaction in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+            transaction.replace_entity(old, new);
+        }
+    }
+}
+
+/// Get a [`History`] from the world, creating one if necessary.
+pub fn history_mut(world: &mut World) -> FetchMut<'_, History> {
+    world.entry::<History>().or_insert_with(History::default)
+}
+
+/// Snapshot every non-system [`Layer`] and every [`DrawingObject`] or
+/// [`Shape`] on one.
+///
+/// A shape's [`ShapePart`]s are left out, they get rebuilt from the shape.
+/// So are [`Preview`]s, otherwise undoing part way through drawing something
+/// would delete the preview out from under the current state.
+fn snapshot(world: &World) -> HashMap<Entity, EntitySnapshot> {
+    let entities = world.entities();
+    let drawing_objects = world.read_storage::<DrawingObject>();
+    let shapes = world.read_storage::<Shape>();
+    let parts = world.read_storage::<ShapePart>();
+    let previews = world.read_storage::<Preview>();
+    let layers = world.read_storage::<Layer>();
+
+    let is_system = |layer: Option<&Layer>| {
+        layer.map_or(false, |layer| matches!(layer.layer_type, LayerType::System))
+    };
+    let layer_of = |entity: Entity| {
+        drawing_objects
+            .get(entity)
+            .map(|drawing_object| drawing_object.layer)
+            .or_else(|| shapes.get(entity).map(|shape| shape.layer))
+    };
+
+    (&entities, !&parts, !&previews)
+        .join()
+        .map(|(entity, _, _)| entity)
+        .filter(|&entity| match (layer_of(entity), layers.get(entity)) {
+            (Some(layer), _) => !is_system(layers.get(layer)),
+            (None, Some(layer)) => !is_system(Some(layer)),
+            (None, None) => false,
+        })
+        .map(|entity| (entity, EntitySnapshot::capture(world, entity)))
+        .collect()
+}
+
+fn diff(
+    before: &HashMap<Entity, EntitySnapshot>,
+    after: &HashMap<Entity, EntitySnapshot>,
+) -> Transaction {
+    let mut changes = Vec::new();
+
+    for (&entity, old) in before {
+        match after.get(&entity) {
+            None => changes.push(Change::Deleted {
+                entity,
+                snapshot: old.clone(),
+            }),
+            Some(new) if new != old => changes.push(Change::Modified {
+                entity,
+                before: old.clone(),
+                after: new.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for (&entity, new) in after {
+        if !before.contains_key(&entity) {
+            changes.push(Change::Created {
+                entity,
+                snapshot: new.clone(),
+            });
+        }
+    }
+
+    Transaction { changes }
+}
+
+fn delete(world: &mut World, entity: Entity) {
+    if let Err(e) = world.delete_entity(entity) {
+        log::warn!("Unable to delete {:?}: {}", entity, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{polyline::Polyline, shapes::ShapeGeometry};
+    use arcs::{components::Geometry, primitives::Line, Point, Vector};
+
+    fn world_with_layer() -> (World, Entity) {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let layer = Layer::create(
+            world.create_entity(),
+            Name::new("default"),
+            Layer::default(),
+        );
+
+        (world, layer)
+    }
+
+    fn add_point(world: &mut World, layer: Entity, location: Point) -> Entity {
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Point(location),
+                layer,
+            })
+            .build()
+    }
+
+    fn point_count(world: &World) -> usize {
+        world.read_storage::<DrawingObject>().join().count()
+    }
+
+    #[test]
+    fn undo_a_creation_then_redo_it() {
+        let (mut world, layer) = world_with_layer();
+        let mut history = History::default();
+        history.reset(&world);
+        add_point(&mut world, layer, Point::new(1.0, 2.0));
+        history.record(&world);
+
+        assert!(history.undo(&mut world));
+        world.maintain();
+        assert_eq!(point_count(&world), 0);
+
+        assert!(history.redo(&mut world));
+        world.maintain();
+        assert_eq!(point_count(&world), 1);
+    }
+
+    #[test]
+    fn shapes_are_recorded_without_their_parts() {
+        let (mut world, layer) = world_with_layer();
+        let mut history = History::default();
+        history.reset(&world);
+        let start = Point::new(0.0, 0.0);
+        let end = Point::new(10.0, 0.0);
+        let shape = world
+            .create_entity()
+            .with(Shape {
+                geometry: ShapeGeometry::Polyline(Polyline::new(vec![start, end], false)),
+                layer,
+            })
+            .build();
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(start, end)),
+                layer,
+            })
+            .with(ShapePart { shape })
+            .build();
+        history.record(&world);
+
+        assert!(history.undo(&mut world));
+        world.maintain();
+        assert_eq!(world.read_storage::<Shape>().join().count(), 0);
+        // the part is left for SyncShapes to tidy up
+        assert_eq!(point_count(&world), 1);
+
+        assert!(history.redo(&mut world));
+        world.maintain();
+        assert_eq!(world.read_storage::<Shape>().join().count(), 1);
+        assert_eq!(point_count(&world), 1);
+    }
+
+    #[test]
+    fn undo_leaves_previews_alone() {
+        let (mut world, layer) = world_with_layer();
+        let mut history = History::default();
+        history.reset(&world);
+        add_point(&mut world, layer, Point::new(1.0, 2.0));
+        history.record(&world);
+
+        // start drawing something else, then undo the point
+        let preview = world
+            .create_entity()
+            .with(Shape {
+                geometry: ShapeGeometry::Polyline(Polyline::new(
+                    vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0)],
+                    false,
+                )),
+                layer,
+            })
+            .with(Preview)
+            .build();
+        assert!(history.undo(&mut world));
+        world.maintain();
+
+        assert_eq!(point_count(&world), 0);
+        assert!(world.is_alive(preview));
+        assert!(world.read_storage::<Shape>().contains(preview));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn undoing_a_move_restores_the_old_geometry() {
+        let (mut world, layer) = world_with_layer();
+        let point = add_point(&mut world, layer, Point::new(1.0, 2.0));
+        let mut history = History::default();
+        history.reset(&world);
+
+        world
+            .write_storage::<DrawingObject>()
+            .get_mut(point)
+            .unwrap()
+            .geometry = Geometry::Point(Point::new(1.0, 2.0) + Vector::new(5.0, 0.0));
+        history.record(&world);
+        history.undo(&mut world);
+
+        let drawing_objects = world.read_storage::<DrawingObject>();
+        assert_eq!(
+            drawing_objects.get(point).unwrap().geometry,
+            Geometry::Point(Point::new(1.0, 2.0))
+        );
+    }
+
+    #[test]
+    fn undoing_a_style_change_restores_the_old_style() {
+        let (mut world, layer) = world_with_layer();
+        let point = add_point(&mut world, layer, Point::new(1.0, 2.0));
+        let mut history = History::default();
+        history.reset(&world);
+
+        world
+            .write_storage::<PointStyle>()
+            .insert(point, PointStyle::default())
+            .unwrap();
+        world
+            .write_storage::<Name>()
+            .insert(point, Name::new("hole"))
+            .unwrap();
+        history.record(&world);
+        history.undo(&mut world);
+
+        assert!(world.read_storage::<PointStyle>().get(point).is_none());
+        assert!(world.read_storage::<Name>().get(point).is_none());
+
+        history.redo(&mut world);
+
+        assert_eq!(
+            world.read_storage::<Name>().get(point),
+            Some(&Name::new("hole"))
+        );
+    }
+
+    #[test]
+    fn hiding_a_layer_can_be_undone() {
+        let (mut world, layer) = world_with_layer();
+        let mut history = History::default();
+        history.reset(&world);
+
+        world
+            .write_storage::<Layer>()
+            .get_mut(layer)
+            .unwrap()
+            .visible = false;
+        history.record(&world);
+        history.undo(&mut world);
+
+        assert!(world.read_storage::<Layer>().get(layer).unwrap().visible);
+    }
+
+    #[test]
+    fn deleted_entities_come_back_and_can_be_deleted_again() {
+        let (mut world, layer) = world_with_layer();
+        let point = add_point(&mut world, layer, Point::new(1.0, 2.0));
+        let mut history = History::default();
+        history.reset(&world);
+
+        world.delete_entity(point).unwrap();
+        history.record(&world);
+        history.undo(&mut world);
+        assert_eq!(point_count(&world), 1);
+
+        history.redo(&mut world);
+        assert_eq!(point_count(&world), 0);
+        assert!(history.can_undo());
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn nothing_changed_means_nothing_to_undo() {
+        let (mut world, layer) = world_with_layer();
+        add_point(&mut world, layer, Point::new(1.0, 2.0));
+        let mut history = History::default();
+        history.reset(&world);
+
+        history.record(&world);
+
+        assert!(!history.can_undo());
+        assert!(!history.undo(&mut world));
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 #[allow(unused_macros)]
 use std::{fmt::Debug, str::FromStr};
 
@@ -5,6 +6,9 @@ use std::{fmt::Debug, str::FromStr};
 pub struct KeyboardEventArgs {
   pub shift_pressed: bool,
   pub control_pressed: bool,
+  pub alt_pressed: bool,
+  /// The command key on a Mac, or the Windows key.
+  pub meta_pressed: bool,
   /// The semantic meaning of the key currently being pressed, if there is
   /// one.
   pub key: Option<VirtualKeyCode>,
@@ -21,74 +25,129 @@ impl KeyboardEventArgs {
   }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[allow(non_camel_case_types)]
+/// A key on the keyboard.
+///
+/// Letters used to have separate lowercase variants, so the lowercase names
+/// are still accepted when deserializing (e.g. a saved keymap).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum VirtualKeyCode {
   Escape,
+  #[serde(alias = "Return")]
   Enter,
+  Tab,
   Control,
   Shift,
   Alt,
+  Meta,
   Left,
   Up,
   Right,
   Down,
   Backspace,
-  Return,
+  Delete,
+  Insert,
+  Home,
+  End,
+  PageUp,
+  PageDown,
+  F1,
+  F2,
+  F3,
+  F4,
+  F5,
+  F6,
+  F7,
+  F8,
+  F9,
+  F10,
+  F11,
+  F12,
   Space,
+  Comma,
+  Period,
+  Minus,
+  Plus,
+  Equals,
+  Slash,
+  Backslash,
+  Semicolon,
+  Colon,
+  Apostrophe,
+  Grave,
+  LeftBracket,
+  RightBracket,
+  Asterisk,
+  At,
+  LessThan,
+  GreaterThan,
+  Question,
+  Exclamation,
+  Hash,
+  Dollar,
+  Percent,
+  Caret,
+  Ampersand,
+  LeftParen,
+  RightParen,
+  Underscore,
+  LeftBrace,
+  RightBrace,
+  Pipe,
+  Tilde,
+  Quote,
+  #[serde(alias = "a")]
   A,
+  #[serde(alias = "b")]
   B,
+  #[serde(alias = "c")]
   C,
+  #[serde(alias = "d")]
   D,
+  #[serde(alias = "e")]
   E,
+  #[serde(alias = "f")]
   F,
+  #[serde(alias = "g")]
   G,
+  #[serde(alias = "h")]
   H,
+  #[serde(alias = "i")]
   I,
+  #[serde(alias = "j")]
   J,
+  #[serde(alias = "k")]
   K,
+  #[serde(alias = "l")]
   L,
+  #[serde(alias = "m")]
   M,
+  #[serde(alias = "n")]
   N,
+  #[serde(alias = "o")]
   O,
+  #[serde(alias = "p")]
   P,
+  #[serde(alias = "q")]
   Q,
+  #[serde(alias = "r")]
   R,
+  #[serde(alias = "s")]
   S,
+  #[serde(alias = "t")]
   T,
+  #[serde(alias = "u")]
   U,
+  #[serde(alias = "v")]
   V,
+  #[serde(alias = "w")]
   W,
+  #[serde(alias = "x")]
   X,
+  #[serde(alias = "y")]
   Y,
+  #[serde(alias = "z")]
   Z,
-  a,
-  b,
-  c,
-  d,
-  e,
-  f,
-  g,
-  h,
-  i,
-  j,
-  k,
-  l,
-  m,
-  n,
-  o,
-  p,
-  q,
-  r,
-  s,
-  t,
-  u,
-  v,
-  w,
-  x,
-  y,
-  z,
   Key1,
   Key2,
   Key3,
@@ -107,61 +166,19 @@ impl FromStr for VirtualKeyCode {
   /// Parses a `KeyboardEvent.key` based on the equivalent name provided in
   /// [the W3C spec][spec].
   ///
+  /// Letters are case-insensitive because the browser reports `"A"` instead
+  /// of `"a"` when shift or caps lock is on.
+  ///
   /// [spec]: https://developer.mozilla.org/en-US/docs/Web/API/KeyboardEvent/key/Key_Values
   fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut chars = s.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+      if c.is_ascii_alphabetic() {
+        return letter(c.to_ascii_uppercase());
+      }
+    }
+
     match s {
-      "A" => Ok(VirtualKeyCode::A),
-      "B" => Ok(VirtualKeyCode::B),
-      "C" => Ok(VirtualKeyCode::C),
-      "D" => Ok(VirtualKeyCode::D),
-      "E" => Ok(VirtualKeyCode::E),
-      "F" => Ok(VirtualKeyCode::F),
-      "G" => Ok(VirtualKeyCode::G),
-      "H" => Ok(VirtualKeyCode::H),
-      "I" => Ok(VirtualKeyCode::I),
-      "J" => Ok(VirtualKeyCode::J),
-      "K" => Ok(VirtualKeyCode::K),
-      "L" => Ok(VirtualKeyCode::L),
-      "M" => Ok(VirtualKeyCode::M),
-      "N" => Ok(VirtualKeyCode::N),
-      "O" => Ok(VirtualKeyCode::O),
-      "P" => Ok(VirtualKeyCode::P),
-      "Q" => Ok(VirtualKeyCode::Q),
-      "R" => Ok(VirtualKeyCode::R),
-      "S" => Ok(VirtualKeyCode::S),
-      "T" => Ok(VirtualKeyCode::T),
-      "U" => Ok(VirtualKeyCode::U),
-      "V" => Ok(VirtualKeyCode::V),
-      "W" => Ok(VirtualKeyCode::W),
-      "X" => Ok(VirtualKeyCode::X),
-      "Y" => Ok(VirtualKeyCode::Y),
-      "Z" => Ok(VirtualKeyCode::Z),
-      "a" => Ok(VirtualKeyCode::a),
-      "b" => Ok(VirtualKeyCode::b),
-      "c" => Ok(VirtualKeyCode::c),
-      "d" => Ok(VirtualKeyCode::d),
-      "e" => Ok(VirtualKeyCode::e),
-      "f" => Ok(VirtualKeyCode::f),
-      "g" => Ok(VirtualKeyCode::g),
-      "h" => Ok(VirtualKeyCode::h),
-      "i" => Ok(VirtualKeyCode::i),
-      "j" => Ok(VirtualKeyCode::j),
-      "k" => Ok(VirtualKeyCode::k),
-      "l" => Ok(VirtualKeyCode::l),
-      "m" => Ok(VirtualKeyCode::m),
-      "n" => Ok(VirtualKeyCode::n),
-      "o" => Ok(VirtualKeyCode::o),
-      "p" => Ok(VirtualKeyCode::p),
-      "q" => Ok(VirtualKeyCode::q),
-      "r" => Ok(VirtualKeyCode::r),
-      "s" => Ok(VirtualKeyCode::s),
-      "t" => Ok(VirtualKeyCode::t),
-      "u" => Ok(VirtualKeyCode::u),
-      "v" => Ok(VirtualKeyCode::v),
-      "w" => Ok(VirtualKeyCode::w),
-      "x" => Ok(VirtualKeyCode::x),
-      "y" => Ok(VirtualKeyCode::y),
-      "z" => Ok(VirtualKeyCode::z),
       "0" => Ok(VirtualKeyCode::Key0),
       "1" => Ok(VirtualKeyCode::Key1),
       "2" => Ok(VirtualKeyCode::Key2),
@@ -173,12 +190,173 @@ impl FromStr for VirtualKeyCode {
       "8" => Ok(VirtualKeyCode::Key8),
       "9" => Ok(VirtualKeyCode::Key9),
       "Enter" => Ok(VirtualKeyCode::Enter),
+      "Tab" => Ok(VirtualKeyCode::Tab),
+      " " | "Spacebar" => Ok(VirtualKeyCode::Space),
       "Backspace" => Ok(VirtualKeyCode::Backspace),
-      "Escape" => Ok(VirtualKeyCode::Escape),
+      "Delete" | "Del" => Ok(VirtualKeyCode::Delete),
+      "Insert" => Ok(VirtualKeyCode::Insert),
+      "Home" => Ok(VirtualKeyCode::Home),
+      "End" => Ok(VirtualKeyCode::End),
+      "PageUp" => Ok(VirtualKeyCode::PageUp),
+      "PageDown" => Ok(VirtualKeyCode::PageDown),
+      "ArrowLeft" | "Left" => Ok(VirtualKeyCode::Left),
+      "ArrowUp" | "Up" => Ok(VirtualKeyCode::Up),
+      "ArrowRight" | "Right" => Ok(VirtualKeyCode::Right),
+      "ArrowDown" | "Down" => Ok(VirtualKeyCode::Down),
+      "F1" => Ok(VirtualKeyCode::F1),
+      "F2" => Ok(VirtualKeyCode::F2),
+      "F3" => Ok(VirtualKeyCode::F3),
+      "F4" => Ok(VirtualKeyCode::F4),
+      "F5" => Ok(VirtualKeyCode::F5),
+      "F6" => Ok(VirtualKeyCode::F6),
+      "F7" => Ok(VirtualKeyCode::F7),
+      "F8" => Ok(VirtualKeyCode::F8),
+      "F9" => Ok(VirtualKeyCode::F9),
+      "F10" => Ok(VirtualKeyCode::F10),
+      "F11" => Ok(VirtualKeyCode::F11),
+      "F12" => Ok(VirtualKeyCode::F12),
+      "," => Ok(VirtualKeyCode::Comma),
+      "." => Ok(VirtualKeyCode::Period),
+      "-" => Ok(VirtualKeyCode::Minus),
+      "+" => Ok(VirtualKeyCode::Plus),
+      "=" => Ok(VirtualKeyCode::Equals),
+      "/" => Ok(VirtualKeyCode::Slash),
+      "\\" => Ok(VirtualKeyCode::Backslash),
+      ";" => Ok(VirtualKeyCode::Semicolon),
+      ":" => Ok(VirtualKeyCode::Colon),
+      "'" => Ok(VirtualKeyCode::Apostrophe),
+      "`" => Ok(VirtualKeyCode::Grave),
+      "[" => Ok(VirtualKeyCode::LeftBracket),
+      "]" => Ok(VirtualKeyCode::RightBracket),
+      "*" => Ok(VirtualKeyCode::Asterisk),
+      "@" => Ok(VirtualKeyCode::At),
+      "<" => Ok(VirtualKeyCode::LessThan),
+      ">" => Ok(VirtualKeyCode::GreaterThan),
+      "?" => Ok(VirtualKeyCode::Question),
+      "!" => Ok(VirtualKeyCode::Exclamation),
+      "#" => Ok(VirtualKeyCode::Hash),
+      "$" => Ok(VirtualKeyCode::Dollar),
+      "%" => Ok(VirtualKeyCode::Percent),
+      "^" => Ok(VirtualKeyCode::Caret),
+      "&" => Ok(VirtualKeyCode::Ampersand),
+      "(" => Ok(VirtualKeyCode::LeftParen),
+      ")" => Ok(VirtualKeyCode::RightParen),
+      "_" => Ok(VirtualKeyCode::Underscore),
+      "{" => Ok(VirtualKeyCode::LeftBrace),
+      "}" => Ok(VirtualKeyCode::RightBrace),
+      "|" => Ok(VirtualKeyCode::Pipe),
+      "~" => Ok(VirtualKeyCode::Tilde),
+      "\"" => Ok(VirtualKeyCode::Quote),
+      "Escape" | "Esc" => Ok(VirtualKeyCode::Escape),
       "Shift" => Ok(VirtualKeyCode::Shift),
       "Control" => Ok(VirtualKeyCode::Control),
       "Alt" => Ok(VirtualKeyCode::Alt),
+      "Meta" | "OS" => Ok(VirtualKeyCode::Meta),
       _ => Err("Unknown KeyboardEvent key"),
     }
   }
 }
+
+fn letter(c: char) -> Result<VirtualKeyCode, &'static str> {
+  match c {
+    'A' => Ok(VirtualKeyCode::A),
+    'B' => Ok(VirtualKeyCode::B),
+    'C' => Ok(VirtualKeyCode::C),
+    'D' => Ok(VirtualKeyCode::D),
+    'E' => Ok(VirtualKeyCode::E),
+    'F' => Ok(VirtualKeyCode::F),
+    'G' => Ok(VirtualKeyCode::G),
+    'H' => Ok(VirtualKeyCode::H),
+    'I' => Ok(VirtualKeyCode::I),
+    'J' => Ok(VirtualKeyCode::J),
+    'K' => Ok(VirtualKeyCode::K),
+    'L' => Ok(VirtualKeyCode::L),
+    'M' => Ok(VirtualKeyCode::M),
+    'N' => Ok(VirtualKeyCode::N),
+    'O' => Ok(VirtualKeyCode::O),
+    'P' => Ok(VirtualKeyCode::P),
+    'Q' => Ok(VirtualKeyCode::Q),
+    'R' => Ok(VirtualKeyCode::R),
+    'S' => Ok(VirtualKeyCode::S),
+    'T' => Ok(VirtualKeyCode::T),
+    'U' => Ok(VirtualKeyCode::U),
+    'V' => Ok(VirtualKeyCode::V),
+    'W' => Ok(VirtualKeyCode::W),
+    'X' => Ok(VirtualKeyCode::X),
+    'Y' => Ok(VirtualKeyCode::Y),
+    'Z' => Ok(VirtualKeyCode::Z),
+    _ => Err("Not a letter"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn letters_are_case_insensitive() {
+    assert_eq!("q".parse(), Ok(VirtualKeyCode::Q));
+    assert_eq!("Q".parse(), Ok(VirtualKeyCode::Q));
+  }
+
+  #[test]
+  fn lowercase_letters_can_still_be_deserialized() {
+    let got: Vec<VirtualKeyCode> = serde_json::from_str(r#"["z", "A", "Escape"]"#).unwrap();
+
+    assert_eq!(
+      got,
+      vec![VirtualKeyCode::Z, VirtualKeyCode::A, VirtualKeyCode::Escape]
+    );
+  }
+
+  #[test]
+  fn parse_navigation_and_function_keys() {
+    let inputs = vec![
+      ("ArrowLeft", VirtualKeyCode::Left),
+      ("ArrowDown", VirtualKeyCode::Down),
+      (" ", VirtualKeyCode::Space),
+      ("Tab", VirtualKeyCode::Tab),
+      ("PageUp", VirtualKeyCode::PageUp),
+      ("F1", VirtualKeyCode::F1),
+      ("F12", VirtualKeyCode::F12),
+      ("/", VirtualKeyCode::Slash),
+      ("Meta", VirtualKeyCode::Meta),
+    ];
+
+    for (src, should_be) in inputs {
+      let got: VirtualKeyCode = src.parse().unwrap();
+      assert_eq!(got, should_be, "{:?}", src);
+    }
+  }
+
+  #[test]
+  fn parse_shifted_punctuation() {
+    let inputs = vec![
+      ("?", VirtualKeyCode::Question),
+      ("#", VirtualKeyCode::Hash),
+      ("(", VirtualKeyCode::LeftParen),
+      ("_", VirtualKeyCode::Underscore),
+      ("}", VirtualKeyCode::RightBrace),
+      ("|", VirtualKeyCode::Pipe),
+      ("\"", VirtualKeyCode::Quote),
+    ];
+
+    for (src, should_be) in inputs {
+      let got: VirtualKeyCode = src.parse().unwrap();
+      assert_eq!(got, should_be, "{:?}", src);
+    }
+  }
+
+  #[test]
+  fn saved_return_keys_are_read_as_enter() {
+    let got: VirtualKeyCode = serde_json::from_str(r#""Return""#).unwrap();
+
+    assert_eq!(got, VirtualKeyCode::Enter);
+  }
+
+  #[test]
+  fn unknown_keys_are_an_error() {
+    assert!("Dead".parse::<VirtualKeyCode>().is_err());
+    assert!("é".parse::<VirtualKeyCode>().is_err());
+  }
+}
@@ -0,0 +1,317 @@
+//! Keyboard shortcuts which work regardless of the current
+//! [`State`](crate::modes::State).
+//!
+//! The [`Keymap`] is checked by [`Model`](crate::model::Model) before a key
+//! press is passed to the current state, so anything it binds will shadow the
+//! state's own key handling.
+//!
+//! Only the user's own bindings are saved. They are laid over
+//! [`Keymap::default()`] when loading, so new default bindings still reach
+//! people who have customised their keymap.
+
+use crate::keyboard_event_args::{KeyboardEventArgs, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+
+/// The `localStorage` key the [`Keymap`] is saved under.
+pub const KEYMAP_KEY: &str = "arcs-wasm-experiment.keymap";
+
+/// A key and the modifiers which must be held down with it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Chord {
+    pub key: VirtualKeyCode,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub meta: bool,
+}
+
+impl Chord {
+    pub fn new(key: VirtualKeyCode) -> Chord {
+        Chord {
+            key,
+            shift: false,
+            ctrl: false,
+            alt: false,
+            meta: false,
+        }
+    }
+
+    pub fn shift(self) -> Chord {
+        Chord {
+            shift: true,
+            ..self
+        }
+    }
+
+    pub fn ctrl(self) -> Chord {
+        Chord { ctrl: true, ..self }
+    }
+
+    fn matches(&self, args: &KeyboardEventArgs) -> bool {
+        args.key == Some(self.key)
+            && args.shift_pressed == self.shift
+            && args.control_pressed == self.ctrl
+            && args.alt_pressed == self.alt
+            && args.meta_pressed == self.meta
+    }
+}
+
+/// Something a key press can do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    /// Abandon whatever is being drawn and go back to selecting.
+    Cancel,
+    Undo,
+    Redo,
+    DeleteSelection,
+    ZoomExtents,
+    ZoomSelection,
+    ToggleOrtho,
+    /// Run a line of text through the command console (e.g. `"LINE"`).
+    Command(String),
+}
+
+/// A single [`Chord`] and what it does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    #[serde(flatten)]
+    pub chord: Chord,
+    pub action: KeyAction,
+}
+
+/// A resource mapping [`Chord`]s to [`KeyAction`]s.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Keymap {
+    pub fn empty() -> Keymap {
+        Keymap {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Bind a [`Chord`] to an action, replacing any previous binding.
+    pub fn bind(&mut self, chord: Chord, action: KeyAction) {
+        self.bindings.retain(|binding| binding.chord != chord);
+        self.bindings.push(Binding { chord, action });
+    }
+
+    /// The default keymap with some bindings of the user's own on top.
+    pub fn with_overrides(overrides: &Keymap) -> Keymap {
+        let mut keymap = Keymap::default();
+
+        for binding in &overrides.bindings {
+            keymap.bind(binding.chord, binding.action.clone());
+        }
+
+        keymap
+    }
+
+    /// The bindings which differ from [`Keymap::default()`], which are all
+    /// that need saving.
+    pub fn overrides(&self) -> Keymap {
+        let defaults = Keymap::default();
+
+        Keymap {
+            bindings: self
+                .bindings
+                .iter()
+                .filter(|binding| !defaults.bindings.contains(binding))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Find the action bound to a key press, if there is one.
+    pub fn lookup(&self, args: &KeyboardEventArgs) -> Option<&KeyAction> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.chord.matches(args))
+            .map(|binding| &binding.action)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("The keymap is always serializable")
+    }
+
+    pub fn from_json(json: &str) -> Result<Keymap, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        use VirtualKeyCode::*;
+
+        let mut keymap = Keymap::empty();
+        let command = |name: &str| KeyAction::Command(name.to_string());
+
+        keymap.bind(Chord::new(Escape), KeyAction::Cancel);
+        keymap.bind(Chord::new(Delete), KeyAction::DeleteSelection);
+        keymap.bind(Chord::new(Backspace), KeyAction::DeleteSelection);
+        keymap.bind(Chord::new(Home), KeyAction::ZoomExtents);
+        keymap.bind(Chord::new(Home).shift(), KeyAction::ZoomSelection);
+        keymap.bind(Chord::new(F8), KeyAction::ToggleOrtho);
+
+        keymap.bind(Chord::new(Z).ctrl(), KeyAction::Undo);
+        keymap.bind(Chord::new(Z).ctrl().shift(), KeyAction::Redo);
+        keymap.bind(Chord::new(Y).ctrl(), KeyAction::Redo);
+        keymap.bind(Chord::new(A), command("ARC"));
+        // plain C is already used by the arc and polyline modes
+        keymap.bind(Chord::new(C).shift(), command("CIRCLE"));
+        keymap.bind(Chord::new(L), command("LINE"));
+        keymap.bind(Chord::new(P), command("POINT"));
+
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::{Action, CommandRegistry},
+        modes::{AddArcMode, AddLineMode, AddPointMode, State, Transition},
+    };
+
+    fn changes_to<S: State + 'static>(keymap: &Keymap, args: KeyboardEventArgs) -> bool {
+        let line = match keymap.lookup(&args) {
+            Some(KeyAction::Command(line)) => line,
+            other => panic!("Expected a command but found {:?}", other),
+        };
+
+        match CommandRegistry::default().parse(line).unwrap() {
+            Action::ChangeState(new_state) => {
+                Transition::ChangeState(new_state()).changes_to::<S>()
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn change_to_arc_mode() {
+        let keymap = Keymap::default();
+
+        assert!(changes_to::<AddArcMode>(
+            &keymap,
+            KeyboardEventArgs::pressing(VirtualKeyCode::A)
+        ));
+    }
+
+    #[test]
+    fn change_to_line_mode() {
+        let keymap = Keymap::default();
+
+        assert!(changes_to::<AddLineMode>(
+            &keymap,
+            KeyboardEventArgs::pressing(VirtualKeyCode::L)
+        ));
+    }
+
+    #[test]
+    fn change_to_point_mode() {
+        let keymap = Keymap::default();
+
+        assert!(changes_to::<AddPointMode>(
+            &keymap,
+            KeyboardEventArgs::pressing(VirtualKeyCode::P)
+        ));
+    }
+
+    #[test]
+    fn pressing_any_other_key_does_nothing() {
+        let keymap = Keymap::default();
+
+        let got = keymap.lookup(&KeyboardEventArgs::pressing(VirtualKeyCode::Q));
+
+        assert_eq!(got, None);
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let keymap = Keymap::default();
+        let ctrl_z = KeyboardEventArgs {
+            control_pressed: true,
+            ..KeyboardEventArgs::pressing(VirtualKeyCode::Z)
+        };
+        let ctrl_shift_z = KeyboardEventArgs {
+            shift_pressed: true,
+            ..ctrl_z
+        };
+        let ctrl_alt_z = KeyboardEventArgs {
+            alt_pressed: true,
+            ..ctrl_z
+        };
+
+        assert_eq!(keymap.lookup(&ctrl_z), Some(&KeyAction::Undo));
+        assert_eq!(keymap.lookup(&ctrl_shift_z), Some(&KeyAction::Redo));
+        assert_eq!(keymap.lookup(&ctrl_alt_z), None);
+    }
+
+    #[test]
+    fn rebinding_a_chord_replaces_it() {
+        let mut keymap = Keymap::default();
+
+        keymap.bind(Chord::new(VirtualKeyCode::Delete), KeyAction::Cancel);
+
+        let got = keymap.lookup(&KeyboardEventArgs::pressing(VirtualKeyCode::Delete));
+        assert_eq!(got, Some(&KeyAction::Cancel));
+    }
+
+    #[test]
+    fn overrides_are_laid_over_the_defaults() {
+        let mut overrides = Keymap::empty();
+        overrides.bind(Chord::new(VirtualKeyCode::L), KeyAction::ZoomExtents);
+
+        let got = Keymap::with_overrides(&overrides);
+
+        let shift_c = KeyboardEventArgs {
+            shift_pressed: true,
+            ..KeyboardEventArgs::pressing(VirtualKeyCode::C)
+        };
+        assert_eq!(
+            got.lookup(&KeyboardEventArgs::pressing(VirtualKeyCode::L)),
+            Some(&KeyAction::ZoomExtents)
+        );
+        assert_eq!(
+            got.lookup(&shift_c),
+            Some(&KeyAction::Command(String::from("CIRCLE")))
+        );
+        assert_eq!(got.overrides(), overrides);
+    }
+
+    #[test]
+    fn the_defaults_have_no_overrides() {
+        let got = Keymap::default().overrides();
+
+        assert_eq!(got, Keymap::empty());
+    }
+
+    #[test]
+    fn load_from_json() {
+        let json = r#"[
+            { "key": "F8", "action": "toggle_ortho" },
+            { "key": "C", "alt": true, "action": { "command": "ZOOM S" } }
+        ]"#;
+
+        let got = Keymap::from_json(json).unwrap();
+
+        let alt_c = KeyboardEventArgs {
+            alt_pressed: true,
+            ..KeyboardEventArgs::pressing(VirtualKeyCode::C)
+        };
+        assert_eq!(
+            got.lookup(&alt_c),
+            Some(&KeyAction::Command(String::from("ZOOM S")))
+        );
+        assert_eq!(Keymap::from_json(&got.to_json()).unwrap(), got);
+    }
+}
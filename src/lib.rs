@@ -1,34 +1,52 @@
 #![recursion_limit = "512"]
 use wasm_bindgen::prelude::*;
-use web_sys::{HtmlCanvasElement, HtmlElement, MouseEvent};
-
-use arcs::{
-    euclid::{Point2D, Size2D},
-    piet_web::WebRenderContext,
-    specs::prelude::*,
-    CanvasSpace,
-};
+use web_sys::{HtmlCanvasElement, HtmlElement, KeyboardEvent, MouseEvent, WheelEvent};
+
+use arcs::{euclid::Size2D, piet_web::WebRenderContext, specs::prelude::*, CanvasSpace};
 use log::Level;
 
 // use seed::{prelude::*, *};
 
 use crate::model::Model;
-use msg::ButtonType;
+use commands::CommandRegistry;
+use msg::{ButtonType, CanvasMouseEvent};
+use settings::EmptySpaceDrag;
+use snaps::SnapKind;
 use std::convert::TryFrom;
 use wasm_bindgen::JsCast;
 
+mod clipboard;
+mod commands;
+mod components;
+mod context_menu;
+mod coordinate_entry;
+mod document;
+mod dxf;
+mod ellipse;
+mod geometry;
+mod history;
 mod keyboard_event_args;
+mod keymap;
 mod model;
 mod modes;
 mod msg;
+mod polyline;
+mod properties;
+mod settings;
+mod shapes;
+mod snaps;
+mod spatial_index;
+mod svg;
+mod tracking;
 mod utils;
 
 const CANVAS_ID: &str = "canvas";
 
 /////////////////////////////////////////////////////////////////////////////////////////
 use yew::prelude::*;
+use yew::services::reader::{FileData, ReaderTask};
 use yew::services::resize::ResizeTask;
-use yew::services::ResizeService;
+use yew::services::{ReaderService, ResizeService};
 use yew::utils::*;
 use yew::Component;
 
@@ -47,6 +65,11 @@ struct Main {
     link: ComponentLink<Self>,
     model: Model,
     _resize_task: ResizeTask,
+    reader_task: Option<ReaderTask>,
+    /// The text typed into the console.
+    console: String,
+    /// Feedback about the last thing done in the console.
+    console_message: Option<String>,
 }
 
 #[derive(Copy, Clone, PartialEq, Properties)]
@@ -68,6 +91,9 @@ impl Component for Main {
             link,
             model: Model::new(props),
             _resize_task,
+            reader_task: None,
+            console: String::new(),
+            console_message: None,
         }
     }
 
@@ -76,12 +102,43 @@ impl Component for Main {
 
         let needs_render = match msg {
             msg::Msg::Rendered => true,
-            msg::Msg::MouseDown(cursor) => self.model.on_mouse_down(cursor),
-            msg::Msg::MouseUp(cursor) => self.model.on_mouse_up(cursor),
-            msg::Msg::MouseMove(cursor) => self.model.on_mouse_move(cursor),
+            msg::Msg::MouseDown(ev) => self.model.on_mouse_down(ev),
+            msg::Msg::MouseUp(ev) => self.model.on_mouse_up(ev),
+            msg::Msg::MouseMove(ev) => self.model.on_mouse_move(ev),
+            msg::Msg::ContextMenu(ev) => self.model.on_context_menu(ev),
+            msg::Msg::ContextMenuItem(index) => self.model.on_context_menu_item(index),
+            msg::Msg::PropertiesClosed => {
+                self.model.properties = None;
+                false
+            }
+            msg::Msg::MouseWheel(ev, delta) => self.model.on_mouse_wheel(ev, delta),
             msg::Msg::KeyPressed(args) => self.model.on_key_pressed(args),
+            msg::Msg::KeyReleased(args) => self.model.on_key_released(args),
+            msg::Msg::Blurred => self.model.on_blur(),
             msg::Msg::ButtonClicked(args) => self.model.on_button_clicked(args),
             msg::Msg::WindowResized => self.resize(),
+            msg::Msg::FileSelected(file) => self.read_file(file),
+            msg::Msg::DocumentLoaded { name, contents } => {
+                self.reader_task = None;
+                let loaded = if name.to_lowercase().ends_with(".dxf") {
+                    self.model.import_dxf(&contents)
+                } else {
+                    self.model.load_document(&contents)
+                };
+                if let Err(e) = loaded {
+                    log::error!("Unable to open \"{}\": {}", name, e);
+                }
+                true
+            }
+            msg::Msg::ConsoleInput(text) => {
+                self.console = text;
+                false
+            }
+            msg::Msg::ConsoleSubmit => self.run_console_command(),
+            msg::Msg::ConsoleComplete => {
+                self.complete_console_command();
+                false
+            }
         };
 
         if needs_render {
@@ -132,27 +189,223 @@ impl Component for Main {
                                 <p class="control"
                                     onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Line))
                                 >{self.view_line_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Polyline))
+                                >{self.view_polyline_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Arc))
+                                >{self.view_arc_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Circle))
+                                >{self.view_circle_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Rectangle))
+                                >{self.view_rectangle_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Polygon))
+                                >{self.view_polygon_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Ellipse))
+                                >{self.view_ellipse_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Erase))
+                                >{self.view_erase_btn()}</p>
                                 <p class="control"
                                     onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Snap))
                                 >{self.view_snap_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::DragMode))
+                                >{self.view_drag_mode_btn()}</p>
+                            </div>
+                        </div>
+                        <div class="level-item has-text-centered">
+                            <div class="field has-addons">
+                                { for SnapKind::ALL.iter().map(|&kind| self.view_object_snap_btn(kind)) }
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Ortho))
+                                >{self.view_ortho_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::PolarTracking))
+                                >{self.view_polar_tracking_btn()}</p>
+                            </div>
+                        </div>
+                        <div class="level-item has-text-centered">
+                            <div class="field has-addons">
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Undo))
+                                >{self.view_undo_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Redo))
+                                >{self.view_redo_btn()}</p>
+                            </div>
+                        </div>
+                        <div class="level-item has-text-centered">
+                            <div class="field has-addons">
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::ZoomExtents))
+                                >{self.view_zoom_btn("Extents", "fa-expand-arrows-alt")}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::ZoomSelection))
+                                >{self.view_zoom_btn("Selection", "fa-search-plus")}</p>
+                            </div>
+                        </div>
+                        <div class="level-item has-text-centered">
+                            <div class="field has-addons">
+                                <p class="control">{self.view_open_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::Save))
+                                >{self.view_save_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::ExportDxf))
+                                >{self.view_export_dxf_btn()}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::ExportSvg(svg::Crop::Viewport)))
+                                >{self.view_export_svg_btn("Export SVG", "fa-image")}</p>
+                                <p class="control"
+                                    onclick=self.link.callback(|_| msg::Msg::ButtonClicked(ButtonType::ExportSvg(svg::Crop::Extents)))
+                                >{self.view_export_svg_btn("SVG (extents)", "fa-expand")}</p>
                             </div>
                         </div>
                     </nav>
-                    <div class="canvas-container" style={format!("cursor: {}", self.model.current_state.get_cursor())}>
+                    <div class="canvas-container" style={format!("cursor: {}", self.model.cursor())}>
                         <canvas id="canvas" width={self.model.canvas_size.width}
                         height={self.model.canvas_size.height} tabindex=1
-                            onmousedown=self.link.callback(|e| msg::Msg::MouseDown(canvas_location(e)))
-                            onmouseup=self.link.callback(|e| msg::Msg::MouseUp(canvas_location(e)))
-                            onmousemove=self.link.callback(|e| msg::Msg::MouseMove(canvas_location(e)))
+                            onmousedown=self.link.callback(|e: MouseEvent| {
+                                // middle-clicking would otherwise start the browser's autoscroll
+                                if e.button() == 1 {
+                                    e.prevent_default();
+                                }
+                                msg::Msg::MouseDown(CanvasMouseEvent::from_button_event(&e))
+                            })
+                            onmouseup=self.link.callback(|e| msg::Msg::MouseUp(CanvasMouseEvent::from_button_event(&e)))
+                            onmousemove=self.link.callback(|e| msg::Msg::MouseMove(CanvasMouseEvent::from_mouse_event(&e)))
+                            oncontextmenu=self.link.callback(|e: MouseEvent| {
+                                // we handle right-clicks ourselves
+                                e.prevent_default();
+                                msg::Msg::ContextMenu(CanvasMouseEvent::from_button_event(&e))
+                            })
+                            onwheel=self.link.callback(|e: WheelEvent| {
+                                // don't scroll the page as well
+                                e.prevent_default();
+                                msg::Msg::MouseWheel(CanvasMouseEvent::from_mouse_event(&e), wheel_delta(&e))
+                            })
                             onkeydown=self.link.callback(msg::Msg::from_key_press)
+                            onkeyup=self.link.callback(msg::Msg::from_key_release)
+                            onblur=self.link.callback(|_| msg::Msg::Blurred)
                         ></canvas>
+                        {self.view_coordinate_entry()}
+                        {self.view_context_menu()}
+                        {self.view_properties()}
                     </div>
+                    {self.view_console()}
                 </div>
         }
     }
 }
 
 impl Main {
+    fn view_console(&self) -> Html {
+        html! {
+            <div class="console">
+                <p class="help">{self.console_message.as_deref().unwrap_or("")}</p>
+                <input class="input is-small" type="text" placeholder="Command"
+                    value=self.console.clone()
+                    oninput=self.link.callback(|e: InputData| msg::Msg::ConsoleInput(e.value))
+                    onkeydown=self.link.batch_callback(|e: KeyboardEvent| match e.key().as_str() {
+                        "Enter" => vec![msg::Msg::ConsoleSubmit],
+                        "Tab" => {
+                            // keep the focus in the console
+                            e.prevent_default();
+                            vec![msg::Msg::ConsoleComplete]
+                        }
+                        "Escape" => vec![msg::Msg::ConsoleInput(String::new())],
+                        _ => Vec::new(),
+                    })
+                />
+            </div>
+        }
+    }
+
+    fn run_console_command(&mut self) -> bool {
+        match self.model.run_command(&self.console) {
+            Ok(needs_render) => {
+                self.console.clear();
+                self.console_message = None;
+                needs_render
+            }
+            Err(e) => {
+                self.console_message = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Fill in the rest of the command name if there's only one match,
+    /// otherwise list the candidates.
+    fn complete_console_command(&mut self) {
+        let registry = self.model.world.read_resource::<CommandRegistry>();
+        let candidates = registry.complete(&self.console);
+
+        match candidates.as_slice() {
+            [] => self.console_message = Some(String::from("No matching commands")),
+            [name] => {
+                self.console = format!("{} ", name);
+                self.console_message = registry.find(name).map(|c| c.description.to_string());
+            }
+            names => self.console_message = Some(names.join(", ")),
+        }
+    }
+
+    fn view_coordinate_entry(&self) -> Html {
+        match coordinate_entry::current_text(&self.model.world) {
+            Some(text) => html! {
+                <span class="coordinate-entry tag is-medium is-dark">{text}</span>
+            },
+            None => html! {},
+        }
+    }
+
+    fn view_context_menu(&self) -> Html {
+        let menu = match &self.model.context_menu {
+            Some(menu) => menu,
+            None => return html! {},
+        };
+        let style = format!("left: {}px; top: {}px", menu.position.x, menu.position.y);
+
+        html! {
+            <div class="context-menu dropdown-content" style={style}>
+                { for menu.items.iter().enumerate().map(|(index, item)| html! {
+                    <a class="dropdown-item"
+                        onclick=self.link.callback(move |_| msg::Msg::ContextMenuItem(index))
+                    >{item.label}</a>
+                }) }
+            </div>
+        }
+    }
+
+    fn view_properties(&self) -> Html {
+        let properties = match &self.model.properties {
+            Some(properties) => properties,
+            None => return html! {},
+        };
+
+        html! {
+            <div class="properties box">
+                <button class="delete is-small"
+                    onclick=self.link.callback(|_| msg::Msg::PropertiesClosed)
+                ></button>
+                <table class="table is-narrow">
+                    { for properties.iter().map(|property| html! {
+                        <tr>
+                            <th>{property.name}</th>
+                            <td>{&property.value}</td>
+                        </tr>
+                    }) }
+                </table>
+            </div>
+        }
+    }
+
     fn view_select_btn(&self) -> Html {
         let classes = if (*self.model.current_state)
             .as_any()
@@ -217,6 +470,137 @@ impl Main {
         }
     }
 
+    fn view_polyline_btn(&self) -> Html {
+        let classes = if (*self.model.current_state)
+            .as_any()
+            .is::<modes::add_polyline_mode::AddPolylineMode>()
+        {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <button class={classes}>
+                <span class="icon is-small">
+                    <i class="fas fa-draw-polygon"></i>
+                </span>
+                <span>{"Polyline"}</span>
+            </button>
+        }
+    }
+
+    fn view_arc_btn(&self) -> Html {
+        let classes = if (*self.model.current_state)
+            .as_any()
+            .is::<modes::add_arc_mode::AddArcMode>()
+        {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <button class={classes}>
+                <span class="icon is-small">
+                    <i class="fas fa-circle-notch"></i>
+                </span>
+                <span>{"Arc"}</span>
+            </button>
+        }
+    }
+
+    fn view_circle_btn(&self) -> Html {
+        let classes = if (*self.model.current_state)
+            .as_any()
+            .is::<modes::add_circle_mode::AddCircleMode>()
+        {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <button class={classes} title="Circle (Shift+C)">
+                <span class="icon is-small">
+                    <i class="far fa-circle"></i>
+                </span>
+                <span>{"Circle"}</span>
+            </button>
+        }
+    }
+
+    fn view_rectangle_btn(&self) -> Html {
+        let classes = if (*self.model.current_state)
+            .as_any()
+            .is::<modes::add_rectangle_mode::AddRectangleMode>()
+        {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <button class={classes}>
+                <span class="icon is-small">
+                    <i class="far fa-square"></i>
+                </span>
+                <span>{"Rectangle"}</span>
+            </button>
+        }
+    }
+
+    fn view_polygon_btn(&self) -> Html {
+        let classes = if (*self.model.current_state)
+            .as_any()
+            .is::<modes::add_polygon_mode::AddPolygonMode>()
+        {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <button class={classes} title="Type a number and press Enter to change the sides">
+                <span class="icon is-small">
+                    <i class="fas fa-vector-square"></i>
+                </span>
+                <span>{"Polygon"}</span>
+            </button>
+        }
+    }
+
+    fn view_ellipse_btn(&self) -> Html {
+        let classes = if (*self.model.current_state)
+            .as_any()
+            .is::<modes::add_ellipse_mode::AddEllipseMode>()
+        {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <button class={classes}>
+                <span class="icon is-small">
+                    <i class="fas fa-egg"></i>
+                </span>
+                <span>{"Ellipse"}</span>
+            </button>
+        }
+    }
+
+    fn view_erase_btn(&self) -> Html {
+        html! {
+            <button class="button is-light">
+                <span class="icon is-small">
+                    <i class="fas fa-eraser"></i>
+                </span>
+                <span>{"Erase"}</span>
+            </button>
+        }
+    }
+
     fn view_snap_btn(&self) -> Html {
         let classes = "button is-light";
 
@@ -236,6 +620,172 @@ impl Main {
         }
     }
 
+    fn view_drag_mode_btn(&self) -> Html {
+        let (icon_class, label) = match self.model.settings().empty_space_drag {
+            EmptySpaceDrag::SelectRegion => ("fas fa-vector-square", "Drag: Select"),
+            EmptySpaceDrag::Pan => ("fas fa-hand-paper", "Drag: Pan"),
+        };
+
+        html! {
+            <button class="button is-light" title="What dragging on empty space does">
+                <span class="icon is-small">
+                    <i class={icon_class}></i>
+                </span>
+                <span>{label}</span>
+            </button>
+        }
+    }
+
+    fn view_object_snap_btn(&self, kind: SnapKind) -> Html {
+        let classes = if self.model.settings().object_snaps.is_enabled(kind) {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <p class="control"
+                onclick=self.link.callback(move |_| msg::Msg::ButtonClicked(ButtonType::ObjectSnap(kind)))
+            >
+                <button class={classes} title={format!("{:?} snap", kind)}>
+                    <span>{kind.label()}</span>
+                </button>
+            </p>
+        }
+    }
+
+    fn view_ortho_btn(&self) -> Html {
+        let classes = if self.model.settings().ortho {
+            "button is-light is-inverted is-active"
+        } else {
+            "button is-light"
+        };
+
+        html! {
+            <button class={classes} title="Keep lines horizontal or vertical (F8)">
+                <span>{"Ortho"}</span>
+            </button>
+        }
+    }
+
+    fn view_polar_tracking_btn(&self) -> Html {
+        let label = match self.model.settings().polar_tracking.increment() {
+            Some(increment) => format!("Polar: {}°", increment.to_degrees()),
+            None => String::from("Polar: Off"),
+        };
+
+        html! {
+            <button class="button is-light" title="Polar tracking increment">
+                <span>{label}</span>
+            </button>
+        }
+    }
+
+    fn view_undo_btn(&self) -> Html {
+        html! {
+            <button class="button is-light" disabled={!self.model.can_undo()}>
+                <span class="icon is-small">
+                    <i class="fas fa-undo"></i>
+                </span>
+                <span>{"Undo"}</span>
+            </button>
+        }
+    }
+
+    fn view_redo_btn(&self) -> Html {
+        html! {
+            <button class="button is-light" disabled={!self.model.can_redo()}>
+                <span class="icon is-small">
+                    <i class="fas fa-redo"></i>
+                </span>
+                <span>{"Redo"}</span>
+            </button>
+        }
+    }
+
+    fn view_zoom_btn(&self, label: &str, icon: &str) -> Html {
+        html! {
+            <button class="button is-light">
+                <span class="icon is-small">
+                    <i class={format!("fas {}", icon)}></i>
+                </span>
+                <span>{label}</span>
+            </button>
+        }
+    }
+
+    fn view_open_btn(&self) -> Html {
+        html! {
+            <div class="file is-light">
+                <label class="file-label">
+                    <input class="file-input" type="file" accept=".json,.dxf,application/json"
+                        onchange=self.link.batch_callback(|change| match change {
+                            ChangeData::Files(files) => {
+                                files.get(0).map(msg::Msg::FileSelected).into_iter().collect()
+                            }
+                            _ => Vec::new(),
+                        })
+                    />
+                    <span class="file-cta">
+                        <span class="file-icon">
+                            <i class="fas fa-folder-open"></i>
+                        </span>
+                        <span class="file-label">{"Open"}</span>
+                    </span>
+                </label>
+            </div>
+        }
+    }
+
+    fn view_save_btn(&self) -> Html {
+        html! {
+            <button class="button is-light">
+                <span class="icon is-small">
+                    <i class="fas fa-save"></i>
+                </span>
+                <span>{"Save"}</span>
+            </button>
+        }
+    }
+
+    fn view_export_dxf_btn(&self) -> Html {
+        html! {
+            <button class="button is-light">
+                <span class="icon is-small">
+                    <i class="fas fa-file-export"></i>
+                </span>
+                <span>{"Export DXF"}</span>
+            </button>
+        }
+    }
+
+    fn view_export_svg_btn(&self, label: &str, icon: &str) -> Html {
+        html! {
+            <button class="button is-light">
+                <span class="icon is-small">
+                    <i class={format!("fas {}", icon)}></i>
+                </span>
+                <span>{label}</span>
+            </button>
+        }
+    }
+
+    fn read_file(&mut self, file: web_sys::File) -> bool {
+        let callback = self
+            .link
+            .callback(|data: FileData| msg::Msg::DocumentLoaded {
+                name: data.name,
+                contents: String::from_utf8_lossy(&data.content).into_owned(),
+            });
+
+        match ReaderService::new().read_file(file, callback) {
+            Ok(task) => self.reader_task = Some(task),
+            Err(e) => log::error!("Unable to read the file: {}", e),
+        }
+
+        false
+    }
+
     fn resize(&mut self) -> bool {
         if let Some(canvas) = self.canvas(CANVAS_ID) {
             self.model.canvas_size = self.parent_size(&canvas).unwrap();
@@ -265,8 +815,7 @@ impl Main {
     }
 
     fn draw(&mut self, canvas: &HtmlCanvasElement) {
-        self.model.dispatcher.dispatch(&self.model.world);
-        self.model.world.maintain();
+        self.model.dispatch();
 
         let canvas_ctx = self.canvas_context_2d(&canvas);
         let browser_window = window();
@@ -290,11 +839,17 @@ impl Main {
     }
 }
 
-fn canvas_location(ev: MouseEvent) -> Point2D<f64, CanvasSpace> {
-    let x = ev.offset_x().into();
-    let y = ev.offset_y().into();
+/// How far the wheel scrolled, in pixels.
+fn wheel_delta(ev: &WheelEvent) -> f64 {
+    // browsers may report the distance in lines or pages instead
+    const LINE_HEIGHT: f64 = 16.0;
+    const PAGE_HEIGHT: f64 = 800.0;
 
-    Point2D::new(x, y)
+    match ev.delta_mode() {
+        WheelEvent::DOM_DELTA_LINE => ev.delta_y() * LINE_HEIGHT,
+        WheelEvent::DOM_DELTA_PAGE => ev.delta_y() * PAGE_HEIGHT,
+        _ => ev.delta_y(),
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////
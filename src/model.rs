@@ -1,9 +1,9 @@
 use arcs::{
   components::{
     layer::LayerType, CursorPosition, Dimension, DrawingObject, Geometry, GridStyle, Layer, Name,
-    PointStyle,
+    PointStyle, Selected, Viewport,
   },
-  euclid::{Length, Point2D, Size2D},
+  euclid::{Length, Size2D},
   piet::Color,
   primitives::Grid,
   specs::prelude::*,
@@ -12,14 +12,35 @@ use arcs::{
   systems::mover::Mover,
   systems::snapper::Snapper,
   window::Window,
-  CanvasSpace,
+  CanvasSpace, Point,
 };
 
-use super::keyboard_event_args::KeyboardEventArgs;
-use super::msg::ButtonType;
+use super::clipboard::Clipboard;
+use super::commands::{Action, CommandError, CommandRegistry};
+use super::context_menu::ContextMenu;
+use super::coordinate_entry::{self, ApplyTypedLocation};
+use super::document::{Document, DocumentError};
+use super::dxf;
+use super::history::History;
+use super::keyboard_event_args::{KeyboardEventArgs, VirtualKeyCode};
+use super::keymap::{KeyAction, Keymap, KEYMAP_KEY};
+use super::msg::{ButtonType, CanvasMouseEvent};
+use super::properties::{self, Property};
+use super::settings::{self, EmptySpaceDrag, Settings, SETTINGS_KEY};
+use super::shapes::SyncShapes;
+use super::snaps::{self, ApplyObjectSnap, SnapKind};
+use super::spatial_index::SyncSpatialIndex;
+use super::tracking::{self, ApplyTracking};
+use super::svg::{self, Crop};
+use super::utils;
 use super::Props;
 
-use super::modes::{ApplicationContext, Idle, MouseButtons, MouseEventArgs, State, Transition};
+use super::modes::{
+  ApplicationContext, Idle, MouseButtons, MouseEventArgs, State, Transition, GRID_SPACING,
+};
+
+/// The `localStorage` key the current drawing is autosaved under.
+const AUTOSAVE_KEY: &str = "arcs-wasm-experiment.drawing";
 
 pub struct Model {
   pub world: World,
@@ -33,17 +54,23 @@ pub struct Model {
   pub command: Entity,
   pub dispatcher: Dispatcher<'static, 'static>,
   pub snap: bool,
+  /// Is the space bar held down, so dragging with the left button pans?
+  space_pressed: bool,
+  /// The point on the drawing which was grabbed when panning with the
+  /// middle button (or space and the left button).
+  pan_anchor: Option<Point>,
+  /// Where the mouse was last seen, so pasting knows where to go.
+  mouse_location: Point,
+  pub context_menu: Option<ContextMenu>,
+  /// The summary of the selection shown by "Properties", if it's open.
+  pub properties: Option<Vec<Property>>,
 }
 
 impl Model {
   pub fn new(props: Props) -> Model {
     let mut world = World::new();
-    arcs::components::register(&mut world);
-    let builder = world.create_entity().with(PointStyle {
-      radius: Dimension::Pixels(3.0),
-      ..Default::default()
-    });
-    let default_layer = Layer::create(builder, Name::new("default_layer"), Layer::default());
+    crate::components::register(&mut world);
+    let default_layer = create_default_layer(&mut world);
 
     let builder = world.create_entity().with(GridStyle {
       stroke: Color::rgb8(0x94, 0x94, 0x94),
@@ -69,7 +96,7 @@ impl Model {
 
     let _cursor_position = world.insert(CursorPosition::default());
 
-    let grid = Grid::new(Length::new(20.), false);
+    let grid = Grid::new(Length::new(GRID_SPACING), false);
     let grid = world
       .create_entity()
       .with(DrawingObject {
@@ -80,14 +107,32 @@ impl Model {
 
     let command = world.create_entity().with(Name::new("command")).build();
 
-    let dispatcher = DispatcherBuilder::new()
+    let mut dispatcher = DispatcherBuilder::new()
       .with(Snapper, "snapper", &[])
-      .with(Draw, "draw", &["snapper"])
+      .with(ApplyObjectSnap, "apply_object_snap", &["snapper"])
+      .with(ApplyTracking, "apply_tracking", &["apply_object_snap"])
+      .with(ApplyTypedLocation, "apply_typed_location", &["apply_tracking"])
+      .with(Draw, "draw", &["apply_typed_location"])
       .with(Deleter, "deleter", &[])
-      .with(Mover, "mover", &["snapper"])
+      .with(Mover, "mover", &["apply_typed_location"])
+      .with(SyncShapes::default(), "sync_shapes", &["draw", "deleter", "mover"])
+      .with(
+        SyncSpatialIndex::default(),
+        "sync_spatial_index",
+        &["sync_shapes"],
+      )
       .build();
+    dispatcher.setup(&mut world);
 
-    Model {
+    let mut history = History::default();
+    history.reset(&world);
+    world.insert(history);
+    world.insert(load_settings());
+    world.insert(CommandRegistry::default());
+    world.insert(load_keymap());
+    world.insert(Clipboard::default());
+
+    let mut model = Model {
       world,
       window,
       default_layer,
@@ -99,7 +144,20 @@ impl Model {
       command: command,
       dispatcher: dispatcher,
       snap: false,
+      space_pressed: false,
+      pan_anchor: None,
+      mouse_location: Point::origin(),
+      context_menu: None,
+      properties: None,
+    };
+
+    if let Some(json) = utils::load_from_local_storage(AUTOSAVE_KEY) {
+      if let Err(e) = model.load_document(&json) {
+        log::warn!("Unable to restore the previous drawing: {}", e);
+      }
     }
+
+    model
   }
 
   fn handle_event<F>(&mut self, handler: F) -> bool
@@ -117,37 +175,236 @@ impl Model {
         pointer: self.pointer,
         grid: self.grid,
         command: self.command,
+        system_layer: self.system_layer,
+        canvas_size: self.canvas_size,
+        dispatcher: &mut self.dispatcher,
       },
     );
     self.handle_transition(transition);
     !suppress_redraw
   }
 
-  pub fn on_mouse_down(&mut self, cursor: Point2D<f64, CanvasSpace>) -> bool {
-    let args = self.mouse_event_args(cursor);
+  pub fn on_mouse_down(&mut self, event: CanvasMouseEvent) -> bool {
+    let args = self.mouse_event_args(event);
     log::debug!("[ON_MOUSE_DOWN] {:?}, {:?}", args, self.current_state);
+    coordinate_entry::forget_typed_location(&self.world);
+
+    // clicking anywhere else just closes the context menu
+    if self.context_menu.take().is_some() {
+      return true;
+    }
+
+    // panning works the same in every mode, and the current state never
+    // finds out so it can keep whatever it was drawing
+    let buttons = args.button_state;
+    let pan_with_space = self.space_pressed && buttons.contains(MouseButtons::LEFT_BUTTON);
+    if buttons.contains(MouseButtons::MIDDLE_BUTTON) || pan_with_space {
+      self.pan_anchor = Some(args.location);
+      return true;
+    }
+
     self.handle_event(|state, ctx| state.on_mouse_down(ctx, &args))
   }
 
-  pub fn on_mouse_up(&mut self, cursor: Point2D<f64, CanvasSpace>) -> bool {
-    let args = self.mouse_event_args(cursor);
+  pub fn on_mouse_up(&mut self, event: CanvasMouseEvent) -> bool {
+    let args = self.mouse_event_args(event);
     log::debug!("[ON_MOUSE_UP] {:?}, {:?}", args, self.current_state);
+
+    if self.pan_anchor.take().is_some() {
+      return true;
+    }
+
     self.handle_event(|state, ctx| state.on_mouse_up(ctx, &args))
   }
 
-  pub fn on_mouse_move(&mut self, cursor: Point2D<f64, CanvasSpace>) -> bool {
-    let args = self.mouse_event_args(cursor);
+  pub fn on_mouse_move(&mut self, event: CanvasMouseEvent) -> bool {
+    let args = self.mouse_event_args(event);
+    coordinate_entry::forget_typed_location(&self.world);
+    self.mouse_location = args.location;
+
+    if let Some(anchor) = self.pan_anchor {
+      // move the viewport so the anchor stays under the cursor
+      let mut viewports = self.world.write_storage::<Viewport>();
+      if let Some(viewport) = viewports.get_mut(self.window.0) {
+        viewport.centre += anchor - args.location;
+      }
+      return true;
+    }
+
     self.handle_event(|state, ctx| state.on_mouse_move(ctx, &args))
   }
 
+  /// The CSS cursor to show over the canvas.
+  pub fn cursor(&self) -> &str {
+    if self.pan_anchor.is_some() {
+      "grabbing"
+    } else if self.space_pressed {
+      "grab"
+    } else {
+      self.current_state.get_cursor()
+    }
+  }
+
+  pub fn on_context_menu(&mut self, event: CanvasMouseEvent) -> bool {
+    let args = self.mouse_event_args(event);
+    log::debug!("[ON_CONTEXT_MENU] {:?}, {:?}", args, self.current_state);
+    self.mouse_location = args.location;
+
+    let mut items = Vec::new();
+    self.handle_event(|state, ctx| {
+      let transition = state.on_context_menu(ctx, &args);
+      items = state.context_menu(&*ctx);
+      transition
+    });
+
+    self.context_menu = if items.is_empty() {
+      None
+    } else {
+      Some(ContextMenu { position: event.cursor, items })
+    };
+
+    true
+  }
+
+  /// Something was picked from the context menu.
+  pub fn on_context_menu_item(&mut self, index: usize) -> bool {
+    let item = self
+      .context_menu
+      .take()
+      .and_then(|menu| menu.items.get(index).copied());
+    log::debug!("[ON_CONTEXT_MENU_ITEM] {:?}, {:?}", item, self.current_state);
+
+    if let Some(item) = item {
+      self.run_action(item.action);
+    }
+
+    // the menu needs to disappear regardless
+    true
+  }
+
+  pub fn on_mouse_wheel(&mut self, event: CanvasMouseEvent, delta: f64) -> bool {
+    let args = self.mouse_event_args(event);
+    self.handle_event(|state, ctx| state.on_mouse_wheel(ctx, &args, delta))
+  }
+
   pub fn on_key_pressed(&mut self, args: KeyboardEventArgs) -> bool {
     log::debug!("[ON_KEY_PRESSED] {:?}, {:?}", args, self.current_state);
+
+    if self.context_menu.is_some() && args.key == Some(VirtualKeyCode::Escape) {
+      self.context_menu = None;
+      return true;
+    }
+
+    // the keymap and space-to-pan mustn't steal keys while a coordinate is
+    // being typed in
+    let typing = coordinate_entry::current_text(&self.world).is_some();
+    // nor any keys the current state has claimed (e.g. backspace removing a
+    // polyline's last vertex)
+    let claimed = self.current_state.consumes_key(&args);
+
+    if args.key == Some(VirtualKeyCode::Space) && !typing {
+      // only redraw the first time, not on every key repeat
+      let changed = !self.space_pressed;
+      self.space_pressed = true;
+      return changed;
+    }
+
+    if !typing && !claimed {
+      let action = self.world.read_resource::<Keymap>().lookup(&args).cloned();
+      if let Some(action) = action {
+        return self.run_key_action(action);
+      }
+    }
+
     self.handle_event(|state, ctx| state.on_key_pressed(ctx, &args))
   }
 
+  pub fn on_key_released(&mut self, args: KeyboardEventArgs) -> bool {
+    if args.key == Some(VirtualKeyCode::Space) {
+      self.space_pressed = false;
+      return true;
+    }
+
+    false
+  }
+
+  /// The canvas lost focus, so we won't hear about any keys or buttons being
+  /// released.
+  pub fn on_blur(&mut self) -> bool {
+    let changed = self.space_pressed || self.pan_anchor.is_some();
+    self.space_pressed = false;
+    self.pan_anchor = None;
+    changed
+  }
+
+  fn run_key_action(&mut self, action: KeyAction) -> bool {
+    log::debug!("[RUN_KEY_ACTION] {:?}", action);
+
+    match action {
+      KeyAction::Cancel => {
+        self.properties = None;
+        self.handle_event(|state, ctx| {
+          state.on_cancelled(ctx);
+          Transition::ChangeState(Box::new(Idle::default()))
+        })
+      }
+      KeyAction::Undo => self.undo(),
+      KeyAction::Redo => self.redo(),
+      KeyAction::DeleteSelection => self.on_button_clicked(ButtonType::Erase),
+      KeyAction::ZoomExtents => self.zoom_to_fit(false),
+      KeyAction::ZoomSelection => self.zoom_to_fit(true),
+      KeyAction::ToggleOrtho => {
+        self.toggle_ortho();
+        true
+      }
+      KeyAction::Command(line) => self.run_command(&line).unwrap_or_else(|e| {
+        log::warn!("Unable to run \"{}\": {}", line, e);
+        false
+      }),
+    }
+  }
+
   pub fn on_button_clicked(&mut self, args: ButtonType) -> bool {
     log::debug!("[ON_BUTTON_CLICKED] {:?}, {:?}", args, self.current_state);
     match args {
+      ButtonType::Undo => return self.undo(),
+      ButtonType::Redo => return self.redo(),
+      ButtonType::ZoomExtents => return self.zoom_to_fit(false),
+      ButtonType::ZoomSelection => return self.zoom_to_fit(true),
+      ButtonType::Copy => {
+        self.copy_selection();
+        return false;
+      }
+      ButtonType::Paste => return self.paste(),
+      ButtonType::Properties => return self.show_properties(),
+      ButtonType::Save => {
+        self.save();
+        return false;
+      }
+      ButtonType::ExportDxf => {
+        self.export_dxf();
+        return false;
+      }
+      ButtonType::ExportSvg(crop) => {
+        self.export_svg(crop);
+        return false;
+      }
+      ButtonType::DragMode => {
+        self.toggle_empty_space_drag();
+        return true;
+      }
+      ButtonType::Ortho => {
+        self.toggle_ortho();
+        return true;
+      }
+      ButtonType::PolarTracking => {
+        self.cycle_polar_tracking();
+        return true;
+      }
+      ButtonType::ObjectSnap(kind) => {
+        self.toggle_object_snap(kind);
+        return true;
+      }
       ButtonType::Snap => {
         self.snap = !self.snap;
 
@@ -166,29 +423,315 @@ impl Model {
     self.handle_event(|state, ctx| state.on_button_clicked(ctx, &args))
   }
 
+  /// Run a line typed into the console.
+  pub fn run_command(&mut self, line: &str) -> Result<bool, CommandError> {
+    let action = self.world.read_resource::<CommandRegistry>().parse(line)?;
+    log::debug!("[RUN_COMMAND] {:?} => {:?}", line, action);
+
+    Ok(self.run_action(action))
+  }
+
+  /// Run an [`Action`] from the console or the context menu.
+  pub fn run_action(&mut self, action: Action) -> bool {
+    match action {
+      Action::Button(button) => self.on_button_clicked(button),
+      _ => self.handle_event(|state, ctx| action.run(state, ctx)),
+    }
+  }
+
+  /// Run the systems over the world, then record an undo step if the current
+  /// state asked for one.
+  pub fn dispatch(&mut self) {
+    self.dispatcher.dispatch(&self.world);
+    self.world.maintain();
+
+    let changed = self
+      .world
+      .write_resource::<History>()
+      .record_pending(&self.world);
+
+    if changed {
+      self.autosave();
+    }
+  }
+
+  pub fn undo(&mut self) -> bool {
+    let changed = self.with_history(|history, world| history.undo(world));
+    if changed {
+      self.autosave();
+    }
+    changed
+  }
+
+  pub fn redo(&mut self) -> bool {
+    let changed = self.with_history(|history, world| history.redo(world));
+    if changed {
+      self.autosave();
+    }
+    changed
+  }
+
+  pub fn settings(&self) -> Settings {
+    settings::current(&self.world)
+  }
+
+  /// Switch between selecting and panning when dragging on empty space.
+  pub fn toggle_empty_space_drag(&mut self) {
+    let mut settings = self.world.write_resource::<Settings>();
+    settings.empty_space_drag = match settings.empty_space_drag {
+      EmptySpaceDrag::SelectRegion => EmptySpaceDrag::Pan,
+      EmptySpaceDrag::Pan => EmptySpaceDrag::SelectRegion,
+    };
+    utils::save_to_local_storage(SETTINGS_KEY, &settings.to_json());
+  }
+
+  /// Turn one type of object snap on or off.
+  pub fn toggle_object_snap(&mut self, kind: SnapKind) {
+    let mut settings = self.world.write_resource::<Settings>();
+    settings.object_snaps.toggle(kind);
+    utils::save_to_local_storage(SETTINGS_KEY, &settings.to_json());
+  }
+
+  /// Turn ortho mode on or off.
+  pub fn toggle_ortho(&mut self) {
+    let mut settings = self.world.write_resource::<Settings>();
+    settings.ortho = !settings.ortho;
+    utils::save_to_local_storage(SETTINGS_KEY, &settings.to_json());
+  }
+
+  /// Switch to the next polar tracking increment.
+  pub fn cycle_polar_tracking(&mut self) {
+    let mut settings = self.world.write_resource::<Settings>();
+    settings.polar_tracking = settings.polar_tracking.next();
+    utils::save_to_local_storage(SETTINGS_KEY, &settings.to_json());
+  }
+
+  /// Zoom so the whole drawing, or just the selection, fills the canvas.
+  pub fn zoom_to_fit(&mut self, selected_only: bool) -> bool {
+    self.handle_event(|_state, ctx| {
+      if !ctx.zoom_to_fit(selected_only) {
+        ctx.suppress_redraw();
+      }
+      Transition::DoNothing
+    })
+  }
+
+  /// Put the selected objects on the clipboard.
+  pub fn copy_selection(&mut self) {
+    let mut clipboard = self.world.write_resource::<Clipboard>();
+    let copied = clipboard.copy_selection(&self.world);
+    log::debug!("[COPY] {} objects", copied);
+  }
+
+  /// Paste the clipboard's contents, centred on the mouse.
+  pub fn paste(&mut self) -> bool {
+    let clipboard = self.world.read_resource::<Clipboard>().clone();
+    if clipboard.is_empty() {
+      return false;
+    }
+
+    clipboard.paste(&mut self.world, self.mouse_location, self.default_layer);
+    self.world.write_resource::<History>().request_checkpoint();
+    true
+  }
+
+  /// Open the properties panel, if there is anything selected to describe.
+  pub fn show_properties(&mut self) -> bool {
+    let properties = properties::describe_selection(&self.world);
+    self.properties = if properties.is_empty() {
+      None
+    } else {
+      Some(properties)
+    };
+
+    true
+  }
+
+  /// Get a copy of the current drawing.
+  pub fn document(&self) -> Document {
+    Document::from_world(&self.world, self.window.0)
+  }
+
+  /// Replace the current drawing with one loaded from JSON.
+  pub fn load_document(&mut self, json: &str) -> Result<(), DocumentError> {
+    let document = Document::from_json(json)?;
+    self.load(document)
+  }
+
+  /// Replace the current drawing with the contents of a DXF file, keeping
+  /// the current view.
+  pub fn import_dxf(&mut self, text: &str) -> Result<(), DocumentError> {
+    let mut document = dxf::read(text)?;
+    document.viewport = self.document().viewport;
+    self.load(document)
+  }
+
+  /// Let the user download the drawing as a DXF file.
+  pub fn export_dxf(&self) {
+    let text = match dxf::write(&self.document()) {
+      Ok(text) => text,
+      Err(e) => {
+        log::error!("Unable to export the drawing: {}", e);
+        return;
+      }
+    };
+
+    if let Err(e) = utils::download("drawing.dxf", "image/vnd.dxf", &text) {
+      log::error!("Unable to download the drawing: {:?}", e);
+    }
+  }
+
+  /// Let the user download the drawing as an SVG image.
+  pub fn export_svg(&self, crop: Crop) {
+    let image = svg::export(&self.document(), crop, self.canvas_size);
+
+    if let Err(e) = utils::download("drawing.svg", "image/svg+xml", &image) {
+      log::error!("Unable to download the drawing: {:?}", e);
+    }
+  }
+
+  fn load(&mut self, document: Document) -> Result<(), DocumentError> {
+    self.cancel_current_state();
+
+    let layers = document.load_into(&mut self.world, self.window.0)?;
+    self.default_layer = self
+      .find_default_layer(&layers)
+      .unwrap_or_else(|| create_default_layer(&mut self.world));
+    self.world.write_storage::<Selected>().clear();
+    self.world.write_resource::<History>().reset(&self.world);
+    self.autosave();
+
+    Ok(())
+  }
+
+  /// Save the drawing to local storage and let the user download it.
+  pub fn save(&self) {
+    let json = self.document().to_json();
+    utils::save_to_local_storage(AUTOSAVE_KEY, &json);
+
+    if let Err(e) = utils::download("drawing.json", "application/json", &json) {
+      log::error!("Unable to download the drawing: {:?}", e);
+    }
+  }
+
+  fn autosave(&self) {
+    utils::save_to_local_storage(AUTOSAVE_KEY, &self.document().to_json());
+  }
+
+  /// Prefer the layer called "default_layer", falling back to the first one.
+  fn find_default_layer(&self, layers: &[Entity]) -> Option<Entity> {
+    let names = self.world.read_storage::<Name>();
+
+    layers
+      .iter()
+      .copied()
+      .find(|&layer| names.get(layer).map_or(false, |n| n.to_string() == "default_layer"))
+      .or_else(|| layers.first().copied())
+  }
+
+  pub fn can_undo(&self) -> bool {
+    self.world.read_resource::<History>().can_undo()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    self.world.read_resource::<History>().can_redo()
+  }
+
+  /// Abandon whatever the current state was doing and clean up its
+  /// temporary objects.
+  fn cancel_current_state(&mut self) {
+    self.handle_event(|state, ctx| {
+      state.on_cancelled(ctx);
+      Transition::DoNothing
+    });
+    self.dispatcher.dispatch(&self.world);
+    self.world.maintain();
+  }
+
+  /// Make sure the current state's temporary objects don't end up in the
+  /// [`History`], then let `func` update the drawing.
+  fn with_history<F>(&mut self, func: F) -> bool
+  where
+    F: FnOnce(&mut History, &mut World) -> bool,
+  {
+    self.cancel_current_state();
+
+    let mut history = self.world.remove::<History>().unwrap_or_default();
+    let changed = func(&mut history, &mut self.world);
+    self.world.insert(history);
+
+    changed
+  }
+
   fn handle_transition(&mut self, transition: Transition) {
     match transition {
       Transition::ChangeState(new_state) => {
         log::debug!("Changing state {:?} => {:?}", self.current_state, new_state);
+        // the snap marker, tracking guide and typed text belong to the old state
+        snaps::clear(&mut self.world);
+        tracking::clear(&mut self.world);
+        coordinate_entry::close(&mut self.world);
         self.current_state = new_state
       }
       Transition::DoNothing => {}
     }
   }
 
-  fn mouse_event_args(&self, cursor: Point2D<f64, CanvasSpace>) -> MouseEventArgs {
+  fn mouse_event_args(&self, event: CanvasMouseEvent) -> MouseEventArgs {
     let viewports = self.world.read_storage();
     let viewport = self.window.viewport(&viewports);
-    let location = arcs::window::to_drawing_coordinates(cursor, viewport, self.canvas_size);
+    let location = arcs::window::to_drawing_coordinates(event.cursor, viewport, self.canvas_size);
 
     MouseEventArgs {
       location,
-      cursor,
-      button_state: MouseButtons::LEFT_BUTTON,
+      cursor: event.cursor,
+      button_state: event.buttons,
+      shift_pressed: event.shift_pressed,
+      control_pressed: event.control_pressed,
+    }
+  }
+}
+
+fn load_settings() -> Settings {
+  match utils::load_from_local_storage(SETTINGS_KEY).map(|json| Settings::from_json(&json)) {
+    Some(Ok(settings)) => settings,
+    Some(Err(e)) => {
+      log::warn!("Unable to load the saved settings: {}", e);
+      Settings::default()
+    }
+    None => Settings::default(),
+  }
+}
+
+fn load_keymap() -> Keymap {
+  match utils::load_from_local_storage(KEYMAP_KEY).map(|json| Keymap::from_json(&json)) {
+    Some(Ok(saved)) => {
+      let keymap = Keymap::with_overrides(&saved);
+      // older versions saved the whole keymap, so trim it down to the
+      // bindings the user actually changed
+      let overrides = keymap.overrides();
+      if overrides != saved {
+        utils::save_to_local_storage(KEYMAP_KEY, &overrides.to_json());
+      }
+      keymap
     }
+    Some(Err(e)) => {
+      log::warn!("Unable to load the saved keymap: {}", e);
+      Keymap::default()
+    }
+    None => Keymap::default(),
   }
 }
 
+fn create_default_layer(world: &mut World) -> Entity {
+  let builder = world.create_entity().with(PointStyle {
+    radius: Dimension::Pixels(3.0),
+    ..Default::default()
+  });
+
+  Layer::create(builder, Name::new("default_layer"), Layer::default())
+}
+
 /// A temporary struct which presents a "view" of [`Model`] which can be used
 /// as a [`ApplicationContext`].
 struct Context<'model> {
@@ -199,6 +742,9 @@ struct Context<'model> {
   pointer: Entity,
   grid: Entity,
   command: Entity,
+  system_layer: Entity,
+  canvas_size: Size2D<f64, CanvasSpace>,
+  dispatcher: &'model mut Dispatcher<'static, 'static>,
 }
 
 impl<'model> ApplicationContext for Context<'model> {
@@ -218,10 +764,18 @@ impl<'model> ApplicationContext for Context<'model> {
     self.default_layer
   }
 
+  fn system_layer(&self) -> Entity {
+    self.system_layer
+  }
+
   fn suppress_redraw(&mut self) {
     *self.suppress_redraw = true;
   }
 
+  fn canvas_size(&self) -> Size2D<f64, CanvasSpace> {
+    self.canvas_size
+  }
+
   fn pointer(&self) -> Entity {
     self.pointer
   }
@@ -232,4 +786,9 @@ impl<'model> ApplicationContext for Context<'model> {
   fn command(&self) -> Entity {
     self.command
   }
+
+  fn run_systems(&mut self) {
+    self.dispatcher.dispatch(self.world);
+    self.world.maintain();
+  }
 }
@@ -1,6 +1,693 @@
-use crate::modes::State;
+use crate::{
+    commands::Action,
+    components::Preview,
+    context_menu::MenuItem,
+    modes::{
+        ApplicationContext, KeyboardEventArgs, MouseEventArgs, State, Transition, VirtualKeyCode,
+    },
+    msg::ButtonType,
+};
+use arcs::{
+    components::{DrawingObject, Geometry},
+    euclid::Angle,
+    primitives::{Arc, Line},
+    specs::prelude::*,
+    Point,
+};
 
+/// Start points closer than this many pixels to the centre would give an arc
+/// too small to see, so they're ignored.
+const MIN_RADIUS_PIXELS: f64 = 1.0;
+
+/// The different ways an arc can be constructed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArcConstruction {
+    /// Click the start point, a point the arc passes through, then the end
+    /// point.
+    ThreePoint,
+    /// Click the centre, the start point (which sets the radius), then the
+    /// end point. The arc is swept anticlockwise from start to end.
+    CentreStartEnd,
+}
+
+impl Default for ArcConstruction {
+    fn default() -> ArcConstruction {
+        ArcConstruction::ThreePoint
+    }
+}
+
+#[derive(Debug)]
+pub struct AddArcMode {
+    construction: ArcConstruction,
+    nested: Box<dyn State>,
+}
+
+impl AddArcMode {
+    pub fn new(construction: ArcConstruction) -> AddArcMode {
+        AddArcMode {
+            construction,
+            nested: initial_state(construction),
+        }
+    }
+
+    fn handle_transition(&mut self, transition: Transition) {
+        match transition {
+            Transition::ChangeState(new_state) => {
+                log::debug!("Changing state {:?} -> {:?}", self.nested, new_state);
+                self.nested = new_state;
+            }
+            Transition::DoNothing => {}
+        }
+    }
+
+    fn change_construction(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        construction: ArcConstruction,
+    ) {
+        if construction != self.construction {
+            self.nested.on_cancelled(ctx);
+            self.construction = construction;
+            self.nested = initial_state(construction);
+        }
+    }
+}
+
+impl State for AddArcMode {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
+        let trans = self.nested.on_mouse_down(ctx, args);
+        self.handle_transition(trans);
+        Transition::DoNothing
+    }
+
+    fn on_mouse_up(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
+        let trans = self.nested.on_mouse_up(ctx, args);
+        self.handle_transition(trans);
+        Transition::DoNothing
+    }
+
+    fn on_key_pressed(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &KeyboardEventArgs,
+    ) -> Transition {
+        match args.key {
+            Some(VirtualKeyCode::C) => {
+                self.change_construction(ctx, ArcConstruction::CentreStartEnd);
+                return Transition::DoNothing;
+            }
+            Some(VirtualKeyCode::T) => {
+                self.change_construction(ctx, ArcConstruction::ThreePoint);
+                return Transition::DoNothing;
+            }
+            _ => {}
+        }
+
+        let trans = self.nested.on_key_pressed(ctx, args);
+        self.handle_transition(trans);
+        Transition::DoNothing
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let trans = self.nested.on_mouse_move(ctx, args);
+        self.handle_transition(trans);
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        self.nested.on_cancelled(ctx);
+        self.nested = initial_state(self.construction);
+    }
+
+    fn get_cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        vec![
+            MenuItem::new("Three Point", Action::Key(VirtualKeyCode::T)),
+            MenuItem::new("Centre, Start, End", Action::Key(VirtualKeyCode::C)),
+            MenuItem::new("Cancel", Action::Button(ButtonType::Select)),
+        ]
+    }
+}
+
+impl Default for AddArcMode {
+    fn default() -> AddArcMode {
+        AddArcMode::new(ArcConstruction::default())
+    }
+}
+
+fn initial_state(construction: ArcConstruction) -> Box<dyn State> {
+    match construction {
+        ArcConstruction::ThreePoint => Box::new(WaitingToPlaceStart::default()),
+        ArcConstruction::CentreStartEnd => Box::new(WaitingToPlaceCentre::default()),
+    }
+}
+
+/// Create the temporary entity used to show the user what they'll get.
+fn create_preview(ctx: &mut dyn ApplicationContext, geometry: Geometry) -> Entity {
+    let layer = ctx.default_layer();
+
+    ctx.world_mut()
+        .create_entity()
+        .with(DrawingObject { geometry, layer })
+        .with(Preview)
+        .build()
+}
+
+fn update_preview(ctx: &mut dyn ApplicationContext, preview: Entity, geometry: Geometry) {
+    let mut drawing_objects = ctx.world().write_storage::<DrawingObject>();
+
+    if let Some(drawing_object) = drawing_objects.get_mut(preview) {
+        drawing_object.geometry = geometry;
+    }
+}
+
+/// Turn the preview into a real part of the drawing.
+fn keep_preview(ctx: &mut dyn ApplicationContext, preview: Entity) {
+    ctx.world().write_storage::<Preview>().remove(preview);
+}
+
+fn delete_preview(ctx: &mut dyn ApplicationContext, preview: Entity) {
+    if let Err(e) = ctx.world_mut().delete_entity(preview) {
+        log::warn!("Unable to delete the arc preview: {}", e);
+    }
+}
+
+/// The arc passing through three points, falling back to a straight line when
+/// the points are collinear.
+fn three_point_geometry(start: Point, middle: Point, end: Point) -> Geometry {
+    match Arc::from_three_points(start, middle, end) {
+        Some(arc) => Geometry::Arc(arc),
+        None => Geometry::Line(Line::new(start, end)),
+    }
+}
+
+/// The anticlockwise arc centred on `centre` which starts at `start` and
+/// stops on the ray pointing towards `end`.
+fn centre_start_end_geometry(centre: Point, start: Point, end: Point) -> Geometry {
+    let radius = (start - centre).length();
+    let start_angle = (start - centre).angle_from_x_axis();
+    let end_angle = (end - centre).angle_from_x_axis();
+    let mut sweep_angle = (end_angle - start_angle).positive();
+
+    if sweep_angle == Angle::zero() {
+        sweep_angle = Angle::two_pi();
+    }
+
+    Geometry::Arc(Arc::from_centre_radius(
+        centre,
+        radius,
+        start_angle,
+        sweep_angle,
+    ))
+}
+
+///////////////////////////////////////////////////////
+/// Three point construction
+/// The base sub-state for [`ArcConstruction::ThreePoint`]. We're waiting for
+/// the user to click the arc's start point.
 #[derive(Debug, Default)]
-pub struct AddArcMode;
+struct WaitingToPlaceStart;
+
+impl State for WaitingToPlaceStart {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        // make sure nothing else is selected
+        ctx.unselect_all();
+
+        let start = ctx.set_cursor_position(args.location);
+        let preview = create_preview(ctx, Geometry::Line(Line::new(start, start)));
+
+        Transition::ChangeState(Box::new(WaitingToPlaceMiddle { start, preview }))
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        ctx.suppress_redraw();
+        ctx.set_cursor_position(args.location);
+
+        Transition::DoNothing
+    }
+}
+
+/// The start point has been placed and we're rubber-banding a chord until the
+/// user clicks a point on the arc.
+#[derive(Debug)]
+struct WaitingToPlaceMiddle {
+    start: Point,
+    preview: Entity,
+}
+
+impl State for WaitingToPlaceMiddle {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let middle = ctx.set_cursor_position(args.location);
+
+        Transition::ChangeState(Box::new(WaitingToPlaceThreePointEnd {
+            start: self.start,
+            middle,
+            preview: self.preview,
+        }))
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let location = ctx.set_cursor_position(args.location);
+        update_preview(
+            ctx,
+            self.preview,
+            Geometry::Line(Line::new(self.start, location)),
+        );
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        // make sure we clean up the temporary chord.
+        delete_preview(ctx, self.preview);
+    }
+}
+
+/// The start and middle points are known, the arc follows the cursor until
+/// the user clicks its end point.
+#[derive(Debug)]
+struct WaitingToPlaceThreePointEnd {
+    start: Point,
+    middle: Point,
+    preview: Entity,
+}
+
+impl State for WaitingToPlaceThreePointEnd {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let end = ctx.set_cursor_position(args.location);
+
+        match Arc::from_three_points(self.start, self.middle, end) {
+            Some(arc) => {
+                update_preview(ctx, self.preview, Geometry::Arc(arc));
+                keep_preview(ctx, self.preview);
+                ctx.unselect_all();
+                ctx.checkpoint();
+                Transition::ChangeState(Box::new(WaitingToPlaceStart::default()))
+            }
+            None => {
+                // collinear points don't make an arc, let the user try again
+                log::debug!("Ignoring a collinear arc end point at {:?}", end);
+                Transition::DoNothing
+            }
+        }
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let end = ctx.set_cursor_position(args.location);
+        update_preview(
+            ctx,
+            self.preview,
+            three_point_geometry(self.start, self.middle, end),
+        );
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        // make sure we clean up the temporary arc.
+        delete_preview(ctx, self.preview);
+    }
+}
+
+///////////////////////////////////////////////////////
+/// Centre, start, end construction
+/// The base sub-state for [`ArcConstruction::CentreStartEnd`]. We're waiting
+/// for the user to click the arc's centre.
+#[derive(Debug, Default)]
+struct WaitingToPlaceCentre;
+
+impl State for WaitingToPlaceCentre {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        // make sure nothing else is selected
+        ctx.unselect_all();
+
+        let centre = ctx.set_cursor_position(args.location);
+        let preview = create_preview(ctx, Geometry::Line(Line::new(centre, centre)));
+
+        Transition::ChangeState(Box::new(WaitingToPlaceRadius { centre, preview }))
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        ctx.suppress_redraw();
+        ctx.set_cursor_position(args.location);
+
+        Transition::DoNothing
+    }
+}
+
+/// The centre has been placed and we're showing the radius until the user
+/// clicks the arc's start point.
+#[derive(Debug)]
+struct WaitingToPlaceRadius {
+    centre: Point,
+    preview: Entity,
+}
+
+impl State for WaitingToPlaceRadius {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let start = ctx.set_cursor_position(args.location);
+
+        let radius_pixels = (start - self.centre).length() * ctx.pixels_per_drawing_unit();
+        if radius_pixels < MIN_RADIUS_PIXELS {
+            // a zero radius arc isn't much use to anyone
+            return Transition::DoNothing;
+        }
+
+        update_preview(
+            ctx,
+            self.preview,
+            centre_start_end_geometry(self.centre, start, start),
+        );
+
+        Transition::ChangeState(Box::new(WaitingToPlaceSweepEnd {
+            centre: self.centre,
+            start,
+            preview: self.preview,
+        }))
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let location = ctx.set_cursor_position(args.location);
+        update_preview(
+            ctx,
+            self.preview,
+            Geometry::Line(Line::new(self.centre, location)),
+        );
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        // make sure we clean up the temporary radius.
+        delete_preview(ctx, self.preview);
+    }
+}
+
+/// The centre and radius are known, the arc sweeps around to follow the
+/// cursor until the user clicks its end point.
+#[derive(Debug)]
+struct WaitingToPlaceSweepEnd {
+    centre: Point,
+    start: Point,
+    preview: Entity,
+}
+
+impl State for WaitingToPlaceSweepEnd {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let end = ctx.set_cursor_position(args.location);
+        update_preview(
+            ctx,
+            self.preview,
+            centre_start_end_geometry(self.centre, self.start, end),
+        );
+        keep_preview(ctx, self.preview);
+        ctx.unselect_all();
+        ctx.checkpoint();
+
+        Transition::ChangeState(Box::new(WaitingToPlaceCentre::default()))
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let end = ctx.set_cursor_position(args.location);
+        update_preview(
+            ctx,
+            self.preview,
+            centre_start_end_geometry(self.centre, self.start, end),
+        );
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        // make sure we clean up the temporary arc.
+        delete_preview(ctx, self.preview);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry, history,
+        modes::test_context::{mouse_at, DummyContext},
+    };
+    use std::f64::consts::PI;
+
+    fn click(mode: &mut AddArcMode, drawing: &mut DummyContext, x: f64, y: f64) {
+        let args = mouse_at(x, y);
+        mode.on_mouse_down(drawing, &args);
+        mode.on_mouse_up(drawing, &args);
+    }
+
+    fn draw(
+        construction: ArcConstruction,
+        drawing: &mut DummyContext,
+        points: &[(f64, f64)],
+    ) -> AddArcMode {
+        let mut mode = AddArcMode::new(construction);
+
+        for &(x, y) in points {
+            click(&mut mode, drawing, x, y);
+        }
+
+        mode
+    }
+
+    /// The arcs which have been kept, ignoring the grid and any previews.
+    fn arcs(drawing: &DummyContext) -> Vec<Arc> {
+        let drawing_objects = drawing.world.read_storage::<DrawingObject>();
+        let previews = drawing.world.read_storage::<Preview>();
+
+        (&drawing_objects, !&previews)
+            .join()
+            .filter_map(|(drawing_object, _)| match &drawing_object.geometry {
+                Geometry::Arc(arc) => Some(Arc::from_centre_radius(
+                    arc.centre(),
+                    arc.radius(),
+                    arc.start_angle(),
+                    arc.sweep_angle(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn preview_count(drawing: &mut DummyContext) -> usize {
+        drawing.world.maintain();
+        drawing.world.read_storage::<Preview>().join().count()
+    }
+
+    fn assert_close(left: f64, right: f64) {
+        assert!((left - right).abs() < 1e-9, "{} != {}", left, right);
+    }
+
+    #[test]
+    fn three_point_arcs_pass_through_the_middle_point() {
+        let mut drawing = DummyContext::default();
+
+        draw(
+            ArcConstruction::ThreePoint,
+            &mut drawing,
+            &[(10.0, 0.0), (0.0, 10.0), (-10.0, 0.0)],
+        );
+
+        let got = arcs(&drawing);
+        assert_eq!(got.len(), 1);
+        assert!((got[0].centre() - Point::zero()).length() < 1e-9);
+        assert_close(got[0].radius(), 10.0);
+        assert_close(got[0].sweep_angle().radians.abs(), PI);
+        // it goes over the top rather than underneath
+        assert!(geometry::arc_contains_angle(&got[0], Angle::frac_pi_2()));
+    }
+
+    #[test]
+    fn centre_start_end_arcs_sweep_anticlockwise() {
+        let mut drawing = DummyContext::default();
+
+        draw(
+            ArcConstruction::CentreStartEnd,
+            &mut drawing,
+            &[(0.0, 0.0), (10.0, 0.0), (0.0, -10.0)],
+        );
+
+        let got = arcs(&drawing);
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].centre(), Point::zero());
+        assert_close(got[0].radius(), 10.0);
+        assert_close(got[0].start_angle().radians, 0.0);
+        assert_close(got[0].sweep_angle().radians, 1.5 * PI);
+    }
+
+    #[test]
+    fn ending_on_the_start_makes_a_full_circle() {
+        let mut drawing = DummyContext::default();
+
+        draw(
+            ArcConstruction::CentreStartEnd,
+            &mut drawing,
+            &[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)],
+        );
+
+        let got = arcs(&drawing);
+        assert_eq!(got.len(), 1);
+        assert_close(got[0].sweep_angle().radians, 2.0 * PI);
+    }
+
+    #[test]
+    fn a_collinear_end_point_is_ignored() {
+        let mut drawing = DummyContext::default();
+        let mut mode = draw(
+            ArcConstruction::ThreePoint,
+            &mut drawing,
+            &[(0.0, 0.0), (5.0, 0.0), (10.0, 0.0)],
+        );
+
+        assert!(arcs(&drawing).is_empty());
+        assert_eq!(preview_count(&mut drawing), 1);
+
+        // we're still waiting for a usable end point
+        click(&mut mode, &mut drawing, 5.0, 5.0);
+        assert_eq!(arcs(&drawing).len(), 1);
+    }
+
+    #[test]
+    fn a_zero_radius_is_rejected() {
+        let mut drawing = DummyContext::default();
+
+        draw(
+            ArcConstruction::CentreStartEnd,
+            &mut drawing,
+            &[(0.0, 0.0), (0.0, 0.0), (10.0, 0.0), (0.0, 10.0)],
+        );
+
+        let got = arcs(&drawing);
+        assert_eq!(got.len(), 1);
+        assert_close(got[0].radius(), 10.0);
+    }
+
+    #[test]
+    fn changing_construction_throws_the_preview_away() {
+        let mut drawing = DummyContext::default();
+        let mut mode = draw(ArcConstruction::ThreePoint, &mut drawing, &[(0.0, 0.0)]);
+        assert_eq!(preview_count(&mut drawing), 1);
+
+        mode.on_key_pressed(
+            &mut drawing,
+            &KeyboardEventArgs::pressing(VirtualKeyCode::C),
+        );
+        assert_eq!(preview_count(&mut drawing), 0);
+
+        click(&mut mode, &mut drawing, 0.0, 0.0);
+        assert_eq!(preview_count(&mut drawing), 1);
+        mode.on_key_pressed(
+            &mut drawing,
+            &KeyboardEventArgs::pressing(VirtualKeyCode::T),
+        );
+        assert_eq!(preview_count(&mut drawing), 0);
+        assert!(arcs(&drawing).is_empty());
+    }
+
+    #[test]
+    fn cancelling_throws_the_preview_away() {
+        let mut drawing = DummyContext::default();
+        let mut mode = draw(
+            ArcConstruction::CentreStartEnd,
+            &mut drawing,
+            &[(0.0, 0.0), (10.0, 0.0)],
+        );
+        assert_eq!(preview_count(&mut drawing), 1);
+
+        mode.on_cancelled(&mut drawing);
+
+        assert_eq!(preview_count(&mut drawing), 0);
+        assert!(arcs(&drawing).is_empty());
+    }
+
+    #[test]
+    fn the_finished_arc_is_kept_and_recorded() {
+        let mut drawing = DummyContext::default();
+        history::history_mut(&mut drawing.world);
+
+        draw(
+            ArcConstruction::ThreePoint,
+            &mut drawing,
+            &[(10.0, 0.0), (0.0, 10.0), (-10.0, 0.0)],
+        );
 
-impl State for AddArcMode {}
+        assert_eq!(preview_count(&mut drawing), 0);
+        assert_eq!(arcs(&drawing).len(), 1);
+        let mut history = drawing.world.write_resource::<history::History>();
+        assert!(history.record_pending(&drawing.world));
+    }
+}
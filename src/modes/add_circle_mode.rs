@@ -0,0 +1,319 @@
+use crate::{
+    commands::Action,
+    components::Preview,
+    context_menu::MenuItem,
+    coordinate_entry::{self, KeyOutcome},
+    modes::{
+        ApplicationContext, KeyboardEventArgs, MouseEventArgs, State, Transition, VirtualKeyCode,
+    },
+    msg::ButtonType,
+};
+use arcs::{
+    components::{DrawingObject, Geometry},
+    euclid::Angle,
+    primitives::{Arc, Line},
+    specs::prelude::*,
+    Point,
+};
+
+/// The different ways a circle can be constructed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CircleConstruction {
+    /// Click the centre, then a point on the circle.
+    CentreRadius,
+    /// Click both ends of a diameter.
+    TwoPoint,
+    /// Click three points on the circle.
+    ThreePoint,
+}
+
+impl CircleConstruction {
+    /// How many clicks it takes to finish a circle.
+    fn points_needed(self) -> usize {
+        match self {
+            CircleConstruction::CentreRadius | CircleConstruction::TwoPoint => 2,
+            CircleConstruction::ThreePoint => 3,
+        }
+    }
+}
+
+impl Default for CircleConstruction {
+    fn default() -> CircleConstruction {
+        CircleConstruction::CentreRadius
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AddCircleMode {
+    construction: CircleConstruction,
+    /// The points clicked so far.
+    points: Vec<Point>,
+    /// The temporary entity used to show the user what they'll get.
+    preview: Option<Entity>,
+}
+
+impl AddCircleMode {
+    pub fn new(construction: CircleConstruction) -> AddCircleMode {
+        AddCircleMode {
+            construction,
+            ..Default::default()
+        }
+    }
+
+    fn change_construction(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        construction: CircleConstruction,
+    ) {
+        if construction != self.construction {
+            self.on_cancelled(ctx);
+            self.construction = construction;
+        }
+    }
+
+    fn place_point(&mut self, ctx: &mut dyn ApplicationContext, location: Point) {
+        if self.points.is_empty() {
+            // make sure nothing else is selected
+            ctx.unselect_all();
+        }
+
+        let geometry = circle_geometry(self.construction, &self.points, location);
+        let finished = self.points.len() + 1 == self.construction.points_needed();
+
+        if finished {
+            match geometry {
+                Some(Geometry::Arc(circle)) => {
+                    self.update_preview(ctx, Geometry::Arc(circle));
+                    // the preview becomes the real circle
+                    if let Some(preview) = self.preview.take() {
+                        ctx.world().write_storage::<Preview>().remove(preview);
+                    }
+                    self.points.clear();
+                    ctx.checkpoint();
+                }
+                _ => {
+                    // a zero-sized or collinear circle, let the user try again
+                    log::debug!("Ignoring a degenerate circle point at {:?}", location);
+                }
+            }
+            return;
+        }
+
+        self.points.push(location);
+        let first = self.points[0];
+        let geometry = circle_geometry(self.construction, &self.points, location)
+            .unwrap_or_else(|| Geometry::Line(Line::new(first, location)));
+        self.update_preview(ctx, geometry);
+    }
+
+    fn update_preview(&mut self, ctx: &mut dyn ApplicationContext, geometry: Geometry) {
+        match self.preview {
+            Some(preview) => {
+                let mut drawing_objects = ctx.world().write_storage::<DrawingObject>();
+                if let Some(drawing_object) = drawing_objects.get_mut(preview) {
+                    drawing_object.geometry = geometry;
+                }
+            }
+            None => {
+                let layer = ctx.default_layer();
+                let preview = ctx
+                    .world_mut()
+                    .create_entity()
+                    .with(DrawingObject { geometry, layer })
+                    .with(Preview)
+                    .build();
+                self.preview = Some(preview);
+            }
+        }
+    }
+}
+
+impl State for AddCircleMode {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
+        let location = ctx.set_cursor_position(args.location);
+        self.place_point(ctx, location);
+
+        Transition::DoNothing
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let snapped = ctx.snap_to_objects(args.location);
+        let location = ctx.set_cursor_position(args.location);
+
+        if self.points.is_empty() {
+            // only redraw when the snap marker needs to move
+            if !snapped {
+                ctx.suppress_redraw();
+            }
+            return Transition::DoNothing;
+        }
+
+        let first = self.points[0];
+        let geometry = circle_geometry(self.construction, &self.points, location)
+            .unwrap_or_else(|| Geometry::Line(Line::new(first, location)));
+        self.update_preview(ctx, geometry);
+
+        Transition::DoNothing
+    }
+
+    fn on_key_pressed(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &KeyboardEventArgs,
+    ) -> Transition {
+        match coordinate_entry::on_key_pressed(ctx.world_mut(), args) {
+            KeyOutcome::Ignored => {}
+            KeyOutcome::Committed(coordinate) => {
+                let previous = self.points.last().copied().unwrap_or_else(Point::origin);
+                let location = coordinate.resolve(previous);
+                ctx.typed_click_at(location);
+                self.place_point(ctx, location);
+                return Transition::DoNothing;
+            }
+            KeyOutcome::Edited | KeyOutcome::Cancelled => return Transition::DoNothing,
+        }
+
+        match args.key {
+            Some(VirtualKeyCode::R) => {
+                self.change_construction(ctx, CircleConstruction::CentreRadius)
+            }
+            Some(VirtualKeyCode::D) => self.change_construction(ctx, CircleConstruction::TwoPoint),
+            Some(VirtualKeyCode::T) => {
+                self.change_construction(ctx, CircleConstruction::ThreePoint)
+            }
+            _ => {}
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        coordinate_entry::close(ctx.world_mut());
+        self.points.clear();
+
+        // make sure we clean up the temporary circle.
+        if let Some(preview) = self.preview.take() {
+            if let Err(e) = ctx.world_mut().delete_entity(preview) {
+                log::warn!("Unable to delete the circle preview: {}", e);
+            }
+        }
+    }
+
+    fn get_cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        vec![
+            MenuItem::new("Centre, Radius", Action::Key(VirtualKeyCode::R)),
+            MenuItem::new("Two Point", Action::Key(VirtualKeyCode::D)),
+            MenuItem::new("Three Point", Action::Key(VirtualKeyCode::T)),
+            MenuItem::new("Cancel", Action::Button(ButtonType::Select)),
+        ]
+    }
+}
+
+/// The circle defined by the points clicked so far and the cursor, or `None`
+/// if they don't define one (yet).
+fn circle_geometry(
+    construction: CircleConstruction,
+    points: &[Point],
+    cursor: Point,
+) -> Option<Geometry> {
+    let (centre, radius) = match (construction, points) {
+        (CircleConstruction::CentreRadius, &[centre]) => (centre, (cursor - centre).length()),
+        (CircleConstruction::TwoPoint, &[first]) => {
+            (first.lerp(cursor, 0.5), (cursor - first).length() / 2.0)
+        }
+        (CircleConstruction::ThreePoint, &[first, second]) => {
+            let arc = Arc::from_three_points(first, second, cursor)?;
+            (arc.centre(), arc.radius())
+        }
+        _ => return None,
+    };
+
+    if radius > 0.0 {
+        Some(circle(centre, radius))
+    } else {
+        None
+    }
+}
+
+/// `arcs` doesn't have a circle primitive, so use an arc which goes all the
+/// way around.
+fn circle(centre: Point, radius: f64) -> Geometry {
+    Geometry::Arc(Arc::from_centre_radius(
+        centre,
+        radius,
+        Angle::zero(),
+        Angle::two_pi(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn centre_and_radius(geometry: Option<Geometry>) -> (Point, f64) {
+        match geometry {
+            Some(Geometry::Arc(arc)) => (arc.centre(), arc.radius()),
+            other => panic!("Expected a circle but found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn two_points_are_a_diameter() {
+        let got = circle_geometry(
+            CircleConstruction::TwoPoint,
+            &[Point::new(-2.0, 1.0)],
+            Point::new(4.0, 1.0),
+        );
+
+        assert_eq!(centre_and_radius(got), (Point::new(1.0, 1.0), 3.0));
+    }
+
+    #[test]
+    fn circle_through_three_points() {
+        let got = circle_geometry(
+            CircleConstruction::ThreePoint,
+            &[Point::new(5.0, 0.0), Point::new(0.0, 5.0)],
+            Point::new(-5.0, 0.0),
+        );
+
+        let (centre, radius) = centre_and_radius(got);
+        assert!((centre - Point::zero()).length() < 1e-9);
+        assert!((radius - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn degenerate_circles_are_rejected() {
+        let centre = Point::new(1.0, 1.0);
+        let inputs = vec![
+            (CircleConstruction::CentreRadius, vec![centre], centre),
+            (
+                CircleConstruction::ThreePoint,
+                vec![Point::zero(), Point::new(1.0, 1.0)],
+                Point::new(2.0, 2.0),
+            ),
+            // still waiting for the second point
+            (CircleConstruction::ThreePoint, vec![centre], Point::zero()),
+        ];
+
+        for (construction, points, cursor) in inputs {
+            let got = circle_geometry(construction, &points, cursor);
+            assert!(got.is_none(), "{:?} {:?}", construction, points);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    commands::Action,
+    context_menu::MenuItem,
+    coordinate_entry::{self, KeyOutcome},
+    ellipse::Ellipse,
+    modes::{
+        outline::Outline, ApplicationContext, KeyboardEventArgs, MouseEventArgs, State, Transition,
+        VirtualKeyCode,
+    },
+    msg::ButtonType,
+    shapes::{Shape, ShapeGeometry},
+};
+use arcs::{primitives::Line, specs::prelude::*, Point};
+
+/// Whether to draw a whole ellipse or only part of one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EllipseKind {
+    /// Click the centre, the end of the major axis, then a point setting the
+    /// length of the minor axis.
+    Full,
+    /// Like [`EllipseKind::Full`], followed by the start and end points of
+    /// the arc. The arc goes anticlockwise from start to end.
+    Arc,
+}
+
+impl EllipseKind {
+    /// How many clicks it takes to finish the ellipse.
+    fn points_needed(self) -> usize {
+        match self {
+            EllipseKind::Full => 3,
+            EllipseKind::Arc => 5,
+        }
+    }
+}
+
+impl Default for EllipseKind {
+    fn default() -> EllipseKind {
+        EllipseKind::Full
+    }
+}
+
+/// Draw ellipses and elliptical arcs.
+///
+/// The finished [`Ellipse`] goes on the drawing as a [`Shape`], but until
+/// then it's previewed with an [`Outline`].
+#[derive(Debug, Default)]
+pub struct AddEllipseMode {
+    kind: EllipseKind,
+    /// The points clicked so far.
+    points: Vec<Point>,
+    outline: Outline,
+}
+
+impl AddEllipseMode {
+    pub fn new(kind: EllipseKind) -> AddEllipseMode {
+        AddEllipseMode {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    fn change_kind(&mut self, ctx: &mut dyn ApplicationContext, kind: EllipseKind) {
+        if kind != self.kind {
+            self.on_cancelled(ctx);
+            self.kind = kind;
+        }
+    }
+
+    fn place_point(&mut self, ctx: &mut dyn ApplicationContext, location: Point) {
+        if self.points.is_empty() {
+            ctx.unselect_all();
+        }
+
+        let ellipse = preview_ellipse(&self.points, location);
+
+        if self.points.len() + 1 == self.kind.points_needed() {
+            match ellipse {
+                Some(ellipse) => {
+                    self.outline.clear(ctx);
+                    let layer = ctx.default_layer();
+                    ctx.world_mut()
+                        .create_entity()
+                        .with(Shape {
+                            geometry: ShapeGeometry::Ellipse(ellipse),
+                            layer,
+                        })
+                        .build();
+                    self.points.clear();
+                    ctx.checkpoint();
+                }
+                None => {
+                    log::debug!("Ignoring a degenerate ellipse point at {:?}", location);
+                }
+            }
+            return;
+        }
+
+        let usable = match self.points.as_slice() {
+            [] => true,
+            &[centre] => location != centre,
+            _ => ellipse.is_some(),
+        };
+
+        if usable {
+            self.points.push(location);
+            self.update_preview(ctx, location);
+        }
+    }
+
+    fn update_preview(&mut self, ctx: &mut dyn ApplicationContext, cursor: Point) {
+        let lines = match (preview_ellipse(&self.points, cursor), self.points.last()) {
+            (Some(ellipse), _) => ellipse.approximate(),
+            (None, Some(&last)) => vec![Line::new(last, cursor)],
+            (None, None) => Vec::new(),
+        };
+
+        self.outline.update_lines(ctx, lines);
+    }
+}
+
+impl State for AddEllipseMode {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
+        let location = ctx.set_cursor_position(args.location);
+        self.place_point(ctx, location);
+
+        Transition::DoNothing
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let snapped = ctx.snap_to_objects(args.location);
+        let location = ctx.set_cursor_position(args.location);
+
+        if !self.points.is_empty() {
+            self.update_preview(ctx, location);
+        } else if !snapped {
+            ctx.suppress_redraw();
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_key_pressed(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &KeyboardEventArgs,
+    ) -> Transition {
+        match coordinate_entry::on_key_pressed(ctx.world_mut(), args) {
+            KeyOutcome::Ignored => {}
+            KeyOutcome::Committed(coordinate) => {
+                let previous = self.points.last().copied().unwrap_or_else(Point::origin);
+                let location = coordinate.resolve(previous);
+                ctx.typed_click_at(location);
+                self.place_point(ctx, location);
+                return Transition::DoNothing;
+            }
+            KeyOutcome::Edited | KeyOutcome::Cancelled => return Transition::DoNothing,
+        }
+
+        match args.key {
+            Some(VirtualKeyCode::F) => self.change_kind(ctx, EllipseKind::Full),
+            Some(VirtualKeyCode::E) => self.change_kind(ctx, EllipseKind::Arc),
+            _ => {}
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        coordinate_entry::close(ctx.world_mut());
+        self.points.clear();
+        self.outline.clear(ctx);
+    }
+
+    fn get_cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        vec![
+            MenuItem::new("Full Ellipse", Action::Key(VirtualKeyCode::F)),
+            MenuItem::new("Elliptical Arc", Action::Key(VirtualKeyCode::E)),
+            MenuItem::new("Cancel", Action::Button(ButtonType::Select)),
+        ]
+    }
+}
+
+/// The ellipse defined by the points clicked so far and the cursor, or `None`
+/// if they don't define one (yet).
+///
+/// While picking the start of an arc the whole ellipse is shown, and after
+/// that the arc from the start to the cursor.
+fn preview_ellipse(points: &[Point], cursor: Point) -> Option<Ellipse> {
+    match *points {
+        [centre, major_end] => ellipse_through(centre, major_end, cursor),
+        [centre, major_end, minor_point] => ellipse_through(centre, major_end, minor_point),
+        [centre, major_end, minor_point, start] => {
+            let ellipse = ellipse_through(centre, major_end, minor_point)?;
+            let start = ellipse.parameter_of(start);
+            let end = ellipse.parameter_of(cursor);
+            if start == end {
+                // an arc with nothing in it
+                return None;
+            }
+            Some(ellipse.with_parameters(start, end))
+        }
+        _ => None,
+    }
+}
+
+/// The full ellipse centred on `centre` with one end of its major axis at
+/// `major_end`, where the minor axis is as long as `minor_point` is far from
+/// the major axis.
+fn ellipse_through(centre: Point, major_end: Point, minor_point: Point) -> Option<Ellipse> {
+    let major_axis = major_end - centre;
+    let major_radius = major_axis.length();
+    let minor_radius = major_axis.cross(minor_point - centre).abs() / major_radius;
+
+    if major_radius > 0.0 && minor_radius > 0.0 {
+        Some(Ellipse::new(
+            centre,
+            major_axis,
+            minor_radius / major_radius,
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::test_context::{single_shape, DummyContext};
+    use arcs::{euclid::Angle, Vector};
+
+    #[test]
+    fn the_third_point_sets_the_minor_axis() {
+        let got = preview_ellipse(
+            &[Point::new(1.0, 1.0), Point::new(5.0, 1.0)],
+            Point::new(3.0, 3.0),
+        )
+        .unwrap();
+
+        assert_eq!(got.centre, Point::new(1.0, 1.0));
+        assert_eq!(got.major_axis, Vector::new(4.0, 0.0));
+        assert_eq!(got.ratio, 0.5);
+        assert!(got.is_closed());
+    }
+
+    #[test]
+    fn an_arc_runs_from_its_start_to_the_cursor() {
+        let points = [
+            Point::zero(),
+            Point::new(4.0, 0.0),
+            Point::new(0.0, 2.0),
+            Point::new(0.0, 5.0),
+        ];
+
+        let got = preview_ellipse(&points, Point::new(-1.0, 0.0)).unwrap();
+
+        assert!((got.start_parameter - Angle::frac_pi_2()).radians.abs() < 1e-9);
+        assert!((got.end_parameter - Angle::pi()).radians.abs() < 1e-9);
+        assert!(!got.is_closed());
+    }
+
+    #[test]
+    fn the_finished_ellipse_is_a_single_shape() {
+        let mut drawing = DummyContext::default();
+        let mut mode = AddEllipseMode::default();
+
+        for &(x, y) in &[(1.0, 1.0), (5.0, 1.0), (3.0, 3.0)] {
+            mode.place_point(&mut drawing, Point::new(x, y));
+        }
+
+        let should_be = Ellipse::new(Point::new(1.0, 1.0), Vector::new(4.0, 0.0), 0.5);
+        assert_eq!(
+            single_shape(&mut drawing),
+            ShapeGeometry::Ellipse(should_be)
+        );
+        assert!(mode.points.is_empty());
+    }
+
+    #[test]
+    fn a_flat_ellipse_is_rejected() {
+        let got = preview_ellipse(&[Point::zero(), Point::new(4.0, 0.0)], Point::new(7.0, 0.0));
+
+        assert_eq!(got, None);
+    }
+}
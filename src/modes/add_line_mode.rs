@@ -1,26 +1,29 @@
 use crate::modes::{
-  ApplicationContext, Idle, KeyboardEventArgs, MouseEventArgs, State, Transition, VirtualKeyCode,
+  change_nested_state, click_nested_at, ApplicationContext, KeyboardEventArgs, MouseEventArgs, State,
+  Transition,
 };
+use crate::commands::Action;
+use crate::context_menu::MenuItem;
+use crate::coordinate_entry::{self, KeyOutcome};
+use crate::msg::ButtonType;
+use crate::tracking;
 
 use arcs::{
   components::{AddLine, AddPoint, CursorPosition, Delete},
   specs::prelude::*,
+  Point,
 };
 
 #[derive(Debug)]
 pub struct AddLineMode {
   nested: Box<dyn State>,
+  /// Where the last point was placed, used by relative coordinates.
+  previous: Option<Point>,
 }
 
 impl AddLineMode {
   fn handle_transition(&mut self, transition: Transition) {
-    match transition {
-      Transition::ChangeState(new_state) => {
-        log::debug!("Changing state {:?} -> {:?}", self.nested, new_state);
-        self.nested = new_state;
-      }
-      Transition::DoNothing => {}
-    }
+    change_nested_state(&mut self.nested, transition);
   }
 }
 impl State for AddLineMode {
@@ -29,14 +32,23 @@ impl State for AddLineMode {
     ctx: &mut dyn ApplicationContext,
     args: &MouseEventArgs,
   ) -> Transition {
+    if !args.is_left_button() {
+      return Transition::DoNothing;
+    }
+
     let trans = self.nested.on_mouse_down(ctx, args);
     self.handle_transition(trans);
     Transition::DoNothing
   }
 
   fn on_mouse_up(&mut self, ctx: &mut dyn ApplicationContext, args: &MouseEventArgs) -> Transition {
+    if !args.is_left_button() {
+      return Transition::DoNothing;
+    }
+
     let trans = self.nested.on_mouse_up(ctx, args);
     self.handle_transition(trans);
+    self.previous = Some(ctx.world().read_resource::<CursorPosition>().location);
     Transition::DoNothing
   }
 
@@ -45,10 +57,14 @@ impl State for AddLineMode {
     ctx: &mut dyn ApplicationContext,
     args: &KeyboardEventArgs,
   ) -> Transition {
-    if args.key == Some(VirtualKeyCode::Escape) {
-      // pressing escape should take us back to idle
-      self.nested.on_cancelled(ctx);
-      return Transition::ChangeState(Box::new(Idle::default()));
+    match coordinate_entry::on_key_pressed(ctx.world_mut(), args) {
+      KeyOutcome::Ignored => {}
+      KeyOutcome::Committed(coordinate) => {
+        let location = coordinate.resolve(self.previous.unwrap_or_else(Point::origin));
+        self.previous = Some(click_nested_at(ctx, &mut self.nested, location));
+        return Transition::DoNothing;
+      }
+      KeyOutcome::Edited | KeyOutcome::Cancelled => return Transition::DoNothing,
     }
 
     let trans = self.nested.on_key_pressed(ctx, args);
@@ -67,15 +83,23 @@ impl State for AddLineMode {
   }
 
   fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+    coordinate_entry::close(ctx.world_mut());
     self.nested.on_cancelled(ctx);
     self.nested = Box::new(WaitingToPlaceStart::default());
   }
+
+  fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+    // each line is finished as soon as it's drawn, so there's nothing to
+    // close or take back
+    vec![MenuItem::new("Cancel", Action::Button(ButtonType::Select))]
+  }
 }
 
 impl Default for AddLineMode {
   fn default() -> AddLineMode {
     AddLineMode {
       nested: Box::new(WaitingToPlaceStart::default()),
+      previous: None,
     }
   }
 }
@@ -117,7 +141,10 @@ impl State for WaitingToPlaceStart {
     ctx: &mut dyn ApplicationContext,
     args: &MouseEventArgs,
   ) -> Transition {
-    ctx.suppress_redraw();
+    // only redraw when the snap marker needs to move
+    if !ctx.snap_to_objects(args.location) {
+      ctx.suppress_redraw();
+    }
     let mut cursor_position = ctx.world_mut().write_resource::<CursorPosition>();
     cursor_position.location = args.location;
 
@@ -144,7 +171,10 @@ impl State for PlacingStart {
     let mut storage: WriteStorage<Delete> = world.write_storage();
     let _ = storage.insert(command_entity, Delete {});
 
-    Transition::ChangeState(Box::new(WaitingToPlaceEnd {}))
+    // the cursor has already been snapped so this is where the point went
+    let start = world.read_resource::<CursorPosition>().location;
+
+    Transition::ChangeState(Box::new(WaitingToPlaceEnd { start }))
   }
 
   fn on_mouse_move(
@@ -152,6 +182,7 @@ impl State for PlacingStart {
     ctx: &mut dyn ApplicationContext,
     args: &MouseEventArgs,
   ) -> Transition {
+    ctx.snap_to_objects(args.location);
     let mut cursor_position = ctx.world_mut().write_resource::<CursorPosition>();
     cursor_position.location = args.location;
 
@@ -171,7 +202,9 @@ impl State for PlacingStart {
 // The base sub-state for [`AddPointMode`]. We're waiting for the user to click
 /// so we can start adding a point to the canvas.
 #[derive(Debug)]
-struct WaitingToPlaceEnd;
+struct WaitingToPlaceEnd {
+  start: Point,
+}
 
 impl State for WaitingToPlaceEnd {
   fn on_mouse_down(
@@ -179,7 +212,7 @@ impl State for WaitingToPlaceEnd {
     _ctx: &mut dyn ApplicationContext,
     _args: &MouseEventArgs,
   ) -> Transition {
-    Transition::ChangeState(Box::new(PlacingEnd {}))
+    Transition::ChangeState(Box::new(PlacingEnd { start: self.start }))
   }
 
   fn on_mouse_move(
@@ -187,6 +220,8 @@ impl State for WaitingToPlaceEnd {
     ctx: &mut dyn ApplicationContext,
     args: &MouseEventArgs,
   ) -> Transition {
+    ctx.snap_to_objects(args.location);
+    ctx.track_from(self.start, args.location, args.shift_pressed);
     let mut cursor_position = ctx.world_mut().write_resource::<CursorPosition>();
     cursor_position.location = args.location;
 
@@ -195,6 +230,7 @@ impl State for WaitingToPlaceEnd {
 
   fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
     // make sure we clean up the temporary line.
+    tracking::clear(ctx.world_mut());
     let command_entity = ctx.command();
     let mut storage: WriteStorage<Delete> = ctx.world_mut().write_storage();
     let _ = storage.insert(command_entity, Delete {});
@@ -202,7 +238,9 @@ impl State for WaitingToPlaceEnd {
 }
 
 #[derive(Debug)]
-struct PlacingEnd;
+struct PlacingEnd {
+  start: Point,
+}
 
 impl State for PlacingEnd {
   fn on_mouse_up(
@@ -210,7 +248,9 @@ impl State for PlacingEnd {
     ctx: &mut dyn ApplicationContext,
     _args: &MouseEventArgs,
   ) -> Transition {
+    tracking::clear(ctx.world_mut());
     ctx.unselect_all();
+    ctx.checkpoint();
     Transition::ChangeState(Box::new(WaitingToPlaceStart::default()))
   }
 
@@ -219,6 +259,8 @@ impl State for PlacingEnd {
     ctx: &mut dyn ApplicationContext,
     args: &MouseEventArgs,
   ) -> Transition {
+    ctx.snap_to_objects(args.location);
+    ctx.track_from(self.start, args.location, args.shift_pressed);
     let mut cursor_position = ctx.world_mut().write_resource::<CursorPosition>();
     cursor_position.location = args.location;
 
@@ -227,6 +269,7 @@ impl State for PlacingEnd {
 
   fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
     // make sure we clean up the temporary line.
+    tracking::clear(ctx.world_mut());
     let command_entity = ctx.command();
     let mut storage: WriteStorage<Delete> = ctx.world_mut().write_storage();
     let _ = storage.insert(command_entity, Delete {});
@@ -1,27 +1,27 @@
 use crate::modes::{
-    AddArcMode, AddLineMode, ApplicationContext, Idle, KeyboardEventArgs, MouseEventArgs, State,
-    Transition, VirtualKeyCode,
+    change_nested_state, click_nested_at, AddArcMode, AddLineMode, ApplicationContext, Idle,
+    KeyboardEventArgs, MouseEventArgs, State, Transition,
 };
 
+use crate::commands::Action;
+use crate::context_menu::MenuItem;
+use crate::coordinate_entry::{self, KeyOutcome};
 use crate::msg::ButtonType;
 use arcs::components::{AddPoint, CursorPosition, Delete};
 use arcs::specs::prelude::*;
 use arcs::specs::WorldExt;
+use arcs::Point;
 
 #[derive(Debug)]
 pub struct AddPointMode {
     nested: Box<dyn State>,
+    /// Where the last point was placed, used by relative coordinates.
+    previous: Option<Point>,
 }
 
 impl AddPointMode {
     fn handle_transition(&mut self, transition: Transition) {
-        match transition {
-            Transition::ChangeState(new_state) => {
-                log::debug!("Changing state {:?} -> {:?}", self.nested, new_state);
-                self.nested = new_state;
-            }
-            Transition::DoNothing => {}
-        }
+        change_nested_state(&mut self.nested, transition);
     }
 }
 
@@ -31,6 +31,10 @@ impl State for AddPointMode {
         ctx: &mut dyn ApplicationContext,
         args: &MouseEventArgs,
     ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
         let trans = self.nested.on_mouse_down(ctx, args);
         self.handle_transition(trans);
         Transition::DoNothing
@@ -41,8 +45,13 @@ impl State for AddPointMode {
         ctx: &mut dyn ApplicationContext,
         args: &MouseEventArgs,
     ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
         let trans = self.nested.on_mouse_up(ctx, args);
         self.handle_transition(trans);
+        self.previous = Some(ctx.world().read_resource::<CursorPosition>().location);
         Transition::DoNothing
     }
 
@@ -51,10 +60,14 @@ impl State for AddPointMode {
         ctx: &mut dyn ApplicationContext,
         args: &KeyboardEventArgs,
     ) -> Transition {
-        if args.key == Some(VirtualKeyCode::Escape) {
-            // pressing escape should take us back to idle
-            self.nested.on_cancelled(ctx);
-            return Transition::ChangeState(Box::new(Idle::default()));
+        match coordinate_entry::on_key_pressed(ctx.world_mut(), args) {
+            KeyOutcome::Ignored => {}
+            KeyOutcome::Committed(coordinate) => {
+                let location = coordinate.resolve(self.previous.unwrap_or_else(Point::origin));
+                self.previous = Some(click_nested_at(ctx, &mut self.nested, location));
+                return Transition::DoNothing;
+            }
+            KeyOutcome::Edited | KeyOutcome::Cancelled => return Transition::DoNothing,
         }
 
         let trans = self.nested.on_key_pressed(ctx, args);
@@ -73,6 +86,7 @@ impl State for AddPointMode {
     }
 
     fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        coordinate_entry::close(ctx.world_mut());
         self.nested.on_cancelled(ctx);
         self.nested = Box::new(WaitingToPlace::default());
     }
@@ -87,15 +101,24 @@ impl State for AddPointMode {
             ButtonType::Point => Transition::ChangeState(Box::new(AddPointMode::default())),
             ButtonType::Line => Transition::ChangeState(Box::new(AddLineMode::default())),
             ButtonType::Select => Transition::ChangeState(Box::new(Idle::default())),
-            ButtonType::Snap => Transition::DoNothing,
+            // the rest of the buttons don't change the mode
+            _ => Transition::DoNothing,
         }
     }
+
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        vec![
+            MenuItem::new("Undo", Action::Button(ButtonType::Undo)),
+            MenuItem::new("Cancel", Action::Button(ButtonType::Select)),
+        ]
+    }
 }
 
 impl Default for AddPointMode {
     fn default() -> AddPointMode {
         AddPointMode {
             nested: Box::new(WaitingToPlace::default()),
+            previous: None,
         }
     }
 }
@@ -128,9 +151,12 @@ impl State for WaitingToPlace {
     fn on_mouse_move(
         &mut self,
         ctx: &mut dyn ApplicationContext,
-        _event_args: &MouseEventArgs,
+        args: &MouseEventArgs,
     ) -> Transition {
-        ctx.suppress_redraw();
+        // only redraw when the snap marker needs to move
+        if !ctx.snap_to_objects(args.location) {
+            ctx.suppress_redraw();
+        }
         Transition::DoNothing
     }
 }
@@ -145,6 +171,7 @@ impl State for PlacingPoint {
         _args: &MouseEventArgs,
     ) -> Transition {
         ctx.unselect_all();
+        ctx.checkpoint();
         Transition::ChangeState(Box::new(WaitingToPlace::default()))
     }
 
@@ -153,6 +180,7 @@ impl State for PlacingPoint {
         ctx: &mut dyn ApplicationContext,
         args: &MouseEventArgs,
     ) -> Transition {
+        ctx.snap_to_objects(args.location);
         let mut cursor_position = ctx.world_mut().write_resource::<CursorPosition>();
         cursor_position.location = args.location;
 
@@ -0,0 +1,262 @@
+use crate::{
+    commands::Action,
+    context_menu::MenuItem,
+    coordinate_entry::{self, KeyOutcome},
+    modes::{
+        outline::Outline, ApplicationContext, KeyboardEventArgs, MouseEventArgs, State, Transition,
+        VirtualKeyCode,
+    },
+    msg::ButtonType,
+};
+use arcs::{components::CursorPosition, euclid::Angle, Point, Vector};
+use std::f64::consts::PI;
+
+/// How many sides a polygon has until the user types something else.
+pub const DEFAULT_SIDES: usize = 6;
+const MIN_SIDES: usize = 3;
+/// Anything with more sides than this is a circle for all intents and
+/// purposes, and would create far too many lines.
+const MAX_SIDES: usize = 1024;
+
+/// How the cursor relates to the polygon being drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PolygonKind {
+    /// The polygon fits inside a circle through the cursor, so the cursor
+    /// is on a vertex.
+    Inscribed,
+    /// The polygon fits around a circle through the cursor, so the cursor is
+    /// on the middle of a side.
+    Circumscribed,
+}
+
+impl Default for PolygonKind {
+    fn default() -> PolygonKind {
+        PolygonKind::Inscribed
+    }
+}
+
+/// Draw regular polygons by clicking the centre and then a vertex (or the
+/// middle of a side).
+///
+/// Type a number and press enter to change the number of sides.
+#[derive(Debug)]
+pub struct AddPolygonMode {
+    sides: usize,
+    kind: PolygonKind,
+    centre: Option<Point>,
+    outline: Outline,
+}
+
+impl AddPolygonMode {
+    pub fn new(kind: PolygonKind) -> AddPolygonMode {
+        AddPolygonMode {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    fn place_point(&mut self, ctx: &mut dyn ApplicationContext, location: Point) {
+        let centre = match self.centre {
+            Some(centre) => centre,
+            None => {
+                ctx.unselect_all();
+                self.centre = Some(location);
+                return;
+            }
+        };
+
+        if location == centre {
+            // a zero-sized polygon isn't much use to anyone
+            return;
+        }
+
+        let vertices = polygon_vertices(centre, location, self.sides, self.kind);
+        self.outline.keep(ctx, &vertices);
+        self.centre = None;
+        ctx.checkpoint();
+    }
+
+    fn update_preview(&mut self, ctx: &mut dyn ApplicationContext, cursor: Point) {
+        if let Some(centre) = self.centre {
+            let vertices = polygon_vertices(centre, cursor, self.sides, self.kind);
+            self.outline.update(ctx, &vertices);
+        }
+    }
+}
+
+impl State for AddPolygonMode {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
+        let location = ctx.set_cursor_position(args.location);
+        self.place_point(ctx, location);
+
+        Transition::DoNothing
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let snapped = ctx.snap_to_objects(args.location);
+        let location = ctx.set_cursor_position(args.location);
+
+        if self.centre.is_some() {
+            self.update_preview(ctx, location);
+        } else if !snapped {
+            ctx.suppress_redraw();
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_key_pressed(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &KeyboardEventArgs,
+    ) -> Transition {
+        // a whole number on its own is the number of sides, not a coordinate
+        if args.key == Some(VirtualKeyCode::Enter) {
+            let text = coordinate_entry::current_text(ctx.world());
+            if let Some(sides) = text.and_then(|text| text.trim().parse::<usize>().ok()) {
+                coordinate_entry::close(ctx.world_mut());
+                self.sides = sides.max(MIN_SIDES).min(MAX_SIDES);
+                let cursor = ctx.world().read_resource::<CursorPosition>().location;
+                self.update_preview(ctx, cursor);
+                return Transition::DoNothing;
+            }
+        }
+
+        match coordinate_entry::on_key_pressed(ctx.world_mut(), args) {
+            KeyOutcome::Ignored => {}
+            KeyOutcome::Committed(coordinate) => {
+                let previous = self.centre.unwrap_or_else(Point::origin);
+                let location = coordinate.resolve(previous);
+                ctx.typed_click_at(location);
+                self.place_point(ctx, location);
+                return Transition::DoNothing;
+            }
+            KeyOutcome::Edited | KeyOutcome::Cancelled => return Transition::DoNothing,
+        }
+
+        let kind = match args.key {
+            Some(VirtualKeyCode::I) => PolygonKind::Inscribed,
+            Some(VirtualKeyCode::C) => PolygonKind::Circumscribed,
+            _ => return Transition::DoNothing,
+        };
+        self.kind = kind;
+        let cursor = ctx.world().read_resource::<CursorPosition>().location;
+        self.update_preview(ctx, cursor);
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        coordinate_entry::close(ctx.world_mut());
+        self.centre = None;
+        self.outline.clear(ctx);
+    }
+
+    fn get_cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        vec![
+            MenuItem::new("Inscribed", Action::Key(VirtualKeyCode::I)),
+            MenuItem::new("Circumscribed", Action::Key(VirtualKeyCode::C)),
+            MenuItem::new("Cancel", Action::Button(ButtonType::Select)),
+        ]
+    }
+}
+
+impl Default for AddPolygonMode {
+    fn default() -> AddPolygonMode {
+        AddPolygonMode {
+            sides: DEFAULT_SIDES,
+            kind: PolygonKind::default(),
+            centre: None,
+            outline: Outline::default(),
+        }
+    }
+}
+
+/// The vertices of a regular polygon centred on `centre`, going
+/// anticlockwise.
+fn polygon_vertices(centre: Point, cursor: Point, sides: usize, kind: PolygonKind) -> Vec<Point> {
+    let to_cursor = cursor - centre;
+    let step = 2.0 * PI / sides as f64;
+
+    let (radius, first_angle) = match kind {
+        PolygonKind::Inscribed => (to_cursor.length(), to_cursor.angle_from_x_axis().radians),
+        PolygonKind::Circumscribed => (
+            // the cursor is on the middle of a side, half a step away from
+            // the nearest vertex
+            to_cursor.length() / (step / 2.0).cos(),
+            to_cursor.angle_from_x_axis().radians - step / 2.0,
+        ),
+    };
+
+    (0..sides)
+        .map(|i| {
+            let (sin, cos) = Angle::radians(first_angle + step * i as f64).sin_cos();
+            centre + Vector::new(cos, sin) * radius
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::test_context::{assert_single_closed_shape, DummyContext};
+
+    fn assert_close(left: Point, right: Point) {
+        assert!((left - right).length() < 1e-9, "{:?} != {:?}", left, right);
+    }
+
+    #[test]
+    fn an_inscribed_square_has_a_vertex_on_the_cursor() {
+        let got = polygon_vertices(
+            Point::zero(),
+            Point::new(2.0, 0.0),
+            4,
+            PolygonKind::Inscribed,
+        );
+
+        assert_eq!(got.len(), 4);
+        assert_close(got[0], Point::new(2.0, 0.0));
+        assert_close(got[1], Point::new(0.0, 2.0));
+    }
+
+    #[test]
+    fn a_circumscribed_square_has_a_side_through_the_cursor() {
+        let got = polygon_vertices(
+            Point::zero(),
+            Point::new(2.0, 0.0),
+            4,
+            PolygonKind::Circumscribed,
+        );
+
+        assert_close(got[0], Point::new(2.0, -2.0));
+        assert_close(got[1], Point::new(2.0, 2.0));
+    }
+
+    #[test]
+    fn the_finished_polygon_is_a_single_shape() {
+        let mut drawing = DummyContext::default();
+        let mut mode = AddPolygonMode::default();
+
+        mode.place_point(&mut drawing, Point::zero());
+        mode.place_point(&mut drawing, Point::new(2.0, 0.0));
+
+        assert_single_closed_shape(&mut drawing, DEFAULT_SIDES);
+        assert_eq!(mode.centre, None);
+    }
+}
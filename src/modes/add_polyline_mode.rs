@@ -0,0 +1,381 @@
+//! Drawing a chain of connected line segments.
+//!
+//! The whole chain is a single [`Shape`] holding a [`Polyline`], so it gets
+//! picked, moved and undone as one object. While it is being drawn the cursor
+//! is the polyline's last vertex and the shape is marked as a [`Preview`].
+
+use crate::{
+    commands::Action,
+    components::Preview,
+    context_menu::MenuItem,
+    coordinate_entry::{self, KeyOutcome},
+    modes::{
+        ApplicationContext, KeyboardEventArgs, MouseEventArgs, State, Transition, VirtualKeyCode,
+    },
+    msg::ButtonType,
+    polyline::Polyline,
+    shapes::{Shape, ShapeGeometry},
+    tracking,
+};
+use arcs::{components::CursorPosition, specs::prelude::*, Point};
+
+/// Clicking within this many pixels of the last vertex finishes the
+/// polyline, which is what happens when the user double-clicks.
+const FINISH_TOLERANCE: f64 = 3.0;
+
+#[derive(Debug, Default)]
+pub struct AddPolylineMode {
+    /// The vertices placed so far.
+    vertices: Vec<Point>,
+    /// The polyline being drawn.
+    shape: Option<Entity>,
+}
+
+impl AddPolylineMode {
+    fn place_vertex(&mut self, ctx: &mut dyn ApplicationContext, location: Point) {
+        match self.vertices.last() {
+            Some(&last) => {
+                let pixels = (location - last).length() * ctx.pixels_per_drawing_unit();
+                if pixels <= FINISH_TOLERANCE {
+                    self.finish(ctx);
+                    return;
+                }
+            }
+            None => {
+                // make sure nothing else is selected
+                ctx.unselect_all();
+                let layer = ctx.default_layer();
+                let shape = ctx
+                    .world_mut()
+                    .create_entity()
+                    .with(Shape {
+                        geometry: ShapeGeometry::Polyline(Polyline::new(Vec::new(), false)),
+                        layer,
+                    })
+                    .with(Preview)
+                    .build();
+                self.shape = Some(shape);
+            }
+        }
+
+        self.vertices.push(location);
+        self.update_preview(ctx, location);
+    }
+
+    /// Remove the last vertex (and the segment leading to it).
+    fn undo_last_vertex(&mut self, ctx: &mut dyn ApplicationContext) {
+        if self.vertices.pop().is_none() {
+            return;
+        }
+
+        if self.vertices.is_empty() {
+            if let Some(shape) = self.shape.take() {
+                delete_shape(ctx, shape);
+            }
+        } else {
+            let cursor = ctx.world().read_resource::<CursorPosition>().location;
+            self.update_preview(ctx, cursor);
+        }
+    }
+
+    /// Join the last vertex back to the first one and finish.
+    fn close(&mut self, ctx: &mut dyn ApplicationContext) {
+        // you need at least a triangle before closing makes sense
+        if self.vertices.len() < 3 {
+            return;
+        }
+
+        self.complete(ctx, true);
+    }
+
+    /// Keep the polyline drawn so far and get ready to start another one.
+    fn finish(&mut self, ctx: &mut dyn ApplicationContext) {
+        self.complete(ctx, false);
+    }
+
+    fn complete(&mut self, ctx: &mut dyn ApplicationContext, closed: bool) {
+        tracking::clear(ctx.world_mut());
+
+        let vertices = std::mem::take(&mut self.vertices);
+        let shape = match self.shape.take() {
+            Some(shape) => shape,
+            None => return,
+        };
+
+        // a single vertex isn't a polyline
+        if vertices.len() < 2 {
+            delete_shape(ctx, shape);
+            return;
+        }
+
+        let world = ctx.world_mut();
+        world.write_storage::<Preview>().remove(shape);
+        if let Some(s) = world.write_storage::<Shape>().get_mut(shape) {
+            s.geometry = ShapeGeometry::Polyline(Polyline::new(vertices, closed));
+        }
+
+        ctx.checkpoint();
+    }
+
+    /// Rubber-band the polyline's last segment to the cursor.
+    fn update_preview(&mut self, ctx: &mut dyn ApplicationContext, cursor: Point) {
+        let shape = match self.shape {
+            Some(shape) => shape,
+            None => return,
+        };
+
+        let mut vertices = self.vertices.clone();
+        vertices.push(cursor);
+
+        if let Some(s) = ctx.world().write_storage::<Shape>().get_mut(shape) {
+            s.geometry = ShapeGeometry::Polyline(Polyline::new(vertices, false));
+        }
+    }
+}
+
+impl State for AddPolylineMode {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
+        let location = ctx.set_cursor_position(args.location);
+        self.place_vertex(ctx, location);
+
+        Transition::DoNothing
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let snapped = ctx.snap_to_objects(args.location);
+
+        match self.vertices.last() {
+            Some(&last) => {
+                ctx.track_from(last, args.location, args.shift_pressed);
+                let location = ctx.set_cursor_position(args.location);
+                self.update_preview(ctx, location);
+            }
+            None => {
+                // only redraw when the snap marker needs to move
+                if !snapped {
+                    ctx.suppress_redraw();
+                }
+                ctx.set_cursor_position(args.location);
+            }
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_key_pressed(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &KeyboardEventArgs,
+    ) -> Transition {
+        match coordinate_entry::on_key_pressed(ctx.world_mut(), args) {
+            KeyOutcome::Ignored => {}
+            KeyOutcome::Committed(coordinate) => {
+                let previous = self.vertices.last().copied().unwrap_or_else(Point::origin);
+                let location = coordinate.resolve(previous);
+                ctx.typed_click_at(location);
+                self.place_vertex(ctx, location);
+                return Transition::DoNothing;
+            }
+            KeyOutcome::Edited | KeyOutcome::Cancelled => return Transition::DoNothing,
+        }
+
+        match args.key {
+            Some(VirtualKeyCode::Enter) => self.finish(ctx),
+            Some(VirtualKeyCode::C) => self.close(ctx),
+            Some(VirtualKeyCode::Backspace) => self.undo_last_vertex(ctx),
+            _ => {}
+        }
+
+        Transition::DoNothing
+    }
+
+    fn consumes_key(&self, args: &KeyboardEventArgs) -> bool {
+        // backspace removes the last vertex instead of deleting the selection
+        args.key == Some(VirtualKeyCode::Backspace) && !self.vertices.is_empty()
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        coordinate_entry::close(ctx.world_mut());
+        self.finish(ctx);
+    }
+
+    fn get_cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        vec![
+            MenuItem::new("Finish", Action::Key(VirtualKeyCode::Enter)),
+            MenuItem::new("Close", Action::Key(VirtualKeyCode::C)),
+            MenuItem::new("Undo Last Vertex", Action::Key(VirtualKeyCode::Backspace)),
+            MenuItem::new("Cancel", Action::Button(ButtonType::Select)),
+        ]
+    }
+}
+
+fn delete_shape(ctx: &mut dyn ApplicationContext, shape: Entity) {
+    if let Err(e) = ctx.world_mut().delete_entity(shape) {
+        log::warn!("Unable to delete a polyline: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{history, modes::test_context::DummyContext};
+
+    /// The polylines which have been drawn, ignoring any previews.
+    fn polylines(drawing: &DummyContext) -> Vec<Polyline> {
+        let shapes = drawing.world.read_storage::<Shape>();
+        let previews = drawing.world.read_storage::<Preview>();
+
+        (&shapes, !&previews)
+            .join()
+            .filter_map(|(shape, _)| match &shape.geometry {
+                ShapeGeometry::Polyline(polyline) => Some(polyline.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn draw(drawing: &mut DummyContext, vertices: &[(f64, f64)]) -> AddPolylineMode {
+        let mut mode = AddPolylineMode::default();
+
+        for &(x, y) in vertices {
+            mode.place_vertex(drawing, Point::new(x, y));
+        }
+
+        mode
+    }
+
+    fn points(vertices: &[(f64, f64)]) -> Vec<Point> {
+        vertices.iter().map(|&(x, y)| Point::new(x, y)).collect()
+    }
+
+    #[test]
+    fn the_cursor_is_the_last_vertex_while_drawing() {
+        let mut drawing = DummyContext::default();
+        let mut mode = draw(&mut drawing, &[(0.0, 0.0), (10.0, 0.0)]);
+
+        mode.update_preview(&mut drawing, Point::new(10.0, 5.0));
+
+        let shapes = drawing.world.read_storage::<Shape>();
+        let preview = shapes.get(mode.shape.unwrap()).unwrap();
+        assert_eq!(
+            preview.geometry,
+            ShapeGeometry::Polyline(Polyline::new(
+                points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 5.0)]),
+                false
+            ))
+        );
+        assert!(polylines(&drawing).is_empty());
+    }
+
+    #[test]
+    fn closing_joins_the_last_vertex_to_the_first() {
+        let mut drawing = DummyContext::default();
+        let vertices = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let mut mode = draw(&mut drawing, &vertices);
+
+        mode.close(&mut drawing);
+
+        assert_eq!(
+            polylines(&drawing),
+            vec![Polyline::new(points(&vertices), true)]
+        );
+        assert!(mode.vertices.is_empty());
+        assert!(mode.shape.is_none());
+    }
+
+    #[test]
+    fn lines_cant_be_closed() {
+        let mut drawing = DummyContext::default();
+        let mut mode = draw(&mut drawing, &[(0.0, 0.0), (10.0, 0.0)]);
+
+        mode.close(&mut drawing);
+
+        assert_eq!(mode.vertices.len(), 2);
+        assert!(polylines(&drawing).is_empty());
+    }
+
+    #[test]
+    fn undoing_a_vertex_removes_the_segment_leading_to_it() {
+        let mut drawing = DummyContext::default();
+        let mut mode = draw(&mut drawing, &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+
+        mode.undo_last_vertex(&mut drawing);
+        mode.finish(&mut drawing);
+
+        assert_eq!(
+            polylines(&drawing),
+            vec![Polyline::new(points(&[(0.0, 0.0), (10.0, 0.0)]), false)]
+        );
+    }
+
+    #[test]
+    fn undoing_the_first_vertex_throws_the_polyline_away() {
+        let mut drawing = DummyContext::default();
+        let mut mode = draw(&mut drawing, &[(0.0, 0.0)]);
+
+        mode.undo_last_vertex(&mut drawing);
+        drawing.world.maintain();
+
+        assert!(mode.shape.is_none());
+        assert_eq!(drawing.world.read_storage::<Shape>().join().count(), 0);
+    }
+
+    #[test]
+    fn clicking_the_last_vertex_again_finishes() {
+        let mut drawing = DummyContext::default();
+
+        let mode = draw(&mut drawing, &[(0.0, 0.0), (10.0, 0.0), (10.0, 1.0)]);
+
+        assert!(mode.vertices.is_empty());
+        assert!(mode.shape.is_none());
+        assert_eq!(
+            polylines(&drawing),
+            vec![Polyline::new(points(&[(0.0, 0.0), (10.0, 0.0)]), false)]
+        );
+    }
+
+    #[test]
+    fn finishing_records_an_undo_step() {
+        let mut drawing = DummyContext::default();
+        history::history_mut(&mut drawing.world);
+        let mut mode = draw(&mut drawing, &[(0.0, 0.0), (10.0, 0.0)]);
+
+        let mut history = drawing.world.write_resource::<history::History>();
+        assert!(!history.record_pending(&drawing.world));
+        drop(history);
+
+        mode.finish(&mut drawing);
+
+        let mut history = drawing.world.write_resource::<history::History>();
+        assert!(history.record_pending(&drawing.world));
+    }
+
+    #[test]
+    fn backspace_is_only_claimed_while_drawing() {
+        let mut drawing = DummyContext::default();
+        let backspace = KeyboardEventArgs::pressing(VirtualKeyCode::Backspace);
+
+        let mut mode = draw(&mut drawing, &[]);
+        assert!(!mode.consumes_key(&backspace));
+
+        mode.place_vertex(&mut drawing, Point::new(0.0, 0.0));
+        assert!(mode.consumes_key(&backspace));
+        assert!(!mode.consumes_key(&KeyboardEventArgs::pressing(VirtualKeyCode::Delete)));
+    }
+}
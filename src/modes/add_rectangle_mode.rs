@@ -0,0 +1,251 @@
+use crate::{
+    commands::Action,
+    context_menu::MenuItem,
+    coordinate_entry::{self, KeyOutcome},
+    modes::{
+        outline::Outline, ApplicationContext, KeyboardEventArgs, MouseEventArgs, State, Transition,
+        VirtualKeyCode,
+    },
+    msg::ButtonType,
+};
+use arcs::{Point, Vector};
+
+/// The different ways a rectangle can be constructed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RectangleConstruction {
+    /// Click two opposite corners.
+    Corners,
+    /// Click the centre, then a corner.
+    Centre,
+}
+
+impl Default for RectangleConstruction {
+    fn default() -> RectangleConstruction {
+        RectangleConstruction::Corners
+    }
+}
+
+/// Draw axis-aligned rectangles as closed polylines.
+#[derive(Debug, Default)]
+pub struct AddRectangleMode {
+    construction: RectangleConstruction,
+    /// The first corner (or the centre), once it has been placed.
+    first: Option<Point>,
+    outline: Outline,
+}
+
+impl AddRectangleMode {
+    pub fn new(construction: RectangleConstruction) -> AddRectangleMode {
+        AddRectangleMode {
+            construction,
+            ..Default::default()
+        }
+    }
+
+    fn change_construction(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        construction: RectangleConstruction,
+    ) {
+        if construction != self.construction {
+            self.on_cancelled(ctx);
+            self.construction = construction;
+        }
+    }
+
+    fn place_point(&mut self, ctx: &mut dyn ApplicationContext, location: Point) {
+        let first = match self.first {
+            Some(first) => first,
+            None => {
+                ctx.unselect_all();
+                self.first = Some(location);
+                return;
+            }
+        };
+
+        match rectangle_corners(self.construction, first, location) {
+            Some(corners) => {
+                self.outline.keep(ctx, &corners);
+                self.first = None;
+                ctx.checkpoint();
+            }
+            None => {
+                // a rectangle with no width or height is just a line
+                log::debug!("Ignoring a degenerate rectangle corner at {:?}", location);
+            }
+        }
+    }
+}
+
+impl State for AddRectangleMode {
+    fn on_mouse_down(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
+        let location = ctx.set_cursor_position(args.location);
+        self.place_point(ctx, location);
+
+        Transition::DoNothing
+    }
+
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let snapped = ctx.snap_to_objects(args.location);
+        let location = ctx.set_cursor_position(args.location);
+
+        match self.first {
+            Some(first) => {
+                let corners = rectangle_corners(self.construction, first, location);
+                self.outline.update(ctx, &corners.unwrap_or_default());
+            }
+            None if !snapped => ctx.suppress_redraw(),
+            None => {}
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_key_pressed(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &KeyboardEventArgs,
+    ) -> Transition {
+        match coordinate_entry::on_key_pressed(ctx.world_mut(), args) {
+            KeyOutcome::Ignored => {}
+            KeyOutcome::Committed(coordinate) => {
+                let previous = self.first.unwrap_or_else(Point::origin);
+                let location = coordinate.resolve(previous);
+                ctx.typed_click_at(location);
+                self.place_point(ctx, location);
+                return Transition::DoNothing;
+            }
+            KeyOutcome::Edited | KeyOutcome::Cancelled => return Transition::DoNothing,
+        }
+
+        match args.key {
+            Some(VirtualKeyCode::C) => self.change_construction(ctx, RectangleConstruction::Centre),
+            Some(VirtualKeyCode::D) => {
+                self.change_construction(ctx, RectangleConstruction::Corners)
+            }
+            _ => {}
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        coordinate_entry::close(ctx.world_mut());
+        self.first = None;
+        self.outline.clear(ctx);
+    }
+
+    fn get_cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        vec![
+            MenuItem::new("Corner to Corner", Action::Key(VirtualKeyCode::D)),
+            MenuItem::new("From Centre", Action::Key(VirtualKeyCode::C)),
+            MenuItem::new("Cancel", Action::Button(ButtonType::Select)),
+        ]
+    }
+}
+
+/// The rectangle's corners, going anticlockwise, or `None` if it would have
+/// no width or height.
+fn rectangle_corners(
+    construction: RectangleConstruction,
+    first: Point,
+    cursor: Point,
+) -> Option<Vec<Point>> {
+    let (min, max) = match construction {
+        RectangleConstruction::Corners => (first.min(cursor), first.max(cursor)),
+        RectangleConstruction::Centre => {
+            let half = (cursor - first).abs();
+            (first - half, first + half)
+        }
+    };
+
+    let size: Vector = max - min;
+    if size.x == 0.0 || size.y == 0.0 {
+        return None;
+    }
+
+    Some(vec![
+        min,
+        Point::new(max.x, min.y),
+        max,
+        Point::new(min.x, max.y),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::test_context::{assert_single_closed_shape, DummyContext};
+
+    #[test]
+    fn the_finished_rectangle_is_a_single_shape() {
+        let mut drawing = DummyContext::default();
+        let mut mode = AddRectangleMode::default();
+
+        mode.place_point(&mut drawing, Point::new(1.0, 3.0));
+        mode.place_point(&mut drawing, Point::new(4.0, 5.0));
+
+        assert_single_closed_shape(&mut drawing, 4);
+        assert_eq!(mode.first, None);
+    }
+
+    #[test]
+    fn corners_can_be_clicked_in_any_order() {
+        let got = rectangle_corners(
+            RectangleConstruction::Corners,
+            Point::new(4.0, 3.0),
+            Point::new(1.0, 5.0),
+        )
+        .unwrap();
+
+        assert_eq!(
+            got,
+            vec![
+                Point::new(1.0, 3.0),
+                Point::new(4.0, 3.0),
+                Point::new(4.0, 5.0),
+                Point::new(1.0, 5.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_centred_rectangle_is_symmetric() {
+        let got = rectangle_corners(
+            RectangleConstruction::Centre,
+            Point::new(1.0, 1.0),
+            Point::new(0.0, 3.0),
+        )
+        .unwrap();
+
+        assert_eq!(got[0], Point::new(0.0, -1.0));
+        assert_eq!(got[2], Point::new(2.0, 3.0));
+    }
+
+    #[test]
+    fn flat_rectangles_are_rejected() {
+        let got = rectangle_corners(
+            RectangleConstruction::Corners,
+            Point::new(1.0, 1.0),
+            Point::new(5.0, 1.0),
+        );
+
+        assert_eq!(got, None);
+    }
+}
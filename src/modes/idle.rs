@@ -1,8 +1,35 @@
-use crate::modes::{
-    AddArcMode, AddLineMode, AddPointMode, ApplicationContext, KeyboardEventArgs, MouseEventArgs,
-    State, Transition, VirtualKeyCode,
+use crate::{
+    clipboard::Clipboard,
+    commands::Action,
+    context_menu::MenuItem,
+    geometry::BoundingBox,
+    modes::{
+        outline::Outline, ApplicationContext, KeyboardEventArgs, MouseEventArgs, State, Transition,
+        VirtualKeyCode, GRID_SPACING,
+    },
+    msg::ButtonType,
+    settings::{self, EmptySpaceDrag},
+    shapes::Shape,
+    spatial_index,
 };
-use arcs::Point;
+use arcs::{
+    components::{Dimension, DrawingObject, LineStyle, Selected},
+    euclid::Point2D,
+    piet::Color,
+    primitives::Line,
+    specs::prelude::*,
+    CanvasSpace, Point, Vector,
+};
+
+/// Releasing the mouse within this many pixels of where it was pressed
+/// counts as a click rather than a drag.
+const CLICK_TOLERANCE: f64 = 3.0;
+const DASH_PIXELS: f64 = 6.0;
+const GAP_PIXELS: f64 = 4.0;
+/// Dashes get longer when zoomed out so we don't create thousands of them.
+const MAX_DASHES_PER_SIDE: f64 = 50.0;
+/// How many grid steps the arrow keys move things by when shift is held.
+const BIG_NUDGE_STEPS: f64 = 10.0;
 
 #[derive(Debug)]
 pub struct Idle {
@@ -10,17 +37,9 @@ pub struct Idle {
 }
 
 impl State for Idle {
-    fn on_key_pressed(
-        &mut self,
-        _ctx: &mut dyn ApplicationContext,
-        event_args: &KeyboardEventArgs,
-    ) -> Transition {
-        match event_args.key {
-            Some(VirtualKeyCode::A) => Transition::ChangeState(Box::new(AddArcMode::default())),
-            Some(VirtualKeyCode::P) => Transition::ChangeState(Box::new(AddPointMode::default())),
-            Some(VirtualKeyCode::L) => Transition::ChangeState(Box::new(AddLineMode::default())),
-            _ => Transition::DoNothing,
-        }
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        self.nested.on_cancelled(ctx);
+        self.nested = Box::new(WaitingToSelect);
     }
 
     fn on_mouse_down(
@@ -28,6 +47,10 @@ impl State for Idle {
         ctx: &mut dyn ApplicationContext,
         args: &MouseEventArgs,
     ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
         if let Transition::ChangeState(new_state) = self.nested.on_mouse_down(ctx, args) {
             self.nested = new_state;
         }
@@ -40,6 +63,10 @@ impl State for Idle {
         ctx: &mut dyn ApplicationContext,
         args: &MouseEventArgs,
     ) -> Transition {
+        if !args.is_left_button() {
+            return Transition::DoNothing;
+        }
+
         if let Transition::ChangeState(new_state) = self.nested.on_mouse_up(ctx, args) {
             self.nested = new_state;
         }
@@ -55,9 +82,104 @@ impl State for Idle {
         self.nested.on_mouse_move(ctx, event_args)
     }
 
+    fn on_context_menu(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        // right-clicking on something when nothing is selected selects it,
+        // so the context menu has something to work with
+        if !anything_selected(ctx.world()) {
+            if let Some(entity) = ctx.entities_under_point(args.location).next() {
+                ctx.select(entity);
+            }
+        }
+
+        Transition::DoNothing
+    }
+
+    fn on_key_pressed(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &KeyboardEventArgs,
+    ) -> Transition {
+        let direction = match args.key.and_then(nudge_direction) {
+            Some(direction) => direction,
+            None => return Transition::DoNothing,
+        };
+
+        let step = if args.control_pressed {
+            // one pixel, regardless of the zoom level
+            1.0 / ctx.pixels_per_drawing_unit()
+        } else if args.shift_pressed {
+            GRID_SPACING * BIG_NUDGE_STEPS
+        } else {
+            GRID_SPACING
+        };
+        let displacement = direction * step;
+
+        if anything_selected(ctx.world()) {
+            ctx.translate_selection(displacement);
+            ctx.checkpoint();
+        } else {
+            ctx.pan_viewport(displacement);
+        }
+
+        Transition::DoNothing
+    }
+
     fn get_cursor(&self) -> &str {
         self.nested.get_cursor()
     }
+
+    fn context_menu(&self, ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        if anything_selected(ctx.world()) {
+            vec![
+                MenuItem::new("Delete", Action::Button(ButtonType::Erase)),
+                MenuItem::new("Copy", Action::Button(ButtonType::Copy)),
+                MenuItem::new("Properties", Action::Button(ButtonType::Properties)),
+                MenuItem::new(
+                    "Zoom to Selection",
+                    Action::Button(ButtonType::ZoomSelection),
+                ),
+            ]
+        } else {
+            let mut items = Vec::new();
+            let can_paste = ctx
+                .world()
+                .try_fetch::<Clipboard>()
+                .map_or(false, |clipboard| !clipboard.is_empty());
+            if can_paste {
+                items.push(MenuItem::new("Paste", Action::Button(ButtonType::Paste)));
+            }
+            items.push(MenuItem::new(
+                "Zoom Extents",
+                Action::Button(ButtonType::ZoomExtents),
+            ));
+            items.push(MenuItem::new("Undo", Action::Button(ButtonType::Undo)));
+            items.push(MenuItem::new("Redo", Action::Button(ButtonType::Redo)));
+            items
+        }
+    }
+}
+
+/// Which way an arrow key moves things, in drawing space.
+fn nudge_direction(key: VirtualKeyCode) -> Option<Vector> {
+    match key {
+        VirtualKeyCode::Left => Some(Vector::new(-1.0, 0.0)),
+        VirtualKeyCode::Right => Some(Vector::new(1.0, 0.0)),
+        VirtualKeyCode::Up => Some(Vector::new(0.0, 1.0)),
+        VirtualKeyCode::Down => Some(Vector::new(0.0, -1.0)),
+        _ => None,
+    }
+}
+
+fn anything_selected(world: &World) -> bool {
+    let selected = world.read_storage::<Selected>();
+    let drawing_objects = world.read_storage::<DrawingObject>();
+    let shapes = world.read_storage::<Shape>();
+    (&selected, &drawing_objects).join().next().is_some()
+        || (&selected, &shapes).join().next().is_some()
 }
 
 impl Default for Idle {
@@ -68,6 +190,30 @@ impl Default for Idle {
     }
 }
 
+/// How clicking on something changes the selection, depending on which
+/// modifier keys are held down.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SelectionMode {
+    /// Select only the things that were clicked on.
+    Replace,
+    /// Add to the selection (Shift).
+    Add,
+    /// Flip whether each thing is selected (Ctrl).
+    Toggle,
+}
+
+impl SelectionMode {
+    fn from_args(args: &MouseEventArgs) -> SelectionMode {
+        if args.control_pressed {
+            SelectionMode::Toggle
+        } else if args.shift_pressed {
+            SelectionMode::Add
+        } else {
+            SelectionMode::Replace
+        }
+    }
+}
+
 /// [`Idle`]'s base sub-state.
 ///
 /// We are waiting for the user to click so we can change the selection or start
@@ -85,13 +231,31 @@ impl State for WaitingToSelect {
 
         match first_item_under_cursor {
             Some(entity) => {
+                let mode = SelectionMode::from_args(args);
+
+                if mode == SelectionMode::Toggle && ctx.is_selected(entity) {
+                    ctx.unselect(entity);
+                    return Transition::DoNothing;
+                }
+
+                // clicking on something which is already selected keeps the
+                // rest of the selection so it can all be dragged together
+                if mode == SelectionMode::Replace && !ctx.is_selected(entity) {
+                    ctx.unselect_all();
+                }
+
                 ctx.select(entity);
                 Transition::ChangeState(Box::new(DraggingSelection::from_args(args)))
             }
-            _ => {
-                ctx.select(ctx.viewport());
-                Transition::ChangeState(Box::new(PanningViewport::from_args(args)))
-            }
+            _ => match settings::current(ctx.world()).empty_space_drag {
+                EmptySpaceDrag::SelectRegion => {
+                    Transition::ChangeState(Box::new(SelectingRegion::from_args(args)))
+                }
+                EmptySpaceDrag::Pan => {
+                    ctx.select(ctx.viewport());
+                    Transition::ChangeState(Box::new(PanningViewport::from_args(args)))
+                }
+            },
         }
     }
 
@@ -134,9 +298,11 @@ impl State for DraggingSelection {
 
     fn on_mouse_up(
         &mut self,
-        _ctx: &mut dyn ApplicationContext,
+        ctx: &mut dyn ApplicationContext,
         _args: &MouseEventArgs,
     ) -> Transition {
+        // the whole drag is undone in one go
+        ctx.checkpoint();
         Transition::ChangeState(Box::new(WaitingToSelect::default()))
     }
 }
@@ -179,139 +345,503 @@ impl State for PanningViewport {
     }
 }
 
+/// The left mouse button was pressed on an empty part of the drawing and the
+/// user is dragging out a rectangle to select things with.
+///
+/// Dragging to the right only selects objects which are completely inside
+/// the rectangle (a "window" selection), while dragging to the left selects
+/// anything the rectangle touches (a "crossing" selection).
+#[derive(Debug)]
+struct SelectingRegion {
+    start: Point,
+    start_cursor: Point2D<f64, CanvasSpace>,
+    mode: SelectionMode,
+    preview: Outline,
+}
+
+impl SelectingRegion {
+    fn from_args(args: &MouseEventArgs) -> Self {
+        SelectingRegion {
+            start: args.location,
+            start_cursor: args.cursor,
+            mode: SelectionMode::from_args(args),
+            preview: Outline::overlay(SelectionKind::Window.line_style()),
+        }
+    }
+}
+
+impl State for SelectingRegion {
+    fn on_mouse_move(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        let kind = SelectionKind::from_drag(self.start, args.location);
+        let pixels_per_drawing_unit = ctx.pixels_per_drawing_unit();
+        let dashes = dashed_rectangle(self.start, args.location, pixels_per_drawing_unit);
+
+        self.preview.set_overlay_style(kind.line_style());
+        self.preview.update_lines(ctx, dashes);
+
+        Transition::DoNothing
+    }
+
+    fn on_mouse_up(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        args: &MouseEventArgs,
+    ) -> Transition {
+        self.preview.clear(ctx);
+
+        if self.mode == SelectionMode::Replace {
+            ctx.unselect_all();
+        }
+
+        // clicking on empty space just clears the selection
+        if (args.cursor - self.start_cursor).length() > CLICK_TOLERANCE {
+            let region = BoundingBox::from_points(&[self.start, args.location]);
+            let fully_enclosed =
+                SelectionKind::from_drag(self.start, args.location) == SelectionKind::Window;
+
+            for entity in spatial_index::entities_in_region(ctx.world(), region, fully_enclosed) {
+                if self.mode == SelectionMode::Toggle && ctx.is_selected(entity) {
+                    ctx.unselect(entity);
+                } else {
+                    ctx.select(entity);
+                }
+            }
+        }
+
+        Transition::ChangeState(Box::new(WaitingToSelect::default()))
+    }
+
+    fn on_cancelled(&mut self, ctx: &mut dyn ApplicationContext) {
+        self.preview.clear(ctx);
+    }
+
+    fn get_cursor(&self) -> &str {
+        "crosshair"
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SelectionKind {
+    Window,
+    Crossing,
+}
+
+impl SelectionKind {
+    fn from_drag(start: Point, end: Point) -> SelectionKind {
+        if end.x >= start.x {
+            SelectionKind::Window
+        } else {
+            SelectionKind::Crossing
+        }
+    }
+
+    fn colour(self) -> Color {
+        match self {
+            SelectionKind::Window => Color::rgb8(0x1e, 0x90, 0xff),
+            SelectionKind::Crossing => Color::rgb8(0x22, 0xaa, 0x22),
+        }
+    }
+
+    fn line_style(self) -> LineStyle {
+        LineStyle {
+            stroke: self.colour(),
+            width: Dimension::Pixels(1.0),
+        }
+    }
+}
+
+/// The dashes making up a rectangle with a constant on-screen dash length.
+fn dashed_rectangle(
+    first_corner: Point,
+    second_corner: Point,
+    pixels_per_drawing_unit: f64,
+) -> Vec<Line> {
+    let dash = DASH_PIXELS / pixels_per_drawing_unit;
+    let gap = GAP_PIXELS / pixels_per_drawing_unit;
+
+    let region = BoundingBox::from_points(&[first_corner, second_corner]);
+    let corners = [
+        region.min,
+        Point::new(region.max.x, region.min.y),
+        region.max,
+        Point::new(region.min.x, region.max.y),
+    ];
+    let mut dashes = Vec::new();
+
+    for (i, &from) in corners.iter().enumerate() {
+        let to = corners[(i + 1) % corners.len()];
+        let length = (to - from).length();
+
+        if length == 0.0 {
+            continue;
+        }
+
+        let direction = (to - from) / length;
+        let period = (dash + gap).max(length / MAX_DASHES_PER_SIDE);
+        let dash_length = period * dash / (dash + gap);
+        let mut distance = 0.0;
+
+        while distance < length {
+            let end = (distance + dash_length).min(length);
+            dashes.push(Line::new(
+                from + direction * distance,
+                from + direction * end,
+            ));
+            distance += period;
+        }
+    }
+
+    dashes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use arcs::euclid::{Length, Scale};
-    use arcs::specs::{Builder, Entity, World, WorldExt};
+    use crate::{
+        geometry,
+        modes::{
+            test_context::{mouse_at, DummyContext},
+            MouseButtons,
+        },
+        settings::Settings,
+        spatial_index::SpatialIndex,
+    };
+    use arcs::specs::Entity;
     use arcs::{
-        components::{layer::LayerType, DrawingObject, Geometry, Layer, Name, Viewport},
-        primitives::Grid,
+        components::{Delete, DrawingObject, Geometry, Viewport},
         Point,
     };
 
-    struct DummyContext {
-        world: World,
-        viewport: Entity,
-        default_layer: Entity,
-        grid: Entity,
-        command: Entity,
-    }
-
-    impl Default for DummyContext {
-        fn default() -> Self {
-            let mut world = World::new();
-            arcs::components::register(&mut world);
-            let viewport = world
-                .create_entity()
-                .with(Viewport {
-                    centre: Point::zero(),
-                    pixels_per_drawing_unit: Scale::new(1.0),
-                })
-                .build();
-
-            let default_layer = Layer::create(
-                world.create_entity(),
-                Name::from("default"),
-                Layer::default(),
-            );
-            let system_layer = Layer::create(
-                world.create_entity(),
-                Name::new("system_layer"),
-                Layer {
-                    z_level: usize::MIN,
-                    visible: true,
-                    layer_type: LayerType::System,
-                },
-            );
-
-            let grid = Grid::new(Length::new(20.));
-            let grid = world
-                .create_entity()
-                .with(DrawingObject {
-                    geometry: Geometry::Grid(grid),
-                    layer: system_layer,
-                })
-                .build();
-
-            let command = world.create_entity().with(Name::new("command")).build();
-
-            DummyContext {
-                world,
-                viewport,
-                default_layer,
-                grid,
-                command,
+    fn add_line(drawing: &mut DummyContext, start: Point, end: Point) -> Entity {
+        let layer = drawing.default_layer;
+        drawing
+            .world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(start, end)),
+                layer,
+            })
+            .build()
+    }
+
+    /// Put every drawing object into a fresh [`SpatialIndex`].
+    fn index_everything(drawing: &mut DummyContext) {
+        let mut index = SpatialIndex::default();
+
+        {
+            let entities = drawing.world.entities();
+            let drawing_objects = drawing.world.read_storage::<DrawingObject>();
+
+            for (entity, drawing_object) in (&entities, &drawing_objects).join() {
+                if let Some(bounds) = geometry::bounding_box(&drawing_object.geometry) {
+                    index.insert(entity, bounds);
+                }
             }
         }
+
+        drawing.world.insert(index);
     }
 
-    impl ApplicationContext for DummyContext {
-        fn world(&self) -> &World {
-            &self.world
-        }
+    fn click(drawing: &mut DummyContext, args: MouseEventArgs) {
+        let mut idle = Idle::default();
 
-        fn world_mut(&mut self) -> &mut World {
-            &mut self.world
-        }
+        idle.on_mouse_down(drawing, &args);
+        idle.on_mouse_up(drawing, &args);
+    }
 
-        fn viewport(&self) -> Entity {
-            self.viewport
-        }
+    fn drag(drawing: &mut DummyContext, from: MouseEventArgs, to: MouseEventArgs) {
+        let mut idle = Idle::default();
 
-        fn default_layer(&self) -> Entity {
-            self.default_layer
-        }
+        idle.on_mouse_down(drawing, &from);
+        idle.on_mouse_move(drawing, &to);
+        idle.on_mouse_up(drawing, &to);
+    }
 
-        fn grid(&self) -> Entity {
-            self.grid
-        }
-        fn command(&self) -> Entity {
-            self.command
+    fn selected(drawing: &DummyContext) -> Vec<Entity> {
+        let entities = drawing.world.entities();
+        let selected = drawing.world.read_storage::<Selected>();
+
+        (&entities, &selected).join().map(|(e, _)| e).collect()
+    }
+
+    #[test]
+    fn dragging_right_selects_enclosed_objects() {
+        let mut drawing = DummyContext::default();
+        let inside = add_line(&mut drawing, Point::new(1.0, 1.0), Point::new(5.0, 5.0));
+        let _partly_inside = add_line(&mut drawing, Point::new(5.0, 5.0), Point::new(50.0, 5.0));
+        index_everything(&mut drawing);
+
+        drag(&mut drawing, mouse_at(0.0, 0.0), mouse_at(10.0, 10.0));
+
+        assert_eq!(selected(&drawing), vec![inside]);
+    }
+
+    #[test]
+    fn dragging_left_selects_anything_touched() {
+        let mut drawing = DummyContext::default();
+        let inside = add_line(&mut drawing, Point::new(1.0, 1.0), Point::new(5.0, 5.0));
+        let partly_inside = add_line(&mut drawing, Point::new(5.0, 5.0), Point::new(50.0, 5.0));
+        let _outside = add_line(&mut drawing, Point::new(20.0, 20.0), Point::new(30.0, 30.0));
+        index_everything(&mut drawing);
+
+        drag(&mut drawing, mouse_at(10.0, 10.0), mouse_at(0.0, 0.0));
+
+        assert_eq!(selected(&drawing), vec![inside, partly_inside]);
+    }
+
+    #[test]
+    fn the_selection_preview_is_removed_afterwards() {
+        let mut drawing = DummyContext::default();
+        index_everything(&mut drawing);
+        let mut idle = Idle::default();
+
+        idle.on_mouse_down(&mut drawing, &mouse_at(0.0, 0.0));
+        idle.on_mouse_move(&mut drawing, &mouse_at(100.0, 50.0));
+        let with_preview = drawing.world.read_storage::<DrawingObject>().join().count();
+        idle.on_mouse_up(&mut drawing, &mouse_at(100.0, 50.0));
+
+        let drawing_objects = drawing.world.read_storage::<DrawingObject>();
+        // just the grid is left
+        assert!(with_preview > 1);
+        assert_eq!(drawing_objects.join().count(), 1);
+    }
+
+    #[test]
+    fn the_selection_preview_reuses_its_dashes() {
+        let mut drawing = DummyContext::default();
+        index_everything(&mut drawing);
+        let mut idle = Idle::default();
+        let dashes = |drawing: &DummyContext| -> Vec<Entity> {
+            let entities = drawing.world.entities();
+            let drawing_objects = drawing.world.read_storage::<DrawingObject>();
+            (&entities, &drawing_objects)
+                .join()
+                .map(|(entity, _)| entity)
+                .collect()
+        };
+
+        idle.on_mouse_down(&mut drawing, &mouse_at(0.0, 0.0));
+        idle.on_mouse_move(&mut drawing, &mouse_at(100.0, 51.0));
+        let before = dashes(&drawing);
+        // still the same number of dashes along each side
+        idle.on_mouse_move(&mut drawing, &mouse_at(100.0, 55.0));
+
+        assert_eq!(dashes(&drawing), before);
+    }
+
+    #[test]
+    fn empty_space_can_pan_instead() {
+        let mut drawing = DummyContext::default();
+        index_everything(&mut drawing);
+        drawing.world.insert(Settings {
+            empty_space_drag: EmptySpaceDrag::Pan,
+            ..Default::default()
+        });
+        let mut idle = Idle::default();
+
+        idle.on_mouse_down(&mut drawing, &mouse_at(0.0, 0.0));
+
+        assert_eq!(idle.get_cursor(), "grab");
+    }
+
+    #[test]
+    fn clicking_replaces_the_selection() {
+        let mut drawing = DummyContext::default();
+        let first = add_line(&mut drawing, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        let second = add_line(&mut drawing, Point::new(0.0, 50.0), Point::new(10.0, 50.0));
+        index_everything(&mut drawing);
+
+        click(&mut drawing, mouse_at(5.0, 0.0));
+        click(&mut drawing, mouse_at(5.0, 50.0));
+
+        assert_eq!(selected(&drawing), vec![second]);
+        assert!(!drawing.is_selected(first));
+    }
+
+    #[test]
+    fn only_the_left_button_selects() {
+        let mut drawing = DummyContext::default();
+        add_line(&mut drawing, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        index_everything(&mut drawing);
+
+        for &button in &[MouseButtons::MIDDLE_BUTTON, MouseButtons::RIGHT_BUTTON] {
+            let args = MouseEventArgs {
+                button_state: button,
+                ..mouse_at(5.0, 0.0)
+            };
+            click(&mut drawing, args);
         }
+
+        assert!(selected(&drawing).is_empty());
+    }
+
+    #[test]
+    fn shift_click_adds_to_the_selection() {
+        let mut drawing = DummyContext::default();
+        let first = add_line(&mut drawing, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        let second = add_line(&mut drawing, Point::new(0.0, 50.0), Point::new(10.0, 50.0));
+        index_everything(&mut drawing);
+
+        click(&mut drawing, mouse_at(5.0, 0.0));
+        click(
+            &mut drawing,
+            MouseEventArgs {
+                shift_pressed: true,
+                ..mouse_at(5.0, 50.0)
+            },
+        );
+
+        assert_eq!(selected(&drawing), vec![first, second]);
+    }
+
+    #[test]
+    fn control_click_toggles_the_selection() {
+        let mut drawing = DummyContext::default();
+        let first = add_line(&mut drawing, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        let second = add_line(&mut drawing, Point::new(0.0, 50.0), Point::new(10.0, 50.0));
+        index_everything(&mut drawing);
+        let control = |args: MouseEventArgs| MouseEventArgs {
+            control_pressed: true,
+            ..args
+        };
+
+        click(&mut drawing, control(mouse_at(5.0, 0.0)));
+        click(&mut drawing, control(mouse_at(5.0, 50.0)));
+        click(&mut drawing, control(mouse_at(5.0, 0.0)));
+
+        assert_eq!(selected(&drawing), vec![second]);
+        assert!(!drawing.is_selected(first));
     }
 
     #[test]
-    fn change_to_arc_mode() {
+    fn the_erase_button_erases_the_selection() {
         let mut idle = Idle::default();
         let mut drawing = DummyContext::default();
-        let args = KeyboardEventArgs::pressing(VirtualKeyCode::A);
+        let line = add_line(&mut drawing, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        drawing.select(line);
+
+        let got = idle.on_button_clicked(&mut drawing, &ButtonType::Erase);
+
+        assert!(got.changes_to::<Idle>());
+        let deletes = drawing.world.read_storage::<Delete>();
+        assert!(deletes.contains(drawing.command));
+    }
+
+    fn start_of(drawing: &DummyContext, line: Entity) -> Point {
+        match drawing.world.read_storage::<DrawingObject>().get(line) {
+            Some(DrawingObject {
+                geometry: Geometry::Line(line),
+                ..
+            }) => line.start,
+            other => panic!("Expected a line but found {:?}", other),
+        }
+    }
 
-        let got = idle.on_key_pressed(&mut drawing, &args);
+    #[test]
+    fn arrow_keys_nudge_the_selection_by_a_grid_step() {
+        let inputs = vec![
+            (
+                KeyboardEventArgs::pressing(VirtualKeyCode::Right),
+                Point::new(GRID_SPACING, 0.0),
+            ),
+            (
+                KeyboardEventArgs {
+                    shift_pressed: true,
+                    ..KeyboardEventArgs::pressing(VirtualKeyCode::Up)
+                },
+                Point::new(0.0, GRID_SPACING * BIG_NUDGE_STEPS),
+            ),
+            (
+                KeyboardEventArgs {
+                    control_pressed: true,
+                    ..KeyboardEventArgs::pressing(VirtualKeyCode::Left)
+                },
+                Point::new(-1.0, 0.0),
+            ),
+        ];
 
-        assert!(got.changes_to::<AddArcMode>());
+        for (args, should_be) in inputs {
+            let mut idle = Idle::default();
+            let mut drawing = DummyContext::default();
+            let line = add_line(&mut drawing, Point::zero(), Point::new(10.0, 0.0));
+            drawing.select(line);
+
+            let got = idle.on_key_pressed(&mut drawing, &args);
+
+            assert!(got.does_nothing());
+            assert_eq!(start_of(&drawing, line), should_be, "{:?}", args);
+        }
     }
 
     #[test]
-    fn change_to_line_mode() {
+    fn arrow_keys_pan_when_nothing_is_selected() {
         let mut idle = Idle::default();
         let mut drawing = DummyContext::default();
-        let args = KeyboardEventArgs::pressing(VirtualKeyCode::L);
+        let line = add_line(&mut drawing, Point::zero(), Point::new(10.0, 0.0));
 
-        let got = idle.on_key_pressed(&mut drawing, &args);
+        idle.on_key_pressed(
+            &mut drawing,
+            &KeyboardEventArgs::pressing(VirtualKeyCode::Down),
+        );
 
-        assert!(got.changes_to::<AddLineMode>());
+        assert_eq!(start_of(&drawing, line), Point::zero());
+        let viewports = drawing.world.read_storage::<Viewport>();
+        let centre = viewports.get(drawing.viewport).unwrap().centre;
+        assert_eq!(centre, Point::new(0.0, -GRID_SPACING));
+    }
+
+    fn labels(items: Vec<MenuItem>) -> Vec<&'static str> {
+        items.into_iter().map(|item| item.label).collect()
     }
 
     #[test]
-    fn change_to_point_mode() {
+    fn right_clicking_empty_space_with_nothing_selected_does_nothing() {
         let mut idle = Idle::default();
         let mut drawing = DummyContext::default();
-        let args = KeyboardEventArgs::pressing(VirtualKeyCode::P);
+        index_everything(&mut drawing);
 
-        let got = idle.on_key_pressed(&mut drawing, &args);
+        idle.on_context_menu(&mut drawing, &mouse_at(5.0, 5.0));
 
-        assert!(got.changes_to::<AddPointMode>());
+        let deletes = drawing.world.read_storage::<Delete>();
+        assert!(!deletes.contains(drawing.command));
+        assert!(selected(&drawing).is_empty());
     }
 
     #[test]
-    fn pressing_any_other_key_does_nothing() {
+    fn right_clicking_selects_whatever_is_under_the_cursor() {
         let mut idle = Idle::default();
         let mut drawing = DummyContext::default();
-        let args = KeyboardEventArgs::pressing(VirtualKeyCode::Q);
+        let line = add_line(&mut drawing, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        index_everything(&mut drawing);
+        assert!(!labels(idle.context_menu(&drawing)).contains(&"Delete"));
+
+        idle.on_context_menu(&mut drawing, &mouse_at(5.0, 0.0));
+
+        assert_eq!(selected(&drawing), vec![line]);
+        let got = labels(idle.context_menu(&drawing));
+        assert_eq!(
+            got,
+            vec!["Delete", "Copy", "Properties", "Zoom to Selection"]
+        );
+    }
+
+    #[test]
+    fn paste_is_only_offered_once_something_has_been_copied() {
+        let idle = Idle::default();
+        let mut drawing = DummyContext::default();
+        let line = add_line(&mut drawing, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        assert!(!labels(idle.context_menu(&drawing)).contains(&"Paste"));
 
-        let got = idle.on_key_pressed(&mut drawing, &args);
+        drawing.select(line);
+        let mut clipboard = Clipboard::default();
+        clipboard.copy_selection(&drawing.world);
+        drawing.world.insert(clipboard);
+        drawing.unselect_all();
 
-        assert!(got.does_nothing());
+        assert_eq!(labels(idle.context_menu(&drawing))[0], "Paste");
     }
 }
@@ -1,26 +1,64 @@
-mod add_arc_mode;
+pub mod add_arc_mode;
+pub mod add_circle_mode;
+pub mod add_ellipse_mode;
 pub mod add_line_mode;
 pub mod add_point_mode;
+pub mod add_polygon_mode;
+pub mod add_polyline_mode;
+pub mod add_rectangle_mode;
 pub mod idle;
+mod outline;
+#[cfg(test)]
+mod test_context;
 
 pub use add_arc_mode::AddArcMode;
+pub use add_circle_mode::AddCircleMode;
+pub use add_ellipse_mode::AddEllipseMode;
 pub use add_line_mode::AddLineMode;
 pub use add_point_mode::AddPointMode;
+pub use add_polygon_mode::AddPolygonMode;
+pub use add_polyline_mode::AddPolylineMode;
+pub use add_rectangle_mode::AddRectangleMode;
 pub use idle::Idle;
 
+use super::components::Preview;
+use super::context_menu::MenuItem;
+use super::coordinate_entry::{self, ApplyTypedLocation};
+use super::geometry::{self, BoundingBox};
+use super::history;
 use super::keyboard_event_args::{KeyboardEventArgs, VirtualKeyCode};
 use super::msg::ButtonType;
+use super::shapes::{Shape, ShapePart};
+use super::snaps::{self, ApplyObjectSnap};
+use super::spatial_index;
+use super::tracking::{self, ApplyTracking};
 
 use arcs::{
     algorithms::Translate,
-    components::{DrawingObject, Selected, Viewport},
-    euclid::{Point2D, Scale},
+    components::{
+        layer::LayerType, CursorPosition, Delete, DrawingObject, Layer, Selected, Viewport,
+    },
+    euclid::{Point2D, Scale, Size2D},
     specs::prelude::*,
+    systems::snapper::Snapper,
     CanvasSpace, DrawingSpace, Point, Vector,
 };
 use genawaiter::sync::{Co, Gen};
 use std::{any::Any, fmt::Debug};
 
+/// The furthest out [`ApplicationContext::zoom_viewport()`] will let you
+/// zoom.
+pub const MIN_PIXELS_PER_DRAWING_UNIT: f64 = 0.01;
+/// The furthest in [`ApplicationContext::zoom_viewport()`] will let you zoom.
+pub const MAX_PIXELS_PER_DRAWING_UNIT: f64 = 100.0;
+/// How much each pixel of mouse wheel scrolling zooms by.
+const ZOOM_PER_WHEEL_PIXEL: f64 = 1.002;
+/// The distance between grid lines, in drawing units.
+pub const GRID_SPACING: f64 = 20.0;
+/// The gap left around the drawing by [`ApplicationContext::zoom_to_fit()`],
+/// in pixels.
+const ZOOM_TO_FIT_MARGIN: f64 = 20.0;
+
 /// Contextual information passed to each [`State`] when it handles events.
 pub trait ApplicationContext {
     fn world(&self) -> &World;
@@ -29,11 +67,19 @@ pub trait ApplicationContext {
     fn viewport(&self) -> Entity;
     /// The default [`arcs::components::Layer`].
     fn default_layer(&self) -> Entity;
+    /// The [`Layer`] used for temporary objects like the grid and selection
+    /// rectangle.
+    fn system_layer(&self) -> Entity;
     /// The pointer for this Application
     fn pointer(&self) -> Entity;
     /// The grid for this Application
     fn grid(&self) -> Entity;
     fn command(&self) -> Entity;
+    /// The size of the canvas, in pixels.
+    fn canvas_size(&self) -> Size2D<f64, CanvasSpace>;
+    /// Run the dispatcher immediately, for when a [`State`] needs the
+    /// results of one command before it can issue the next.
+    fn run_systems(&mut self);
 
     /// An optimisation hint that the canvas doesn't need to be redrawn after
     /// this event handler returns.
@@ -42,9 +88,9 @@ pub trait ApplicationContext {
     /// Get a list of all the entities which lie "under" a point, for some
     /// definition of "under".
     ///
-    /// Typically this will be implemented by the drawing canvas having some
-    /// sort of "pick box" where anything within, say, 3 pixels of something is
-    /// considered to be "under" it.
+    /// The canvas has a "pick box" where anything within 3 pixels of the
+    /// location is considered to be "under" it. Entities are returned closest
+    /// first.
     fn entities_under_point<'this>(
         &'this self,
         location: Point,
@@ -59,22 +105,18 @@ pub trait ApplicationContext {
         } = viewports.get(self.viewport()).unwrap();
 
         /// Generators aren't stable so we use the `genawaiter` hack to
-        /// "close over" our `Space`.
+        /// "close over" our spatial index.
         async fn iter(
             world: &World,
             pixels_per_drawing_unit: Scale<f64, DrawingSpace, CanvasSpace>,
             location: Point,
             co: Co<Entity>,
         ) {
-            let search_radius = pixels_per_drawing_unit.get() / PIXEL_RADIUS;
-
-            // TODO: selection is currently not working, reactivate
-            // this by fixing the Space issue
-            // let space = world.read_resource::<Space>();
+            let search_radius = PIXEL_RADIUS / pixels_per_drawing_unit.get();
 
-            // for spatial in space.query_point(location, search_radius) {
-            //     co.yield_(spatial.entity).await;
-            // }
+            for entity in spatial_index::entities_near(world, location, search_radius) {
+                co.yield_(entity).await;
+            }
         }
 
         Box::new(
@@ -90,23 +132,143 @@ pub trait ApplicationContext {
             .unwrap();
     }
 
+    /// Remove an object from the selection.
+    fn unselect(&mut self, target: Entity) {
+        self.world().write_storage::<Selected>().remove(target);
+    }
+
+    fn is_selected(&self, target: Entity) -> bool {
+        self.world().read_storage::<Selected>().contains(target)
+    }
+
     /// Clear the selection.
     fn unselect_all(&mut self) {
         self.world().write_storage::<Selected>().clear();
     }
 
+    /// Delete every selected object.
+    ///
+    /// The actual deleting is done by the
+    /// [`arcs::systems::deleter::Deleter`] system the next time the
+    /// dispatcher runs.
+    fn delete_selection(&mut self) {
+        // the viewport gets selected while panning, make sure we don't
+        // delete it by accident
+        let viewport = self.viewport();
+        self.unselect(viewport);
+
+        let command = self.command();
+        let _ = self
+            .world()
+            .write_storage::<Delete>()
+            .insert(command, Delete {});
+        self.checkpoint();
+    }
+
     /// Translate all selected objects by a specific amount.
     fn translate_selection(&mut self, displacement: Vector) {
         let world = self.world();
-        let (entities, selected, mut drawing_objects): (
-            Entities,
+        let (selected, parts, mut drawing_objects, mut shapes): (
             ReadStorage<Selected>,
+            ReadStorage<ShapePart>,
             WriteStorage<DrawingObject>,
+            WriteStorage<Shape>,
         ) = world.system_data();
 
-        for (_, _, drawing_object) in (&entities, &selected, &mut drawing_objects).join() {
+        // parts are redrawn from their shape
+        for (_, _, drawing_object) in (&selected, !&parts, &mut drawing_objects).join() {
             drawing_object.geometry.translate(displacement);
         }
+        for (_, shape) in (&selected, &mut shapes).join() {
+            shape.geometry.translate(displacement);
+        }
+    }
+
+    /// Mark the end of an operation, everything changed since the previous
+    /// checkpoint will be undone as a single step.
+    fn checkpoint(&mut self) {
+        history::history_mut(self.world_mut()).request_checkpoint();
+    }
+
+    /// Update the [`CursorPosition`] and run it through the [`Snapper`],
+    /// object snaps and tracking, returning the location the cursor ended up
+    /// at.
+    ///
+    /// States which maintain their own preview geometry use this so they
+    /// snap the same way as the points and lines created by the dispatcher.
+    fn set_cursor_position(&mut self, location: Point) -> Point {
+        self.world_mut().write_resource::<CursorPosition>().location = location;
+        RunNow::run_now(&mut Snapper, self.world());
+        RunNow::run_now(&mut ApplyObjectSnap, self.world());
+        RunNow::run_now(&mut ApplyTracking, self.world());
+        RunNow::run_now(&mut ApplyTypedLocation, self.world());
+
+        self.world().read_resource::<CursorPosition>().location
+    }
+
+    /// Look for an object to snap to near `location`, showing its marker and
+    /// making the dispatcher put new objects there.
+    ///
+    /// Returns `true` if the marker changed and the canvas needs a redraw.
+    fn snap_to_objects(&mut self, location: Point) -> bool {
+        let pixels_per_drawing_unit = self.pixels_per_drawing_unit();
+        let system_layer = self.system_layer();
+
+        snaps::update(
+            self.world_mut(),
+            system_layer,
+            location,
+            pixels_per_drawing_unit,
+        )
+    }
+
+    /// Constrain a line being drawn from `start` using ortho mode or polar
+    /// tracking, showing an alignment guide.
+    ///
+    /// Returns `true` if the guide changed and the canvas needs a redraw.
+    fn track_from(&mut self, start: Point, location: Point, shift_pressed: bool) -> bool {
+        let pixels_per_drawing_unit = self.pixels_per_drawing_unit();
+        let system_layer = self.system_layer();
+
+        tracking::update(
+            self.world_mut(),
+            system_layer,
+            start,
+            location,
+            shift_pressed,
+            pixels_per_drawing_unit,
+        )
+    }
+
+    /// Get ready to click at a location the user typed in, making sure it
+    /// won't be moved by the grid, object snaps or tracking.
+    fn typed_click_at(&mut self, location: Point) -> MouseEventArgs {
+        snaps::clear(self.world_mut());
+        tracking::clear(self.world_mut());
+        coordinate_entry::set_typed_location(self.world_mut(), location);
+
+        let cursor = {
+            let viewports = self.world().read_storage::<Viewport>();
+            let viewport = viewports.get(self.viewport()).unwrap();
+            arcs::window::to_canvas_coordinates(location, viewport, self.canvas_size())
+        };
+
+        MouseEventArgs {
+            location,
+            cursor,
+            button_state: MouseButtons::LEFT_BUTTON,
+            shift_pressed: false,
+            control_pressed: false,
+        }
+    }
+
+    fn pixels_per_drawing_unit(&self) -> f64 {
+        self.world()
+            .read_storage::<Viewport>()
+            .get(self.viewport())
+            .unwrap()
+            .pixels_per_drawing_unit
+            .get()
     }
 
     fn pan_viewport(&mut self, displacement: Vector) {
@@ -114,6 +276,103 @@ pub trait ApplicationContext {
         let viewport = viewports.get_mut(self.viewport()).unwrap();
         viewport.translate(displacement);
     }
+
+    /// Zoom in by a particular `factor` (values less than `1.0` zoom out),
+    /// keeping the `anchor` point in the same spot on the canvas.
+    fn zoom_viewport(&mut self, factor: f64, anchor: Point) {
+        let mut viewports = self.world().write_storage::<Viewport>();
+        let viewport = viewports.get_mut(self.viewport()).unwrap();
+        zoom_about(viewport, factor, anchor);
+    }
+
+    /// Zoom and pan so every object on a visible layer (or just the selected
+    /// ones) fits on the canvas, returning `false` if there was nothing to
+    /// zoom to.
+    fn zoom_to_fit(&mut self, selected_only: bool) -> bool {
+        let bounds = match drawing_extents(self.world(), selected_only) {
+            Some(bounds) => bounds,
+            None => return false,
+        };
+
+        let canvas_size = self.canvas_size();
+        let mut viewports = self.world().write_storage::<Viewport>();
+        let viewport = viewports.get_mut(self.viewport()).unwrap();
+        fit_to(viewport, bounds, canvas_size);
+
+        true
+    }
+}
+
+/// The area covered by everything on the visible, non-system layers, ignoring
+/// anything which is still being drawn.
+fn drawing_extents(world: &World, selected_only: bool) -> Option<BoundingBox> {
+    let drawing_objects = world.read_storage::<DrawingObject>();
+    let shapes = world.read_storage::<Shape>();
+    let parts = world.read_storage::<ShapePart>();
+    let previews = world.read_storage::<Preview>();
+    let layers = world.read_storage::<Layer>();
+    let selected = world.read_storage::<Selected>();
+
+    let objects = (&drawing_objects, !&parts, !&previews, selected.maybe())
+        .join()
+        .map(|(d, _, _, is_selected)| (d.layer, geometry::bounding_box(&d.geometry), is_selected));
+    let shapes = (&shapes, !&previews, selected.maybe())
+        .join()
+        .map(|(shape, _, is_selected)| (shape.layer, shape.geometry.bounding_box(), is_selected));
+
+    objects
+        .chain(shapes)
+        .filter(|(_, _, is_selected)| !selected_only || is_selected.is_some())
+        .filter(|(layer, _, _)| {
+            layers.get(*layer).map_or(true, |layer| {
+                layer.visible && !matches!(layer.layer_type, LayerType::System)
+            })
+        })
+        .filter_map(|(_, bounds, _)| bounds)
+        .fold(None, |extents: Option<BoundingBox>, bounds| {
+            Some(extents.map_or(bounds, |e| e.union(&bounds)))
+        })
+}
+
+/// Centre a [`Viewport`] on some `bounds`, zooming so they fill the canvas
+/// (minus a margin).
+fn fit_to(viewport: &mut Viewport, bounds: BoundingBox, canvas_size: Size2D<f64, CanvasSpace>) {
+    let available_width = (canvas_size.width - 2.0 * ZOOM_TO_FIT_MARGIN).max(1.0);
+    let available_height = (canvas_size.height - 2.0 * ZOOM_TO_FIT_MARGIN).max(1.0);
+    let size = bounds.size();
+
+    // a single point (or a horizontal/vertical line) doesn't constrain the
+    // zoom in that direction
+    let scale_x = if size.width > 0.0 {
+        available_width / size.width
+    } else {
+        f64::INFINITY
+    };
+    let scale_y = if size.height > 0.0 {
+        available_height / size.height
+    } else {
+        f64::INFINITY
+    };
+    let scale = scale_x.min(scale_y);
+
+    if scale.is_finite() {
+        viewport.pixels_per_drawing_unit =
+            Scale::new(scale.clamp(MIN_PIXELS_PER_DRAWING_UNIT, MAX_PIXELS_PER_DRAWING_UNIT));
+    }
+    viewport.centre = bounds.center();
+}
+
+/// Scale a [`Viewport`] around an `anchor` point, staying within the
+/// [`MIN_PIXELS_PER_DRAWING_UNIT`] and [`MAX_PIXELS_PER_DRAWING_UNIT`] limits.
+fn zoom_about(viewport: &mut Viewport, factor: f64, anchor: Point) {
+    let previous = viewport.pixels_per_drawing_unit.get();
+    let scale = (previous * factor).clamp(MIN_PIXELS_PER_DRAWING_UNIT, MAX_PIXELS_PER_DRAWING_UNIT);
+
+    // the anchor is (anchor - centre) * scale pixels from the middle of the
+    // canvas, so shrink that offset by however much we actually zoomed
+    let actual_factor = scale / previous;
+    viewport.centre = anchor + (viewport.centre - anchor) / actual_factor;
+    viewport.pixels_per_drawing_unit = Scale::new(scale);
 }
 
 impl<'a, A: ApplicationContext + ?Sized> ApplicationContext for &'a mut A {
@@ -137,6 +396,10 @@ impl<'a, A: ApplicationContext + ?Sized> ApplicationContext for &'a mut A {
         (**self).default_layer()
     }
 
+    fn system_layer(&self) -> Entity {
+        (**self).system_layer()
+    }
+
     fn pointer(&self) -> Entity {
         (**self).pointer()
     }
@@ -146,7 +409,15 @@ impl<'a, A: ApplicationContext + ?Sized> ApplicationContext for &'a mut A {
     }
 
     fn command(&self) -> Entity {
-        (**self).grid()
+        (**self).command()
+    }
+
+    fn canvas_size(&self) -> Size2D<f64, CanvasSpace> {
+        (**self).canvas_size()
+    }
+
+    fn run_systems(&mut self) {
+        (**self).run_systems();
     }
 }
 
@@ -183,6 +454,29 @@ pub trait State: Debug + AsAny {
         Transition::DoNothing
     }
 
+    /// The right mouse button was clicked.
+    fn on_context_menu(
+        &mut self,
+        _ctx: &mut dyn ApplicationContext,
+        _event_args: &MouseEventArgs,
+    ) -> Transition {
+        Transition::DoNothing
+    }
+
+    /// The mouse wheel was scrolled by `delta` pixels, where positive values
+    /// scroll down.
+    ///
+    /// By default this zooms in or out around the cursor.
+    fn on_mouse_wheel(
+        &mut self,
+        ctx: &mut dyn ApplicationContext,
+        event_args: &MouseEventArgs,
+        delta: f64,
+    ) -> Transition {
+        ctx.zoom_viewport(ZOOM_PER_WHEEL_PIXEL.powf(-delta), event_args.location);
+        Transition::DoNothing
+    }
+
     /// A button was pressed on the keyboard.
     fn on_key_pressed(
         &mut self,
@@ -192,6 +486,12 @@ pub trait State: Debug + AsAny {
         Transition::DoNothing
     }
 
+    /// Should a key go straight to [`State::on_key_pressed()`] instead of
+    /// being looked up in the keymap first?
+    fn consumes_key(&self, _event_args: &KeyboardEventArgs) -> bool {
+        false
+    }
+
     /// A ui button was clicked
     fn on_button_clicked(
         &mut self,
@@ -201,16 +501,32 @@ pub trait State: Debug + AsAny {
         self.on_cancelled(ctx);
         match event_args {
             ButtonType::Arc => Transition::ChangeState(Box::new(AddArcMode::default())),
+            ButtonType::Circle => Transition::ChangeState(Box::new(AddCircleMode::default())),
             ButtonType::Point => Transition::ChangeState(Box::new(AddPointMode::default())),
             ButtonType::Line => Transition::ChangeState(Box::new(AddLineMode::default())),
+            ButtonType::Polyline => Transition::ChangeState(Box::new(AddPolylineMode::default())),
+            ButtonType::Rectangle => Transition::ChangeState(Box::new(AddRectangleMode::default())),
+            ButtonType::Polygon => Transition::ChangeState(Box::new(AddPolygonMode::default())),
+            ButtonType::Ellipse => Transition::ChangeState(Box::new(AddEllipseMode::default())),
             ButtonType::Select => Transition::ChangeState(Box::new(Idle::default())),
-            ButtonType::Snap => Transition::DoNothing,
+            ButtonType::Erase => {
+                ctx.delete_selection();
+                Transition::ChangeState(Box::new(Idle::default()))
+            }
+            // the rest of the buttons don't change the mode
+            _ => Transition::DoNothing,
         }
     }
 
     fn get_cursor(&self) -> &str {
         "default"
     }
+
+    /// The items to show in the context menu, after
+    /// [`State::on_context_menu()`] has handled the right-click.
+    fn context_menu(&self, _ctx: &dyn ApplicationContext) -> Vec<MenuItem> {
+        Vec::new()
+    }
 }
 
 /// A helper trait for casting `self` to [`Any`].
@@ -232,6 +548,35 @@ pub enum Transition {
     DoNothing,
 }
 
+/// Switch a mode's nested [`State`] if the [`Transition`] asks for it.
+fn change_nested_state(nested: &mut Box<dyn State>, transition: Transition) {
+    match transition {
+        Transition::ChangeState(new_state) => {
+            log::debug!("Changing state {:?} -> {:?}", nested, new_state);
+            *nested = new_state;
+        }
+        Transition::DoNothing => {}
+    }
+}
+
+/// Click at a location the user typed in by sending a mouse down and up to a
+/// mode's nested [`State`], returning where the click ended up.
+fn click_nested_at(
+    ctx: &mut dyn ApplicationContext,
+    nested: &mut Box<dyn State>,
+    location: Point,
+) -> Point {
+    let args = ctx.typed_click_at(location);
+    let transition = nested.on_mouse_down(ctx, &args);
+    change_nested_state(nested, transition);
+    // the dispatcher needs to create the point before we can let go of it
+    ctx.run_systems();
+    let transition = nested.on_mouse_up(ctx, &args);
+    change_nested_state(nested, transition);
+
+    ctx.world().read_resource::<CursorPosition>().location
+}
+
 #[allow(dead_code)]
 impl Transition {
     /// Checks whether the transition will change to a particular [`State`].
@@ -262,13 +607,175 @@ pub struct MouseEventArgs {
     pub cursor: Point2D<f64, CanvasSpace>,
     /// The state of the mouse buttons.
     pub button_state: MouseButtons,
+    pub shift_pressed: bool,
+    pub control_pressed: bool,
+}
+
+impl MouseEventArgs {
+    /// Does this event involve the left mouse button?
+    ///
+    /// States use this to ignore the middle and right buttons, which are
+    /// used for panning and the context menu.
+    pub fn is_left_button(&self) -> bool {
+        self.button_state.contains(MouseButtons::LEFT_BUTTON)
+    }
 }
 
 bitflags::bitflags! {
     /// Which mouse button (or buttons) are pressed?
+    ///
+    /// The bits are the same as the ones used by [`MouseEvent.buttons`][mdn].
+    ///
+    /// [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/buttons
     pub struct MouseButtons: u8 {
-        const LEFT_BUTTON = 0;
-        const RIGHT_BUTTON = 1;
-        const MIDDLE_BUTTON = 2;
+        const LEFT_BUTTON = 1;
+        const RIGHT_BUTTON = 2;
+        const MIDDLE_BUTTON = 4;
+    }
+}
+
+impl MouseButtons {
+    /// Decode `MouseEvent.buttons`, ignoring the "back" and "forward"
+    /// buttons.
+    pub fn from_buttons(buttons: u16) -> MouseButtons {
+        MouseButtons::from_bits_truncate(buttons as u8)
+    }
+
+    /// Decode `MouseEvent.button`, the button which was just pressed or
+    /// released. Annoyingly, this numbers the buttons differently.
+    pub fn from_button(button: i16) -> MouseButtons {
+        match button {
+            0 => MouseButtons::LEFT_BUTTON,
+            1 => MouseButtons::MIDDLE_BUTTON,
+            2 => MouseButtons::RIGHT_BUTTON,
+            _ => MouseButtons::empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arcs::{
+        components::{Geometry, Name},
+        primitives::Line,
+    };
+
+    fn viewport(scale: f64) -> Viewport {
+        Viewport {
+            centre: Point::new(10.0, 20.0),
+            pixels_per_drawing_unit: Scale::new(scale),
+        }
+    }
+
+    fn to_canvas(viewport: &Viewport, location: Point) -> Vector {
+        (location - viewport.centre) * viewport.pixels_per_drawing_unit.get()
+    }
+
+    #[test]
+    fn the_anchor_stays_under_the_cursor() {
+        let mut vp = viewport(2.0);
+        let anchor = Point::new(-30.0, 45.0);
+        let before = to_canvas(&vp, anchor);
+
+        zoom_about(&mut vp, 1.5, anchor);
+
+        assert_eq!(vp.pixels_per_drawing_unit.get(), 3.0);
+        let after = to_canvas(&vp, anchor);
+        assert!((after - before).length() < 1e-9);
+    }
+
+    #[test]
+    fn fit_a_wide_drawing_to_the_canvas() {
+        let mut vp = viewport(1.0);
+        let bounds = BoundingBox::new(Point::new(0.0, 0.0), Point::new(360.0, 10.0));
+
+        fit_to(&mut vp, bounds, Size2D::new(400.0, 300.0));
+
+        assert_eq!(vp.centre, Point::new(180.0, 5.0));
+        assert_eq!(vp.pixels_per_drawing_unit.get(), 1.0);
+    }
+
+    #[test]
+    fn fitting_a_single_point_just_centres_it() {
+        let mut vp = viewport(2.5);
+        let location = Point::new(-4.0, 7.0);
+
+        fit_to(
+            &mut vp,
+            BoundingBox::new(location, location),
+            Size2D::new(400.0, 300.0),
+        );
+
+        assert_eq!(vp.centre, location);
+        assert_eq!(vp.pixels_per_drawing_unit.get(), 2.5);
+    }
+
+    #[test]
+    fn zooming_is_clamped() {
+        let mut vp = viewport(50.0);
+        let anchor = Point::new(0.0, 0.0);
+        let before = to_canvas(&vp, anchor);
+
+        zoom_about(&mut vp, 10.0, anchor);
+
+        assert_eq!(
+            vp.pixels_per_drawing_unit.get(),
+            MAX_PIXELS_PER_DRAWING_UNIT
+        );
+        // only zoomed by 2x, but the anchor still shouldn't move
+        assert!((to_canvas(&vp, anchor) - before).length() < 1e-9);
+
+        zoom_about(&mut vp, 1e-9, anchor);
+        assert_eq!(
+            vp.pixels_per_drawing_unit.get(),
+            MIN_PIXELS_PER_DRAWING_UNIT
+        );
+    }
+
+    #[test]
+    fn previews_are_left_out_of_the_extents() {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let layer = Layer::create(
+            world.create_entity(),
+            Name::new("default"),
+            Layer::default(),
+        );
+        let line = |start: Point, end: Point| DrawingObject {
+            geometry: Geometry::Line(Line::new(start, end)),
+            layer,
+        };
+        world
+            .create_entity()
+            .with(line(Point::new(0.0, 0.0), Point::new(10.0, 5.0)))
+            .build();
+        world
+            .create_entity()
+            .with(line(Point::new(10.0, 5.0), Point::new(500.0, 500.0)))
+            .with(Preview)
+            .build();
+
+        let got = drawing_extents(&world, false).unwrap();
+
+        assert_eq!(
+            got,
+            BoundingBox::new(Point::new(0.0, 0.0), Point::new(10.0, 5.0))
+        );
+    }
+
+    #[test]
+    fn decode_the_browsers_button_numbers() {
+        // the left and middle buttons held down
+        assert_eq!(
+            MouseButtons::from_buttons(1 | 4),
+            MouseButtons::LEFT_BUTTON | MouseButtons::MIDDLE_BUTTON
+        );
+        // the "back" button isn't something we care about
+        assert_eq!(MouseButtons::from_buttons(8), MouseButtons::empty());
+
+        assert_eq!(MouseButtons::from_button(0), MouseButtons::LEFT_BUTTON);
+        assert_eq!(MouseButtons::from_button(1), MouseButtons::MIDDLE_BUTTON);
+        assert_eq!(MouseButtons::from_button(2), MouseButtons::RIGHT_BUTTON);
     }
 }
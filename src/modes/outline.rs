@@ -0,0 +1,145 @@
+//! A shape drawn as line segments, shared by the modes which draw rectangles,
+//! polygons and ellipses, and by the selection rectangle.
+
+use crate::{
+    components::Preview,
+    modes::ApplicationContext,
+    polyline::Polyline,
+    shapes::{Shape, ShapeGeometry},
+};
+use arcs::{
+    components::{DrawingObject, Geometry, LineStyle},
+    primitives::Line,
+    specs::prelude::*,
+    Point,
+};
+
+/// The line entities making up an outline.
+///
+/// The entities are reused as the outline changes shape, so dragging it
+/// around doesn't keep creating and deleting things.
+#[derive(Debug, Default)]
+pub(crate) struct Outline {
+    lines: Vec<Entity>,
+    /// Overlays go on the system layer with their own style, because they
+    /// aren't part of the drawing.
+    overlay_style: Option<LineStyle>,
+}
+
+impl Outline {
+    /// An outline drawn over the drawing rather than being part of it (e.g.
+    /// the selection rectangle).
+    pub(crate) fn overlay(style: LineStyle) -> Outline {
+        Outline {
+            lines: Vec::new(),
+            overlay_style: Some(style),
+        }
+    }
+
+    /// Change the style used for an overlay, taking effect on the next
+    /// update.
+    pub(crate) fn set_overlay_style(&mut self, style: LineStyle) {
+        self.overlay_style = Some(style);
+    }
+
+    /// Make the outline a closed loop through `vertices`.
+    pub(crate) fn update(&mut self, ctx: &mut dyn ApplicationContext, vertices: &[Point]) {
+        self.update_lines(ctx, closed_segments(vertices));
+    }
+
+    /// Move the outline onto `segments`, adding or removing lines when the
+    /// number of segments changes.
+    pub(crate) fn update_lines(&mut self, ctx: &mut dyn ApplicationContext, segments: Vec<Line>) {
+        while self.lines.len() > segments.len() {
+            let line = self.lines.pop().unwrap();
+            if let Err(e) = ctx.world_mut().delete_entity(line) {
+                log::warn!("Unable to delete part of an outline: {}", e);
+            }
+        }
+
+        let layer = match self.overlay_style {
+            Some(_) => ctx.system_layer(),
+            None => ctx.default_layer(),
+        };
+        while self.lines.len() < segments.len() {
+            let line = ctx
+                .world_mut()
+                .create_entity()
+                .with(DrawingObject {
+                    geometry: Geometry::Line(Line::new(Point::zero(), Point::zero())),
+                    layer,
+                })
+                .with(Preview)
+                .build();
+            self.lines.push(line);
+        }
+
+        let mut drawing_objects = ctx.world().write_storage::<DrawingObject>();
+        let mut line_styles = ctx.world().write_storage::<LineStyle>();
+        for (&line, segment) in self.lines.iter().zip(segments) {
+            if let Some(drawing_object) = drawing_objects.get_mut(line) {
+                drawing_object.geometry = Geometry::Line(segment);
+            }
+            if let Some(style) = &self.overlay_style {
+                if line_styles.get(line) != Some(style) {
+                    let _ = line_styles.insert(line, style.clone());
+                }
+            }
+        }
+    }
+
+    /// Throw the outline away.
+    pub(crate) fn clear(&mut self, ctx: &mut dyn ApplicationContext) {
+        self.update_lines(ctx, Vec::new());
+    }
+
+    /// Throw the outline away and add a closed polyline through `vertices`
+    /// to the drawing in its place, so the finished shape is a single
+    /// object.
+    pub(crate) fn keep(&mut self, ctx: &mut dyn ApplicationContext, vertices: &[Point]) {
+        self.clear(ctx);
+
+        let layer = ctx.default_layer();
+        ctx.world_mut()
+            .create_entity()
+            .with(Shape {
+                geometry: ShapeGeometry::Polyline(Polyline::new(vertices.to_vec(), true)),
+                layer,
+            })
+            .build();
+    }
+}
+
+/// The lines joining each vertex to the next, wrapping back around to the
+/// first one.
+pub(crate) fn closed_segments(vertices: &[Point]) -> Vec<Line> {
+    if vertices.len() < 2 {
+        return Vec::new();
+    }
+
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(&start, &end)| Line::new(start, end))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_last_vertex_joins_back_to_the_first() {
+        let vertices = [
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(0.0, 1.0),
+        ];
+
+        let got = closed_segments(&vertices);
+
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[2].start, Point::new(0.0, 1.0));
+        assert_eq!(got[2].end, Point::new(0.0, 0.0));
+    }
+}
@@ -0,0 +1,167 @@
+//! A fake [`ApplicationContext`] for the [`State`](crate::modes::State)
+//! tests.
+
+use crate::{
+    components::Preview,
+    modes::{ApplicationContext, MouseButtons, MouseEventArgs, GRID_SPACING},
+    shapes::{Shape, ShapeGeometry},
+};
+use arcs::{
+    components::{
+        layer::LayerType, CursorPosition, DrawingObject, Geometry, Layer, Name, Viewport,
+    },
+    euclid::{Length, Point2D, Scale, Size2D},
+    primitives::Grid,
+    specs::prelude::*,
+    CanvasSpace, Point,
+};
+
+/// An [`ApplicationContext`] with just enough set up for testing a
+/// [`State`](crate::modes::State) without the rest of the application.
+pub(crate) struct DummyContext {
+    pub(crate) world: World,
+    pub(crate) viewport: Entity,
+    pub(crate) default_layer: Entity,
+    pub(crate) system_layer: Entity,
+    pub(crate) pointer: Entity,
+    pub(crate) grid: Entity,
+    pub(crate) command: Entity,
+}
+
+impl Default for DummyContext {
+    fn default() -> Self {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let viewport = world
+            .create_entity()
+            .with(Viewport {
+                centre: Point::zero(),
+                pixels_per_drawing_unit: Scale::new(1.0),
+            })
+            .build();
+
+        let default_layer = Layer::create(
+            world.create_entity(),
+            Name::from("default"),
+            Layer::default(),
+        );
+        let system_layer = Layer::create(
+            world.create_entity(),
+            Name::new("system_layer"),
+            Layer {
+                z_level: usize::MIN,
+                visible: true,
+                layer_type: LayerType::System,
+            },
+        );
+
+        let pointer = world.create_entity().build();
+
+        let grid = Grid::new(Length::new(GRID_SPACING), false);
+        let grid = world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Grid(grid),
+                layer: system_layer,
+            })
+            .build();
+
+        let command = world.create_entity().with(Name::new("command")).build();
+        world.insert(CursorPosition::default());
+
+        DummyContext {
+            world,
+            viewport,
+            default_layer,
+            system_layer,
+            pointer,
+            grid,
+            command,
+        }
+    }
+}
+
+impl ApplicationContext for DummyContext {
+    fn world(&self) -> &World {
+        &self.world
+    }
+
+    fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    fn viewport(&self) -> Entity {
+        self.viewport
+    }
+
+    fn default_layer(&self) -> Entity {
+        self.default_layer
+    }
+
+    fn system_layer(&self) -> Entity {
+        self.system_layer
+    }
+
+    fn pointer(&self) -> Entity {
+        self.pointer
+    }
+
+    fn grid(&self) -> Entity {
+        self.grid
+    }
+    fn command(&self) -> Entity {
+        self.command
+    }
+
+    fn canvas_size(&self) -> Size2D<f64, CanvasSpace> {
+        Size2D::new(800.0, 600.0)
+    }
+
+    fn run_systems(&mut self) {
+        // tests call the systems they need themselves
+    }
+
+    fn set_cursor_position(&mut self, location: Point) -> Point {
+        // the snapping systems aren't set up, so the cursor goes where it's
+        // told
+        self.world.write_resource::<CursorPosition>().location = location;
+        location
+    }
+}
+
+/// The left button being pressed at a point on the drawing.
+pub(crate) fn mouse_at(x: f64, y: f64) -> MouseEventArgs {
+    MouseEventArgs {
+        location: Point::new(x, y),
+        cursor: Point2D::new(x, y),
+        button_state: MouseButtons::LEFT_BUTTON,
+        shift_pressed: false,
+        control_pressed: false,
+    }
+}
+
+/// Check a drawing mode left exactly one [`Shape`] and no previews behind,
+/// returning the shape's geometry.
+pub(crate) fn single_shape(ctx: &mut DummyContext) -> ShapeGeometry {
+    ctx.world.maintain();
+
+    let previews = ctx.world.read_storage::<Preview>();
+    assert_eq!(previews.join().count(), 0, "Previews were left behind");
+
+    let shapes = ctx.world.read_storage::<Shape>();
+    let mut got: Vec<_> = shapes.join().map(|shape| shape.geometry.clone()).collect();
+    assert_eq!(got.len(), 1, "Expected a single shape, found {:?}", got);
+    got.remove(0)
+}
+
+/// Check a drawing mode left a single closed polyline with `vertices`
+/// corners.
+pub(crate) fn assert_single_closed_shape(ctx: &mut DummyContext, vertices: usize) {
+    match single_shape(ctx) {
+        ShapeGeometry::Polyline(polyline) => {
+            assert!(polyline.closed);
+            assert_eq!(polyline.vertices.len(), vertices);
+        }
+        other => panic!("Expected a polyline, found {:?}", other),
+    }
+}
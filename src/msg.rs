@@ -1,45 +1,137 @@
 use super::keyboard_event_args::{KeyboardEventArgs, VirtualKeyCode};
+use super::modes::MouseButtons;
+use super::snaps::SnapKind;
+use super::svg::Crop;
 use arcs::{euclid::Point2D, CanvasSpace};
-use web_sys::KeyboardEvent;
+use web_sys::{KeyboardEvent, MouseEvent};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 #[allow(dead_code)]
 pub enum ButtonType {
   Arc,
+  Circle,
   Point,
   Line,
+  Polyline,
+  Rectangle,
+  Polygon,
+  Ellipse,
   Select,
   Snap,
+  DragMode,
+  /// Turn an object snap on or off.
+  ObjectSnap(SnapKind),
+  Ortho,
+  /// Cycle through the polar tracking increments.
+  PolarTracking,
+  Erase,
+  /// Copy the selection to the clipboard.
+  Copy,
+  /// Paste whatever was last copied, centred on the cursor.
+  Paste,
+  /// Show a summary of the selection.
+  Properties,
+  Undo,
+  Redo,
+  ZoomExtents,
+  ZoomSelection,
+  Save,
+  ExportDxf,
+  ExportSvg(Crop),
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[allow(dead_code)]
 pub enum Msg {
   Rendered,
-  MouseDown(Point2D<f64, CanvasSpace>),
-  MouseUp(Point2D<f64, CanvasSpace>),
-  MouseMove(Point2D<f64, CanvasSpace>),
+  MouseDown(CanvasMouseEvent),
+  MouseUp(CanvasMouseEvent),
+  MouseMove(CanvasMouseEvent),
+  /// The right mouse button was clicked.
+  ContextMenu(CanvasMouseEvent),
+  /// An item was picked from the context menu.
+  ContextMenuItem(usize),
+  /// The properties panel's close button was clicked.
+  PropertiesClosed,
+  /// The mouse wheel was scrolled by some number of pixels.
+  MouseWheel(CanvasMouseEvent, f64),
   KeyPressed(KeyboardEventArgs),
+  KeyReleased(KeyboardEventArgs),
+  /// The canvas lost keyboard focus.
+  Blurred,
   ButtonClicked(ButtonType),
   WindowResized,
+  /// The user picked a drawing to open.
+  FileSelected(web_sys::File),
+  /// The contents of a drawing have been read and are ready to load.
+  DocumentLoaded { name: String, contents: String },
+  /// The text in the console changed.
+  ConsoleInput(String),
+  /// Run whatever is in the console.
+  ConsoleSubmit,
+  /// Try to finish the command name being typed into the console.
+  ConsoleComplete,
 }
 
-impl Msg {
-  pub fn from_key_press(ev: KeyboardEvent) -> Self {
-    let key = match ev.key().parse::<VirtualKeyCode>() {
-      Ok(got) => Some(got),
-      Err(_) => {
-        // encountered an unknown key code, log it so we can update the
-        // FromStr impl
-        log::warn!("Encountered an unknown key: {}", ev.key());
-        None
-      }
-    };
-
-    Msg::KeyPressed(KeyboardEventArgs {
+/// The parts of a [`MouseEvent`] on the canvas which we care about.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CanvasMouseEvent {
+  /// Where the mouse is, relative to the canvas's top-left corner.
+  pub cursor: Point2D<f64, CanvasSpace>,
+  /// The mouse buttons which are held down.
+  pub buttons: MouseButtons,
+  pub shift_pressed: bool,
+  pub control_pressed: bool,
+}
+
+impl CanvasMouseEvent {
+  pub fn from_mouse_event(ev: &MouseEvent) -> Self {
+    CanvasMouseEvent {
+      cursor: Point2D::new(ev.offset_x().into(), ev.offset_y().into()),
+      buttons: MouseButtons::from_buttons(ev.buttons()),
       shift_pressed: ev.shift_key(),
       control_pressed: ev.ctrl_key(),
-      key,
-    })
+    }
+  }
+
+  /// Like [`CanvasMouseEvent::from_mouse_event()`], but for events caused by
+  /// a button being pressed or released.
+  ///
+  /// By the time `mouseup` fires the released button is no longer in
+  /// `buttons`, so it gets added back in from `button`.
+  pub fn from_button_event(ev: &MouseEvent) -> Self {
+    let mut event = CanvasMouseEvent::from_mouse_event(ev);
+    event.buttons |= MouseButtons::from_button(ev.button());
+    event
+  }
+}
+
+impl Msg {
+  pub fn from_key_press(ev: KeyboardEvent) -> Self {
+    Msg::KeyPressed(keyboard_event_args(&ev))
+  }
+
+  pub fn from_key_release(ev: KeyboardEvent) -> Self {
+    Msg::KeyReleased(keyboard_event_args(&ev))
+  }
+}
+
+fn keyboard_event_args(ev: &KeyboardEvent) -> KeyboardEventArgs {
+  let key = match ev.key().parse::<VirtualKeyCode>() {
+    Ok(got) => Some(got),
+    Err(_) => {
+      // encountered an unknown key code, log it so we can update the
+      // FromStr impl
+      log::warn!("Encountered an unknown key: {}", ev.key());
+      None
+    }
+  };
+
+  KeyboardEventArgs {
+    shift_pressed: ev.shift_key(),
+    control_pressed: ev.ctrl_key(),
+    alt_pressed: ev.alt_key(),
+    meta_pressed: ev.meta_key(),
+    key,
   }
 }
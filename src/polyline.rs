@@ -0,0 +1,168 @@
+//! Polylines, a chain of straight segments which is treated as one object.
+//!
+//! `arcs` doesn't have a polyline [`Geometry`], so they are stored in a
+//! [`Shape`] instead.
+//!
+//! [`Geometry`]: arcs::components::Geometry
+//! [`Shape`]: crate::shapes::Shape
+
+use crate::geometry::{self, BoundingBox};
+use arcs::{primitives::Line, Point, Vector};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub vertices: Vec<Point>,
+    /// Is there a segment from the last vertex back to the first?
+    pub closed: bool,
+}
+
+impl Polyline {
+    pub fn new(vertices: Vec<Point>, closed: bool) -> Self {
+        Polyline { vertices, closed }
+    }
+
+    /// The segments joining consecutive vertices, including the one back to
+    /// the start when the polyline is closed.
+    pub fn segments(&self) -> Vec<Line> {
+        let mut segments: Vec<Line> = self
+            .vertices
+            .windows(2)
+            .map(|pair| Line::new(pair[0], pair[1]))
+            .collect();
+
+        if self.closed && self.vertices.len() > 2 {
+            let last = self.vertices[self.vertices.len() - 1];
+            segments.push(Line::new(last, self.vertices[0]));
+        }
+
+        segments
+    }
+
+    /// The total length of all the segments.
+    pub fn length(&self) -> f64 {
+        self.segments()
+            .iter()
+            .map(|segment| (segment.end - segment.start).length())
+            .sum()
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        if self.vertices.is_empty() {
+            None
+        } else {
+            Some(BoundingBox::from_points(&self.vertices))
+        }
+    }
+
+    /// How far `location` is from the closest point on the polyline.
+    pub fn distance_to(&self, location: Point) -> Option<f64> {
+        if let [only] = self.vertices.as_slice() {
+            return Some((*only - location).length());
+        }
+
+        self.segments()
+            .iter()
+            .map(|segment| (geometry::closest_point_on_line(segment, location) - location).length())
+            .min_by(|left, right| left.partial_cmp(right).unwrap_or(Ordering::Equal))
+    }
+
+    pub fn translate(&mut self, displacement: Vector) {
+        for vertex in &mut self.vertices {
+            *vertex += displacement;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(closed: bool) -> Polyline {
+        Polyline::new(
+            vec![
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 10.0),
+                Point::new(0.0, 10.0),
+            ],
+            closed,
+        )
+    }
+
+    #[test]
+    fn open_polylines_join_consecutive_vertices() {
+        let got = square(false).segments();
+
+        assert_eq!(got.len(), 3);
+        assert_eq!(
+            got[0],
+            Line::new(Point::new(0.0, 0.0), Point::new(10.0, 0.0))
+        );
+        assert_eq!(
+            got[2],
+            Line::new(Point::new(10.0, 10.0), Point::new(0.0, 10.0))
+        );
+    }
+
+    #[test]
+    fn closed_polylines_go_back_to_the_start() {
+        let got = square(true).segments();
+
+        assert_eq!(got.len(), 4);
+        assert_eq!(
+            got[3],
+            Line::new(Point::new(0.0, 10.0), Point::new(0.0, 0.0))
+        );
+        assert_eq!(square(true).length(), 40.0);
+    }
+
+    #[test]
+    fn a_closed_line_is_still_just_a_line() {
+        let polyline = Polyline::new(vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0)], true);
+
+        assert_eq!(polyline.segments().len(), 1);
+    }
+
+    #[test]
+    fn distance_is_measured_to_the_closest_segment() {
+        let polyline = square(false);
+
+        assert_eq!(polyline.distance_to(Point::new(5.0, 2.0)), Some(2.0));
+        // the open side is a long way from everything but the closing segment
+        assert!(polyline.distance_to(Point::new(-1.0, 5.0)).unwrap() > 5.0);
+        assert_eq!(square(true).distance_to(Point::new(-1.0, 5.0)), Some(1.0));
+    }
+
+    #[test]
+    fn extreme_coordinates_dont_panic() {
+        // the first segment is infinitely long, so its distance is NaN
+        let polyline = Polyline::new(
+            vec![
+                Point::new(-f64::MAX, 0.0),
+                Point::new(f64::MAX, 0.0),
+                Point::new(f64::MAX, 1.0),
+            ],
+            false,
+        );
+
+        assert!(polyline.distance_to(Point::new(0.0, 0.0)).is_some());
+    }
+
+    #[test]
+    fn bounds_and_translation() {
+        let mut polyline = square(false);
+
+        polyline.translate(Vector::new(1.0, 2.0));
+
+        assert_eq!(polyline.vertices[0], Point::new(1.0, 2.0));
+        assert_eq!(
+            polyline.bounding_box(),
+            Some(BoundingBox::new(
+                Point::new(1.0, 2.0),
+                Point::new(11.0, 12.0)
+            ))
+        );
+        assert_eq!(Polyline::new(Vec::new(), false).bounding_box(), None);
+    }
+}
@@ -0,0 +1,329 @@
+//! A summary of the selection, shown by the "Properties" context menu item.
+
+use crate::shapes::{Shape, ShapeGeometry, ShapePart};
+use arcs::{
+    components::{DrawingObject, Geometry, Name, Selected},
+    specs::prelude::*,
+    Point,
+};
+
+/// One row of the properties panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: &'static str,
+    pub value: String,
+}
+
+impl Property {
+    fn new(name: &'static str, value: impl ToString) -> Property {
+        Property {
+            name,
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Describe whatever is selected.
+///
+/// A single object gets its type, layer and dimensions, while a bigger
+/// selection is broken down by type. Nothing selected gives an empty list.
+pub fn describe_selection(world: &World) -> Vec<Property> {
+    let selected = world.read_storage::<Selected>();
+    let drawing_objects = world.read_storage::<DrawingObject>();
+    let shapes = world.read_storage::<Shape>();
+    let parts = world.read_storage::<ShapePart>();
+
+    let objects = (&selected, &drawing_objects, !&parts)
+        .join()
+        .map(|(_, drawing_object, _)| Object::Drawing(drawing_object));
+    let shapes = (&selected, &shapes)
+        .join()
+        .map(|(_, shape)| Object::Shape(shape));
+    let objects: Vec<Object> = objects.chain(shapes).collect();
+
+    match objects.as_slice() {
+        [] => Vec::new(),
+        [object] => describe(world, object),
+        objects => summarise(objects),
+    }
+}
+
+/// Something which can be selected.
+enum Object<'a> {
+    Drawing(&'a DrawingObject),
+    Shape(&'a Shape),
+}
+
+impl<'a> Object<'a> {
+    fn layer(&self) -> Entity {
+        match self {
+            Object::Drawing(drawing_object) => drawing_object.layer,
+            Object::Shape(shape) => shape.layer,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Object::Drawing(drawing_object) => type_name(&drawing_object.geometry),
+            Object::Shape(shape) => shape.geometry.type_name(),
+        }
+    }
+}
+
+fn describe(world: &World, object: &Object) -> Vec<Property> {
+    let layer = world
+        .read_storage::<Name>()
+        .get(object.layer())
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+    let mut properties = vec![
+        Property::new("Type", object.type_name()),
+        Property::new("Layer", layer),
+    ];
+
+    match object {
+        Object::Drawing(drawing_object) => {
+            describe_geometry(&drawing_object.geometry, &mut properties)
+        }
+        Object::Shape(shape) => describe_shape(&shape.geometry, &mut properties),
+    }
+
+    properties
+}
+
+fn describe_geometry(geometry: &Geometry, properties: &mut Vec<Property>) {
+    match geometry {
+        Geometry::Point(point) => {
+            properties.push(Property::new("Location", format_point(*point)));
+        }
+        Geometry::Line(line) => {
+            properties.push(Property::new("Start", format_point(line.start)));
+            properties.push(Property::new("End", format_point(line.end)));
+            properties.push(Property::new(
+                "Length",
+                format_number((line.end - line.start).length()),
+            ));
+        }
+        Geometry::Arc(arc) => {
+            properties.push(Property::new("Centre", format_point(arc.centre())));
+            properties.push(Property::new("Radius", format_number(arc.radius())));
+            properties.push(Property::new(
+                "Start Angle",
+                format_number(arc.start_angle().to_degrees()),
+            ));
+            properties.push(Property::new(
+                "Sweep Angle",
+                format_number(arc.sweep_angle().to_degrees()),
+            ));
+        }
+        _ => {}
+    }
+}
+
+fn describe_shape(geometry: &ShapeGeometry, properties: &mut Vec<Property>) {
+    match geometry {
+        ShapeGeometry::Polyline(polyline) => {
+            properties.push(Property::new("Vertices", polyline.vertices.len()));
+            properties.push(Property::new(
+                "Closed",
+                if polyline.closed { "yes" } else { "no" },
+            ));
+            properties.push(Property::new("Length", format_number(polyline.length())));
+        }
+        ShapeGeometry::Ellipse(ellipse) => {
+            let major_radius = ellipse.major_axis.length();
+            let rotation = ellipse.major_axis.y.atan2(ellipse.major_axis.x);
+
+            properties.push(Property::new("Centre", format_point(ellipse.centre)));
+            properties.push(Property::new("Major Radius", format_number(major_radius)));
+            properties.push(Property::new(
+                "Minor Radius",
+                format_number(major_radius * ellipse.ratio),
+            ));
+            properties.push(Property::new(
+                "Rotation",
+                format_number(rotation.to_degrees()),
+            ));
+            if !ellipse.is_closed() {
+                properties.push(Property::new(
+                    "Start Angle",
+                    format_number(ellipse.start_parameter.to_degrees()),
+                ));
+                properties.push(Property::new(
+                    "Sweep Angle",
+                    format_number(ellipse.sweep().to_degrees()),
+                ));
+            }
+        }
+    }
+}
+
+fn summarise(objects: &[Object]) -> Vec<Property> {
+    let mut properties = vec![Property::new("Objects", objects.len())];
+
+    for &name in &[
+        "Point",
+        "Line",
+        "Arc",
+        "Polyline",
+        "Ellipse",
+        "Elliptical Arc",
+    ] {
+        let count = objects
+            .iter()
+            .filter(|object| object.type_name() == name)
+            .count();
+        if count > 0 {
+            properties.push(Property::new(name, count));
+        }
+    }
+
+    properties
+}
+
+fn type_name(geometry: &Geometry) -> &'static str {
+    match geometry {
+        Geometry::Point(_) => "Point",
+        Geometry::Line(_) => "Line",
+        Geometry::Arc(_) => "Arc",
+        _ => "Other",
+    }
+}
+
+fn format_point(point: Point) -> String {
+    format!("{}, {}", format_number(point.x), format_number(point.y))
+}
+
+fn format_number(value: f64) -> String {
+    // 3 decimal places is plenty, without the trailing zeroes
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+
+    if text == "-0" {
+        String::from("0")
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ellipse::Ellipse, polyline::Polyline};
+    use arcs::{components::Layer, primitives::Line, Vector};
+
+    fn add(world: &mut World, layer: Entity, geometry: Geometry) {
+        world
+            .create_entity()
+            .with(DrawingObject { geometry, layer })
+            .with(Selected)
+            .build();
+    }
+
+    fn drawing() -> (World, Entity) {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let layer = Layer::create(world.create_entity(), Name::new("walls"), Layer::default());
+
+        (world, layer)
+    }
+
+    #[test]
+    fn a_single_line_shows_its_dimensions() {
+        let (mut world, layer) = drawing();
+        let line = Line::new(Point::new(0.0, 0.0), Point::new(3.0, 4.0));
+        add(&mut world, layer, Geometry::Line(line));
+
+        let got = describe_selection(&world);
+
+        let should_be = vec![
+            Property::new("Type", "Line"),
+            Property::new("Layer", "walls"),
+            Property::new("Start", "0, 0"),
+            Property::new("End", "3, 4"),
+            Property::new("Length", "5"),
+        ];
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn bigger_selections_are_counted_by_type() {
+        let (mut world, layer) = drawing();
+        add(&mut world, layer, Geometry::Point(Point::new(1.0, 2.0)));
+        add(&mut world, layer, Geometry::Point(Point::new(1.5, 2.0)));
+        let line = Line::new(Point::new(0.0, 0.0), Point::new(3.0, 4.0));
+        add(&mut world, layer, Geometry::Line(line));
+
+        let got = describe_selection(&world);
+
+        let should_be = vec![
+            Property::new("Objects", 3),
+            Property::new("Point", 2),
+            Property::new("Line", 1),
+        ];
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn polylines_show_their_vertices_and_length() {
+        let (mut world, layer) = drawing();
+        let vertices = vec![
+            Point::new(0.0, 0.0),
+            Point::new(3.0, 4.0),
+            Point::new(3.0, 0.0),
+        ];
+        world
+            .create_entity()
+            .with(Shape {
+                geometry: ShapeGeometry::Polyline(Polyline::new(vertices, true)),
+                layer,
+            })
+            .with(Selected)
+            .build();
+
+        let got = describe_selection(&world);
+
+        let should_be = vec![
+            Property::new("Type", "Polyline"),
+            Property::new("Layer", "walls"),
+            Property::new("Vertices", 3),
+            Property::new("Closed", "yes"),
+            Property::new("Length", 12),
+        ];
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn ellipses_show_both_radii() {
+        let (mut world, layer) = drawing();
+        let ellipse = Ellipse::new(Point::new(1.0, 2.0), Vector::new(0.0, 4.0), 0.5);
+        world
+            .create_entity()
+            .with(Shape {
+                geometry: ShapeGeometry::Ellipse(ellipse),
+                layer,
+            })
+            .with(Selected)
+            .build();
+
+        let got = describe_selection(&world);
+
+        let should_be = vec![
+            Property::new("Type", "Ellipse"),
+            Property::new("Layer", "walls"),
+            Property::new("Centre", "1, 2"),
+            Property::new("Major Radius", 4),
+            Property::new("Minor Radius", 2),
+            Property::new("Rotation", 90),
+        ];
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn numbers_are_rounded_sensibly() {
+        assert_eq!(format_number(1.0 / 3.0), "0.333");
+        assert_eq!(format_number(2.5), "2.5");
+        assert_eq!(format_number(-0.0001), "0");
+        assert_eq!(format_number(10.0), "10");
+    }
+}
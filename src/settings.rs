@@ -0,0 +1,87 @@
+//! User preferences which change how the editor behaves.
+
+use crate::{snaps::ObjectSnaps, tracking::PolarTracking};
+use arcs::specs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The `localStorage` key the [`Settings`] are saved under.
+pub const SETTINGS_KEY: &str = "arcs-wasm-experiment.settings";
+
+/// What happens when the user drags across an empty part of the drawing
+/// while selecting.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptySpaceDrag {
+    /// Draw a rubber-band rectangle and select what it covers.
+    SelectRegion,
+    /// Move the viewport around.
+    Pan,
+}
+
+impl Default for EmptySpaceDrag {
+    fn default() -> EmptySpaceDrag {
+        EmptySpaceDrag::SelectRegion
+    }
+}
+
+/// A resource containing the user's preferences.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub empty_space_drag: EmptySpaceDrag,
+    /// Which object snaps are turned on.
+    pub object_snaps: ObjectSnaps,
+    /// Keep new lines horizontal or vertical.
+    pub ortho: bool,
+    pub polar_tracking: PolarTracking,
+}
+
+impl Settings {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Settings are always serializable")
+    }
+
+    /// Parse previously saved settings, using the defaults for anything
+    /// which is missing.
+    pub fn from_json(json: &str) -> Result<Settings, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Get a copy of the current [`Settings`], falling back to the defaults if
+/// they haven't been added to the [`World`].
+pub fn current(world: &World) -> Settings {
+    world
+        .try_fetch::<Settings>()
+        .map(|settings| settings.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_the_defaults() {
+        let got = Settings::from_json("{}").unwrap();
+
+        assert_eq!(got, Settings::default());
+    }
+
+    #[test]
+    fn round_trip_through_json() {
+        let settings = Settings {
+            empty_space_drag: EmptySpaceDrag::Pan,
+            object_snaps: ObjectSnaps {
+                nearest: true,
+                ..Default::default()
+            },
+            ortho: true,
+            polar_tracking: PolarTracking::Every30Degrees,
+        };
+
+        let got = Settings::from_json(&settings.to_json()).unwrap();
+
+        assert_eq!(got, settings);
+    }
+}
@@ -0,0 +1,368 @@
+//! Objects whose geometry `arcs` can't represent directly.
+//!
+//! A [`Shape`] is a single object as far as the rest of the application is
+//! concerned. It gets picked, snapped to, moved, saved and undone as a whole.
+//! The renderer only knows how to draw [`DrawingObject`]s though, so
+//! [`SyncShapes`] keeps a set of [`ShapePart`] lines tracing each shape's
+//! outline. Parts are purely for show and are never picked, snapped to or
+//! saved themselves.
+
+use crate::{
+    ellipse::Ellipse,
+    geometry::{self, BoundingBox},
+    polyline::Polyline,
+};
+use arcs::{
+    components::{DrawingObject, Geometry, LineStyle, Selected},
+    primitives::Line,
+    specs::{prelude::*, storage::ComponentEvent},
+    Point, Vector,
+};
+
+/// The geometry a [`Shape`] can have.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeGeometry {
+    Polyline(Polyline),
+    Ellipse(Ellipse),
+}
+
+impl ShapeGeometry {
+    /// The lines used to draw the shape.
+    pub fn outline(&self) -> Vec<Line> {
+        match self {
+            ShapeGeometry::Polyline(polyline) => polyline.segments(),
+            ShapeGeometry::Ellipse(ellipse) => ellipse.approximate(),
+        }
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        match self {
+            ShapeGeometry::Polyline(polyline) => polyline.bounding_box(),
+            ShapeGeometry::Ellipse(ellipse) => Some(ellipse.bounding_box()),
+        }
+    }
+
+    /// How far `location` is from the closest point on the shape.
+    pub fn distance_to(&self, location: Point) -> Option<f64> {
+        match self {
+            ShapeGeometry::Polyline(polyline) => polyline.distance_to(location),
+            ShapeGeometry::Ellipse(ellipse) => Some(ellipse.distance_to(location)),
+        }
+    }
+
+    /// Does any part of the shape touch a region?
+    pub fn intersects_box(&self, region: &BoundingBox) -> bool {
+        self.outline()
+            .into_iter()
+            .any(|line| geometry::intersects_box(&Geometry::Line(line), region))
+    }
+
+    /// Does a region completely enclose the shape?
+    pub fn inside_box(&self, region: &BoundingBox) -> bool {
+        self.bounding_box().map_or(false, |bounds| {
+            region.min.x <= bounds.min.x
+                && region.min.y <= bounds.min.y
+                && bounds.max.x <= region.max.x
+                && bounds.max.y <= region.max.y
+        })
+    }
+
+    pub fn translate(&mut self, displacement: Vector) {
+        match self {
+            ShapeGeometry::Polyline(polyline) => polyline.translate(displacement),
+            ShapeGeometry::Ellipse(ellipse) => ellipse.translate(displacement),
+        }
+    }
+
+    /// The geometry object snaps should look at.
+    ///
+    /// Snapping to the lines an ellipse is drawn with would give dozens of
+    /// meaningless endpoints, so only the ends of an elliptical arc are used.
+    pub fn snap_geometry(&self) -> Vec<Geometry> {
+        match self {
+            ShapeGeometry::Polyline(polyline) => polyline
+                .segments()
+                .into_iter()
+                .map(Geometry::Line)
+                .collect(),
+            ShapeGeometry::Ellipse(ellipse) if ellipse.is_closed() => Vec::new(),
+            ShapeGeometry::Ellipse(ellipse) => vec![
+                Geometry::Point(ellipse.start_point()),
+                Geometry::Point(ellipse.end_point()),
+            ],
+        }
+    }
+
+    /// What this kind of shape is called, for showing to the user.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ShapeGeometry::Polyline(_) => "Polyline",
+            ShapeGeometry::Ellipse(ellipse) if ellipse.is_closed() => "Ellipse",
+            ShapeGeometry::Ellipse(_) => "Elliptical Arc",
+        }
+    }
+}
+
+/// An object on the drawing with [`ShapeGeometry`], the equivalent of a
+/// [`DrawingObject`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    pub geometry: ShapeGeometry,
+    pub layer: Entity,
+}
+
+impl Component for Shape {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+/// Marks a [`DrawingObject`] which is only there to draw part of a [`Shape`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapePart {
+    pub shape: Entity,
+}
+
+impl Component for ShapePart {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Keeps each [`Shape`]'s [`ShapePart`]s up to date, throwing away the parts
+/// of shapes which have been deleted.
+///
+/// Parts are also given their shape's [`Selected`] and [`LineStyle`] so they
+/// are drawn the same way.
+#[derive(Debug, Default)]
+pub struct SyncShapes {
+    changes: Option<ReaderId<ComponentEvent>>,
+}
+
+impl<'a> System<'a> for SyncShapes {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Shape>,
+        WriteStorage<'a, ShapePart>,
+        WriteStorage<'a, DrawingObject>,
+        WriteStorage<'a, Selected>,
+        WriteStorage<'a, LineStyle>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, shapes, mut parts, mut drawing_objects, mut selected, mut line_styles) =
+            data;
+        let mut changed = BitSet::new();
+
+        let changes = self
+            .changes
+            .as_mut()
+            .expect("The system should have been set up");
+
+        for event in shapes.channel().read(changes) {
+            match *event {
+                ComponentEvent::Inserted(id)
+                | ComponentEvent::Modified(id)
+                | ComponentEvent::Removed(id) => {
+                    changed.add(id);
+                }
+            }
+        }
+
+        // out with the old
+        for (entity, part) in (&entities, &parts).join() {
+            let orphaned = !entities.is_alive(part.shape) || !shapes.contains(part.shape);
+
+            if orphaned || changed.contains(part.shape.id()) {
+                if let Err(e) = entities.delete(entity) {
+                    log::warn!("Unable to delete part of a shape: {}", e);
+                }
+            }
+        }
+
+        // in with the new
+        let mut new_parts = Vec::new();
+
+        for (entity, shape, _) in (&entities, &shapes, &changed).join() {
+            for line in shape.geometry.outline() {
+                let part = entities
+                    .build_entity()
+                    .with(
+                        DrawingObject {
+                            geometry: Geometry::Line(line),
+                            layer: shape.layer,
+                        },
+                        &mut drawing_objects,
+                    )
+                    .with(ShapePart { shape: entity }, &mut parts)
+                    .build();
+                new_parts.push((part, entity));
+            }
+        }
+
+        // parts which were only just built may not be joined over until the
+        // world is maintained, so they're added separately
+        let mut pairs: Vec<(Entity, Entity)> = (&entities, &parts)
+            .join()
+            .map(|(entity, part)| (entity, part.shape))
+            .collect();
+        pairs.extend(new_parts);
+
+        for (part, shape) in pairs {
+            if selected.contains(shape) {
+                if !selected.contains(part) {
+                    let _ = selected.insert(part, Selected);
+                }
+            } else {
+                selected.remove(part);
+            }
+
+            match line_styles.get(shape).cloned() {
+                Some(style) => {
+                    let _ = line_styles.insert(part, style);
+                }
+                None => {
+                    line_styles.remove(part);
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.changes = Some(WriteStorage::<Shape>::fetch(world).register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arcs::{components::Layer, euclid::Angle};
+
+    fn polyline(vertices: &[(f64, f64)], closed: bool) -> ShapeGeometry {
+        let vertices = vertices.iter().map(|&(x, y)| Point::new(x, y)).collect();
+        ShapeGeometry::Polyline(Polyline::new(vertices, closed))
+    }
+
+    fn setup() -> (World, SyncShapes, Entity) {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let mut system = SyncShapes::default();
+        System::setup(&mut system, &mut world);
+        let layer = world.create_entity().with(Layer::default()).build();
+
+        (world, system, layer)
+    }
+
+    fn parts_of(world: &World, shape: Entity) -> Vec<Line> {
+        let parts = world.read_storage::<ShapePart>();
+        let drawing_objects = world.read_storage::<DrawingObject>();
+
+        (&parts, &drawing_objects)
+            .join()
+            .filter(|(part, _)| part.shape == shape)
+            .filter_map(|(_, drawing_object)| match &drawing_object.geometry {
+                Geometry::Line(line) => Some(Line::new(line.start, line.end)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn run(world: &mut World, system: &mut SyncShapes) {
+        system.run_now(world);
+        world.maintain();
+    }
+
+    #[test]
+    fn shapes_are_drawn_with_their_outline() {
+        let (mut world, mut system, layer) = setup();
+        let geometry = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], true);
+        let shape = world
+            .create_entity()
+            .with(Shape {
+                geometry: geometry.clone(),
+                layer,
+            })
+            .build();
+
+        run(&mut world, &mut system);
+
+        assert_eq!(parts_of(&world, shape), geometry.outline());
+    }
+
+    #[test]
+    fn changing_a_shape_replaces_its_parts() {
+        let (mut world, mut system, layer) = setup();
+        let shape = world
+            .create_entity()
+            .with(Shape {
+                geometry: polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], false),
+                layer,
+            })
+            .build();
+        run(&mut world, &mut system);
+
+        world
+            .write_storage::<Shape>()
+            .get_mut(shape)
+            .unwrap()
+            .geometry
+            .translate(Vector::new(0.0, 5.0));
+        run(&mut world, &mut system);
+
+        assert_eq!(
+            parts_of(&world, shape),
+            vec![
+                Line::new(Point::new(0.0, 5.0), Point::new(10.0, 5.0)),
+                Line::new(Point::new(10.0, 5.0), Point::new(10.0, 15.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn deleting_a_shape_deletes_its_parts() {
+        let (mut world, mut system, layer) = setup();
+        let shape = world
+            .create_entity()
+            .with(Shape {
+                geometry: polyline(&[(0.0, 0.0), (10.0, 0.0)], false),
+                layer,
+            })
+            .build();
+        run(&mut world, &mut system);
+        assert_eq!(world.read_storage::<ShapePart>().join().count(), 1);
+
+        world.delete_entity(shape).unwrap();
+        run(&mut world, &mut system);
+
+        assert_eq!(world.read_storage::<ShapePart>().join().count(), 0);
+        assert_eq!(world.read_storage::<DrawingObject>().join().count(), 0);
+    }
+
+    #[test]
+    fn only_the_ends_of_an_elliptical_arc_are_snapped_to() {
+        let ellipse = Ellipse::new(Point::zero(), Vector::new(4.0, 0.0), 0.5);
+        let arc = ellipse.with_parameters(Angle::zero(), Angle::frac_pi_2());
+
+        assert!(ShapeGeometry::Ellipse(ellipse).snap_geometry().is_empty());
+        let got = ShapeGeometry::Ellipse(arc).snap_geometry();
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[0], Geometry::Point(Point::new(4.0, 0.0)));
+    }
+
+    #[test]
+    fn parts_are_selected_along_with_their_shape() {
+        let (mut world, mut system, layer) = setup();
+        let shape = world
+            .create_entity()
+            .with(Shape {
+                geometry: polyline(&[(0.0, 0.0), (10.0, 0.0)], false),
+                layer,
+            })
+            .with(Selected)
+            .build();
+
+        run(&mut world, &mut system);
+        assert_eq!(world.read_storage::<Selected>().join().count(), 2);
+
+        world.write_storage::<Selected>().remove(shape);
+        run(&mut world, &mut system);
+        assert_eq!(world.read_storage::<Selected>().join().count(), 0);
+    }
+}
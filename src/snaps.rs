@@ -0,0 +1,565 @@
+//! Object snaps, which pull the cursor onto interesting points (end points,
+//! midpoints, centres, etc.) of nearby objects.
+//!
+//! A [`State`](crate::modes::State) calls [`update()`] as the mouse moves to
+//! find the best [`SnapPoint`] and show its marker on the system layer. The
+//! [`ApplyObjectSnap`] system then moves the [`CursorPosition`] onto it after
+//! the grid [`arcs::systems::snapper::Snapper`] has run, so the objects
+//! created by the dispatcher end up in the right spot.
+
+use crate::{components::Preview, geometry, settings, shapes::Shape, spatial_index::SpatialIndex};
+use arcs::{
+    components::{
+        layer::LayerType, CursorPosition, Dimension, DrawingObject, Geometry, Layer, LineStyle,
+        Selected,
+    },
+    euclid::Angle,
+    piet::Color,
+    primitives::{Arc, Line},
+    specs::prelude::*,
+    Point, Vector,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// How close (in pixels) the cursor needs to be before it snaps to something.
+pub const SNAP_TOLERANCE: f64 = 8.0;
+/// The width of a snap marker, in pixels.
+const MARKER_SIZE: f64 = 10.0;
+
+/// The different types of object snap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SnapKind {
+    /// The ends of lines and arcs, and points.
+    Endpoint,
+    Midpoint,
+    /// The centre of an arc.
+    Centre,
+    /// Where two lines, or a line and an arc, cross.
+    Intersection,
+    /// The closest point on an object, used when nothing better is around.
+    Nearest,
+}
+
+impl SnapKind {
+    pub const ALL: [SnapKind; 5] = [
+        SnapKind::Endpoint,
+        SnapKind::Midpoint,
+        SnapKind::Centre,
+        SnapKind::Intersection,
+        SnapKind::Nearest,
+    ];
+
+    /// A short name to show in the UI.
+    pub fn label(self) -> &'static str {
+        match self {
+            SnapKind::Endpoint => "End",
+            SnapKind::Midpoint => "Mid",
+            SnapKind::Centre => "Cen",
+            SnapKind::Intersection => "Int",
+            SnapKind::Nearest => "Nea",
+        }
+    }
+}
+
+/// Which [`SnapKind`]s are turned on.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectSnaps {
+    pub endpoint: bool,
+    pub midpoint: bool,
+    pub centre: bool,
+    pub intersection: bool,
+    pub nearest: bool,
+}
+
+impl ObjectSnaps {
+    pub fn is_enabled(&self, kind: SnapKind) -> bool {
+        match kind {
+            SnapKind::Endpoint => self.endpoint,
+            SnapKind::Midpoint => self.midpoint,
+            SnapKind::Centre => self.centre,
+            SnapKind::Intersection => self.intersection,
+            SnapKind::Nearest => self.nearest,
+        }
+    }
+
+    pub fn toggle(&mut self, kind: SnapKind) {
+        let flag = match kind {
+            SnapKind::Endpoint => &mut self.endpoint,
+            SnapKind::Midpoint => &mut self.midpoint,
+            SnapKind::Centre => &mut self.centre,
+            SnapKind::Intersection => &mut self.intersection,
+            SnapKind::Nearest => &mut self.nearest,
+        };
+        *flag = !*flag;
+    }
+}
+
+impl Default for ObjectSnaps {
+    fn default() -> ObjectSnaps {
+        ObjectSnaps {
+            endpoint: true,
+            midpoint: true,
+            centre: true,
+            intersection: true,
+            // snapping to anything and everything gets annoying
+            nearest: false,
+        }
+    }
+}
+
+/// A location the cursor can snap to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SnapPoint {
+    pub location: Point,
+    pub kind: SnapKind,
+}
+
+/// Find the best [`SnapPoint`] within `radius` of a location.
+///
+/// The closest endpoint, midpoint, centre or intersection wins, with
+/// [`SnapKind::Nearest`] only used as a last resort. Selected objects are
+/// ignored because they are usually the thing being drawn.
+pub fn find_snap(
+    world: &World,
+    location: Point,
+    radius: f64,
+    enabled: &ObjectSnaps,
+) -> Option<SnapPoint> {
+    let nearby = nearby_geometry(world, location, radius);
+
+    let mut candidates: Vec<SnapPoint> = nearby
+        .iter()
+        .flat_map(|geometry| snap_points(geometry, location))
+        .collect();
+
+    if enabled.intersection {
+        for (i, first) in nearby.iter().enumerate() {
+            for second in &nearby[i + 1..] {
+                candidates.extend(intersections(first, second).into_iter().map(|location| {
+                    SnapPoint {
+                        location,
+                        kind: SnapKind::Intersection,
+                    }
+                }));
+            }
+        }
+    }
+
+    let distance = |snap: &SnapPoint| (snap.location - location).length();
+    let closest = |snaps: Vec<&SnapPoint>| {
+        snaps
+            .into_iter()
+            .min_by(|left, right| {
+                distance(left)
+                    .partial_cmp(&distance(right))
+                    .unwrap_or(Ordering::Equal)
+            })
+            .copied()
+    };
+
+    let (nearest, others): (Vec<_>, Vec<_>) = candidates
+        .iter()
+        .filter(|snap| enabled.is_enabled(snap.kind) && distance(snap) <= radius)
+        .partition(|snap| snap.kind == SnapKind::Nearest);
+
+    closest(others).or_else(|| closest(nearest))
+}
+
+/// The geometry of the unselected objects on visible, non-system layers near
+/// a location, ignoring previews of things still being drawn.
+fn nearby_geometry(world: &World, location: Point, radius: f64) -> Vec<Geometry> {
+    let index = match world.try_fetch::<SpatialIndex>() {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+    let drawing_objects = world.read_storage::<DrawingObject>();
+    let shapes = world.read_storage::<Shape>();
+    let layers = world.read_storage::<Layer>();
+    let selected = world.read_storage::<Selected>();
+    let previews = world.read_storage::<Preview>();
+
+    index
+        .query_point(location, radius)
+        .into_iter()
+        .filter(|&entity| !selected.contains(entity) && !previews.contains(entity))
+        .filter_map(|entity| match drawing_objects.get(entity) {
+            Some(d) => Some((d.layer, vec![d.geometry.clone()])),
+            None => shapes
+                .get(entity)
+                .map(|shape| (shape.layer, shape.geometry.snap_geometry())),
+        })
+        .filter(|(layer, _)| {
+            layers.get(*layer).map_or(true, |layer| {
+                layer.visible && !matches!(layer.layer_type, LayerType::System)
+            })
+        })
+        .flat_map(|(_, geometry)| geometry)
+        .collect()
+}
+
+/// The snap points on a single piece of geometry.
+fn snap_points(geometry: &Geometry, location: Point) -> Vec<SnapPoint> {
+    let snap = |location, kind| SnapPoint { location, kind };
+
+    match geometry {
+        Geometry::Point(point) => vec![snap(*point, SnapKind::Endpoint)],
+        Geometry::Line(line) => vec![
+            snap(line.start, SnapKind::Endpoint),
+            snap(line.end, SnapKind::Endpoint),
+            snap(line.start.lerp(line.end, 0.5), SnapKind::Midpoint),
+            snap(
+                geometry::closest_point_on_line(line, location),
+                SnapKind::Nearest,
+            ),
+        ],
+        Geometry::Arc(arc) => {
+            let mut snaps = vec![snap(arc.centre(), SnapKind::Centre)];
+
+            if arc.sweep_angle().radians.abs() < Angle::<f64>::two_pi().radians {
+                let middle = arc.start_angle() + arc.sweep_angle() / 2.0;
+                snaps.push(snap(geometry::arc_start(arc), SnapKind::Endpoint));
+                snaps.push(snap(geometry::arc_end(arc), SnapKind::Endpoint));
+                snaps.push(snap(
+                    geometry::point_on_arc(arc, middle),
+                    SnapKind::Midpoint,
+                ));
+            }
+
+            let angle = (location - arc.centre()).angle_from_x_axis();
+            if location != arc.centre() && geometry::arc_contains_angle(arc, angle) {
+                snaps.push(snap(geometry::point_on_arc(arc, angle), SnapKind::Nearest));
+            }
+
+            snaps
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn intersections(first: &Geometry, second: &Geometry) -> Vec<Point> {
+    match (first, second) {
+        (Geometry::Line(a), Geometry::Line(b)) => {
+            geometry::line_intersection(a, b).into_iter().collect()
+        }
+        (Geometry::Line(line), Geometry::Arc(arc)) | (Geometry::Arc(arc), Geometry::Line(line)) => {
+            geometry::line_arc_intersections(line, arc)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// A resource holding the active snap and the entities used to draw its
+/// marker.
+#[derive(Debug, Default)]
+pub struct ObjectSnap {
+    active: Option<SnapPoint>,
+    marker: Vec<Entity>,
+}
+
+impl ObjectSnap {
+    pub fn active(&self) -> Option<SnapPoint> {
+        self.active
+    }
+}
+
+/// Look for something to snap to near `location`, updating the marker if
+/// the snap changed.
+///
+/// Returns `true` when the canvas needs to be redrawn.
+pub fn update(
+    world: &mut World,
+    system_layer: Entity,
+    location: Point,
+    pixels_per_drawing_unit: f64,
+) -> bool {
+    let enabled = settings::current(world).object_snaps;
+    let snap = find_snap(
+        world,
+        location,
+        SNAP_TOLERANCE / pixels_per_drawing_unit,
+        &enabled,
+    );
+
+    let previous = world
+        .entry::<ObjectSnap>()
+        .or_insert_with(ObjectSnap::default)
+        .active;
+    if previous == snap {
+        return false;
+    }
+
+    clear(world);
+
+    let marker = match snap {
+        Some(snap) => {
+            let size = MARKER_SIZE / pixels_per_drawing_unit;
+            marker_geometry(snap, size)
+                .into_iter()
+                .map(|geometry| {
+                    world
+                        .create_entity()
+                        .with(DrawingObject {
+                            geometry,
+                            layer: system_layer,
+                        })
+                        .with(LineStyle {
+                            stroke: Color::rgb8(0xff, 0x8c, 0x00),
+                            width: Dimension::Pixels(1.5),
+                        })
+                        .build()
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    let mut object_snap = world.write_resource::<ObjectSnap>();
+    object_snap.active = snap;
+    object_snap.marker = marker;
+
+    true
+}
+
+/// Forget the active snap and remove its marker.
+pub fn clear(world: &mut World) {
+    let marker = match world.try_fetch_mut::<ObjectSnap>() {
+        Some(mut object_snap) => {
+            object_snap.active = None;
+            std::mem::take(&mut object_snap.marker)
+        }
+        None => return,
+    };
+
+    for entity in marker {
+        if let Err(e) = world.delete_entity(entity) {
+            log::warn!("Unable to delete the snap marker: {}", e);
+        }
+    }
+}
+
+/// The glyph drawn around a [`SnapPoint`]: a square for end points, a
+/// triangle for midpoints, a circle for centres, a cross for intersections
+/// and an hourglass for the nearest point.
+fn marker_geometry(snap: SnapPoint, size: f64) -> Vec<Geometry> {
+    let half = size / 2.0;
+    let at = |dx: f64, dy: f64| snap.location + Vector::new(dx * half, dy * half);
+    let line = |from: Point, to: Point| Geometry::Line(Line::new(from, to));
+
+    let top_left = at(-1.0, 1.0);
+    let top_right = at(1.0, 1.0);
+    let bottom_left = at(-1.0, -1.0);
+    let bottom_right = at(1.0, -1.0);
+
+    match snap.kind {
+        SnapKind::Endpoint => vec![
+            line(bottom_left, bottom_right),
+            line(bottom_right, top_right),
+            line(top_right, top_left),
+            line(top_left, bottom_left),
+        ],
+        SnapKind::Midpoint => {
+            let apex = at(0.0, 1.0);
+            vec![
+                line(bottom_left, bottom_right),
+                line(bottom_right, apex),
+                line(apex, bottom_left),
+            ]
+        }
+        SnapKind::Centre => vec![Geometry::Arc(Arc::from_centre_radius(
+            snap.location,
+            half,
+            Angle::zero(),
+            Angle::two_pi(),
+        ))],
+        SnapKind::Intersection => vec![line(bottom_left, top_right), line(top_left, bottom_right)],
+        SnapKind::Nearest => vec![
+            line(bottom_left, top_right),
+            line(top_left, bottom_right),
+            line(top_left, top_right),
+            line(bottom_left, bottom_right),
+        ],
+    }
+}
+
+/// Moves the [`CursorPosition`] onto the active [`SnapPoint`].
+///
+/// This needs to run after the grid snapper so object snaps take precedence.
+#[derive(Debug, Default)]
+pub struct ApplyObjectSnap;
+
+impl<'a> System<'a> for ApplyObjectSnap {
+    type SystemData = (Read<'a, ObjectSnap>, Write<'a, CursorPosition>);
+
+    fn run(&mut self, (object_snap, mut cursor_position): Self::SystemData) {
+        if let Some(snap) = object_snap.active {
+            cursor_position.location = snap.location;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{polyline::Polyline, shapes::ShapeGeometry};
+
+    fn world_with(geometries: Vec<Geometry>) -> World {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let layer = Layer::create(
+            world.create_entity(),
+            arcs::components::Name::new("default"),
+            Layer::default(),
+        );
+        let mut index = SpatialIndex::default();
+
+        for geometry in geometries {
+            let bounds = geometry::bounding_box(&geometry).unwrap();
+            let entity = world
+                .create_entity()
+                .with(DrawingObject { geometry, layer })
+                .build();
+            index.insert(entity, bounds);
+        }
+
+        world.insert(index);
+        world
+    }
+
+    fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> Geometry {
+        Geometry::Line(Line::new(Point::new(x1, y1), Point::new(x2, y2)))
+    }
+
+    #[test]
+    fn snap_to_the_end_of_a_line() {
+        let world = world_with(vec![line(0.0, 0.0, 10.0, 0.0)]);
+
+        let got = find_snap(&world, Point::new(9.0, 1.0), 2.0, &ObjectSnaps::default());
+
+        assert_eq!(
+            got,
+            Some(SnapPoint {
+                location: Point::new(10.0, 0.0),
+                kind: SnapKind::Endpoint
+            })
+        );
+    }
+
+    #[test]
+    fn snap_to_an_intersection() {
+        let world = world_with(vec![line(0.0, 0.0, 10.0, 10.0), line(0.0, 4.0, 12.0, 4.0)]);
+
+        let got = find_snap(&world, Point::new(4.2, 4.3), 2.0, &ObjectSnaps::default());
+
+        assert_eq!(
+            got,
+            Some(SnapPoint {
+                location: Point::new(4.0, 4.0),
+                kind: SnapKind::Intersection
+            })
+        );
+    }
+
+    #[test]
+    fn previews_are_never_snapped_to() {
+        let world = world_with(vec![line(0.0, 0.0, 10.0, 0.0)]);
+        let lines: Vec<Entity> = (&world.entities(), &world.read_storage::<DrawingObject>())
+            .join()
+            .map(|(entity, _)| entity)
+            .collect();
+        for line in lines {
+            let _ = world.write_storage().insert(line, Preview);
+        }
+
+        let got = find_snap(&world, Point::new(9.0, 1.0), 2.0, &ObjectSnaps::default());
+
+        assert_eq!(got, None);
+    }
+
+    #[test]
+    fn snap_to_each_segment_of_a_polyline() {
+        let mut world = world_with(Vec::new());
+        let layer = (&world.entities(), &world.read_storage::<Layer>())
+            .join()
+            .map(|(entity, _)| entity)
+            .next()
+            .unwrap();
+        let vertices = vec![
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(10.0, 10.0),
+        ];
+        let geometry = ShapeGeometry::Polyline(Polyline::new(vertices, false));
+        let bounds = geometry.bounding_box().unwrap();
+        let shape = world
+            .create_entity()
+            .with(Shape { geometry, layer })
+            .build();
+        world.write_resource::<SpatialIndex>().insert(shape, bounds);
+
+        let got = find_snap(&world, Point::new(9.0, 4.5), 2.0, &ObjectSnaps::default());
+
+        assert_eq!(
+            got,
+            Some(SnapPoint {
+                location: Point::new(10.0, 5.0),
+                kind: SnapKind::Midpoint
+            })
+        );
+    }
+
+    #[test]
+    fn disabled_snaps_are_ignored() {
+        let world = world_with(vec![line(0.0, 0.0, 10.0, 0.0)]);
+        let enabled = ObjectSnaps {
+            midpoint: false,
+            ..Default::default()
+        };
+
+        let got = find_snap(&world, Point::new(5.0, 1.0), 2.0, &enabled);
+
+        assert_eq!(got, None);
+    }
+
+    #[test]
+    fn nearest_is_only_used_when_nothing_else_is_close() {
+        let world = world_with(vec![Geometry::Arc(Arc::from_centre_radius(
+            Point::new(0.0, 0.0),
+            10.0,
+            Angle::zero(),
+            Angle::pi(),
+        ))]);
+        let enabled = ObjectSnaps {
+            nearest: true,
+            ..Default::default()
+        };
+
+        let near_the_top = find_snap(&world, Point::new(0.5, 11.0), 2.0, &enabled).unwrap();
+        let somewhere_else = find_snap(&world, Point::new(7.0, 7.5), 2.0, &enabled).unwrap();
+
+        assert_eq!(near_the_top.kind, SnapKind::Midpoint);
+        assert_eq!(somewhere_else.kind, SnapKind::Nearest);
+        assert!((somewhere_else.location.to_vector().length() - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn the_marker_is_replaced_when_the_snap_changes() {
+        let mut world = world_with(vec![line(0.0, 0.0, 10.0, 0.0)]);
+        let system_layer = world.create_entity().build();
+
+        assert!(update(&mut world, system_layer, Point::new(0.5, 0.0), 1.0));
+        assert!(!update(&mut world, system_layer, Point::new(0.0, 0.5), 1.0));
+        let square = world.read_resource::<ObjectSnap>().marker.clone();
+        assert_eq!(square.len(), 4);
+
+        assert!(update(
+            &mut world,
+            system_layer,
+            Point::new(50.0, 50.0),
+            1.0
+        ));
+        assert!(world.read_resource::<ObjectSnap>().marker.is_empty());
+        assert!(square.iter().all(|&e| !world.is_alive(e)));
+    }
+}
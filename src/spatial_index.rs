@@ -0,0 +1,479 @@
+//! A coarse spatial index used to find the objects near a point on the
+//! drawing.
+
+use crate::{
+    components::Preview,
+    geometry::{self, BoundingBox},
+    shapes::{Shape, ShapePart},
+};
+use arcs::{
+    components::{layer::LayerType, DrawingObject, Layer},
+    specs::{prelude::*, storage::ComponentEvent, world::Index},
+    Point, Vector,
+};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+/// The width of each bucket, in drawing units.
+const DEFAULT_CELL_SIZE: f64 = 100.0;
+
+/// Objects covering more than this many cells are kept in a separate list
+/// which is checked on every query instead of being added to each bucket.
+const MAX_CELLS_PER_OBJECT: i64 = 256;
+
+type Cell = (i64, i64);
+
+/// A grid of buckets mapping regions of the drawing to the [`DrawingObject`]s
+/// and [`Shape`]s whose bounding boxes overlap them.
+#[derive(Debug)]
+pub struct SpatialIndex {
+    cell_size: f64,
+    buckets: HashMap<Cell, Vec<Entity>>,
+    oversized: Vec<Entity>,
+    bounds: HashMap<Index, (Entity, BoundingBox)>,
+}
+
+impl SpatialIndex {
+    pub fn with_cell_size(cell_size: f64) -> Self {
+        SpatialIndex {
+            cell_size,
+            buckets: HashMap::new(),
+            oversized: Vec::new(),
+            bounds: HashMap::new(),
+        }
+    }
+
+    /// Add an entity to the index, replacing any previous entry.
+    pub fn insert(&mut self, entity: Entity, bounds: BoundingBox) {
+        self.remove(entity.id());
+
+        match self.cells(&bounds) {
+            Some(cells) => {
+                for cell in cells {
+                    self.buckets.entry(cell).or_default().push(entity);
+                }
+            }
+            None => self.oversized.push(entity),
+        }
+
+        self.bounds.insert(entity.id(), (entity, bounds));
+    }
+
+    /// Remove whatever entity was stored at this index.
+    pub fn remove(&mut self, id: Index) {
+        let (entity, bounds) = match self.bounds.remove(&id) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        match self.cells(&bounds) {
+            Some(cells) => {
+                for cell in cells {
+                    if let Some(bucket) = self.buckets.get_mut(&cell) {
+                        bucket.retain(|e| *e != entity);
+
+                        if bucket.is_empty() {
+                            self.buckets.remove(&cell);
+                        }
+                    }
+                }
+            }
+            None => self.oversized.retain(|e| *e != entity),
+        }
+    }
+
+    /// The bounding box an entity was indexed with.
+    pub fn bounds_of(&self, entity: Entity) -> Option<BoundingBox> {
+        self.bounds
+            .get(&entity.id())
+            .filter(|(e, _)| *e == entity)
+            .map(|(_, bounds)| *bounds)
+    }
+
+    /// Find every entity whose bounding box overlaps a region.
+    pub fn query_region(&self, region: BoundingBox) -> Vec<Entity> {
+        let mut seen = HashSet::new();
+        let mut hits = Vec::new();
+
+        let candidates: Vec<Entity> = match self.cells(&region) {
+            Some(cells) => cells
+                .filter_map(|cell| self.buckets.get(&cell))
+                .flatten()
+                .chain(self.oversized.iter())
+                .copied()
+                .collect(),
+            // the region covers too many cells to visit, so check everything
+            None => self.bounds.values().map(|&(entity, _)| entity).collect(),
+        };
+
+        for entity in candidates {
+            if !seen.insert(entity) {
+                continue;
+            }
+
+            match self.bounds_of(entity) {
+                Some(bounds) if overlaps(&bounds, &region) => hits.push(entity),
+                _ => {}
+            }
+        }
+
+        hits
+    }
+
+    /// Find every entity whose bounding box lies within `radius` of a point.
+    pub fn query_point(&self, location: Point, radius: f64) -> Vec<Entity> {
+        let offset = Vector::new(radius, radius);
+        self.query_region(BoundingBox::new(location - offset, location + offset))
+    }
+
+    /// The cells a bounding box touches, or `None` if it is too big to be
+    /// stored in the buckets.
+    fn cells(&self, bounds: &BoundingBox) -> Option<impl Iterator<Item = Cell>> {
+        let to_cell = |value: f64| (value / self.cell_size).floor() as i64;
+        let (min_x, min_y) = (to_cell(bounds.min.x), to_cell(bounds.min.y));
+        let (max_x, max_y) = (to_cell(bounds.max.x), to_cell(bounds.max.y));
+
+        // saturating casts mean huge coordinates could overflow the area
+        let columns = max_x.checked_sub(min_x)?.checked_add(1)?;
+        let rows = max_y.checked_sub(min_y)?.checked_add(1)?;
+
+        if columns.checked_mul(rows)? > MAX_CELLS_PER_OBJECT {
+            return None;
+        }
+
+        Some((min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y))))
+    }
+}
+
+impl Default for SpatialIndex {
+    fn default() -> SpatialIndex {
+        SpatialIndex::with_cell_size(DEFAULT_CELL_SIZE)
+    }
+}
+
+/// Like [`BoundingBox::intersects()`], except boxes which only touch along an
+/// edge (e.g. a horizontal line) still count.
+fn overlaps(a: &BoundingBox, b: &BoundingBox) -> bool {
+    a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+}
+
+/// Find the [`DrawingObject`]s and [`Shape`]s on visible, non-system layers
+/// which are within `radius` of a point, closest first. Previews are never
+/// found.
+pub fn entities_near(world: &World, location: Point, radius: f64) -> Vec<Entity> {
+    let index = world.read_resource::<SpatialIndex>();
+    let drawing_objects = world.read_storage::<DrawingObject>();
+    let shapes = world.read_storage::<Shape>();
+    let layers = world.read_storage::<Layer>();
+    let previews = world.read_storage::<Preview>();
+
+    let mut hits: Vec<(Entity, f64)> = index
+        .query_point(location, radius)
+        .into_iter()
+        .filter(|&entity| !previews.contains(entity))
+        .filter_map(|entity| {
+            let (layer, distance) = match drawing_objects.get(entity) {
+                Some(d) => (d.layer, geometry::distance_to(&d.geometry, location)),
+                None => {
+                    let shape = shapes.get(entity)?;
+                    (shape.layer, shape.geometry.distance_to(location))
+                }
+            };
+
+            if !selectable(&layers, layer) {
+                return None;
+            }
+
+            distance
+                .filter(|distance| *distance <= radius)
+                .map(|distance| (entity, distance))
+        })
+        .collect();
+
+    // extreme coordinates can give a NaN distance, which mustn't panic
+    hits.sort_by(|(_, left), (_, right)| left.partial_cmp(right).unwrap_or(Ordering::Equal));
+    hits.into_iter().map(|(entity, _)| entity).collect()
+}
+
+/// Find the [`DrawingObject`]s and [`Shape`]s on visible, non-system layers
+/// which touch a region, or which are entirely inside it when
+/// `fully_enclosed` is set. Previews are never found.
+pub fn entities_in_region(world: &World, region: BoundingBox, fully_enclosed: bool) -> Vec<Entity> {
+    let index = world.read_resource::<SpatialIndex>();
+    let drawing_objects = world.read_storage::<DrawingObject>();
+    let shapes = world.read_storage::<Shape>();
+    let layers = world.read_storage::<Layer>();
+    let previews = world.read_storage::<Preview>();
+
+    let mut hits: Vec<Entity> = index
+        .query_region(region)
+        .into_iter()
+        .filter(|&entity| !previews.contains(entity))
+        .filter(|&entity| {
+            if let Some(drawing_object) = drawing_objects.get(entity) {
+                selectable(&layers, drawing_object.layer)
+                    && if fully_enclosed {
+                        geometry::inside_box(&drawing_object.geometry, &region)
+                    } else {
+                        geometry::intersects_box(&drawing_object.geometry, &region)
+                    }
+            } else if let Some(shape) = shapes.get(entity) {
+                selectable(&layers, shape.layer)
+                    && if fully_enclosed {
+                        shape.geometry.inside_box(&region)
+                    } else {
+                        shape.geometry.intersects_box(&region)
+                    }
+            } else {
+                false
+            }
+        })
+        .collect();
+
+    // keep the results in a predictable order
+    hits.sort();
+    hits
+}
+
+/// Only objects on visible layers can be picked, and never the ones on system
+/// layers (e.g. the grid).
+fn selectable(layers: &ReadStorage<Layer>, layer: Entity) -> bool {
+    layers.get(layer).map_or(true, |l| {
+        l.visible && !matches!(l.layer_type, LayerType::System)
+    })
+}
+
+/// Keeps the [`SpatialIndex`] in sync with changes to [`DrawingObject`]s and
+/// [`Shape`]s.
+///
+/// The [`ShapePart`]s used to draw a shape aren't indexed, the shape itself
+/// is.
+#[derive(Debug, Default)]
+pub struct SyncSpatialIndex {
+    changes: Option<ReaderId<ComponentEvent>>,
+    shape_changes: Option<ReaderId<ComponentEvent>>,
+}
+
+impl<'a> System<'a> for SyncSpatialIndex {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, DrawingObject>,
+        ReadStorage<'a, Shape>,
+        ReadStorage<'a, ShapePart>,
+        Write<'a, SpatialIndex>,
+    );
+
+    fn run(&mut self, (entities, drawing_objects, shapes, parts, mut index): Self::SystemData) {
+        let mut changed = BitSet::new();
+        let mut changed_shapes = BitSet::new();
+        let mut removed = Vec::new();
+
+        let changes = self
+            .changes
+            .as_mut()
+            .expect("The system should have been set up");
+        let shape_changes = self
+            .shape_changes
+            .as_mut()
+            .expect("The system should have been set up");
+
+        for event in drawing_objects.channel().read(changes) {
+            match *event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    changed.add(id);
+                }
+                ComponentEvent::Removed(id) => removed.push(id),
+            }
+        }
+
+        for event in shapes.channel().read(shape_changes) {
+            match *event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    changed_shapes.add(id);
+                }
+                ComponentEvent::Removed(id) => removed.push(id),
+            }
+        }
+
+        for id in removed {
+            index.remove(id);
+        }
+
+        for (entity, drawing_object, _, _) in
+            (&entities, &drawing_objects, !&parts, &changed).join()
+        {
+            match geometry::bounding_box(&drawing_object.geometry) {
+                Some(bounds) => index.insert(entity, bounds),
+                None => index.remove(entity.id()),
+            }
+        }
+
+        for (entity, shape, _) in (&entities, &shapes, &changed_shapes).join() {
+            match shape.geometry.bounding_box() {
+                Some(bounds) => index.insert(entity, bounds),
+                None => index.remove(entity.id()),
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.changes = Some(WriteStorage::<DrawingObject>::fetch(world).register_reader());
+        self.shape_changes = Some(WriteStorage::<Shape>::fetch(world).register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{polyline::Polyline, shapes::ShapeGeometry};
+    use arcs::{components::Geometry, primitives::Line};
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.create_entity().build()).collect()
+    }
+
+    #[test]
+    fn find_an_entity_in_a_region() {
+        let ents = entities(2);
+        let mut index = SpatialIndex::with_cell_size(10.0);
+        index.insert(
+            ents[0],
+            BoundingBox::new(Point::new(0.0, 0.0), Point::new(5.0, 5.0)),
+        );
+        index.insert(
+            ents[1],
+            BoundingBox::new(Point::new(50.0, 50.0), Point::new(55.0, 55.0)),
+        );
+
+        let got = index.query_point(Point::new(6.0, 6.0), 2.0);
+
+        assert_eq!(got, vec![ents[0]]);
+    }
+
+    #[test]
+    fn removed_entities_are_no_longer_found() {
+        let ents = entities(1);
+        let mut index = SpatialIndex::with_cell_size(10.0);
+        index.insert(
+            ents[0],
+            BoundingBox::new(Point::new(0.0, 0.0), Point::new(25.0, 0.0)),
+        );
+
+        index.remove(ents[0].id());
+
+        assert!(index.query_point(Point::new(12.0, 0.0), 1.0).is_empty());
+        assert!(index.bounds.is_empty());
+    }
+
+    #[test]
+    fn huge_objects_are_still_found() {
+        let ents = entities(1);
+        let mut index = SpatialIndex::with_cell_size(1.0);
+        index.insert(
+            ents[0],
+            BoundingBox::new(Point::new(-1000.0, -1000.0), Point::new(1000.0, 1000.0)),
+        );
+
+        let got = index.query_point(Point::new(999.0, -999.0), 0.5);
+
+        assert_eq!(got, vec![ents[0]]);
+    }
+
+    #[test]
+    fn absurdly_large_bounds_dont_overflow() {
+        let ents = entities(1);
+        let mut index = SpatialIndex::with_cell_size(10.0);
+        let bounds = BoundingBox::new(Point::new(-1e300, -1e300), Point::new(1e300, 1e300));
+
+        index.insert(ents[0], bounds);
+
+        assert_eq!(index.query_point(Point::zero(), 1.0), vec![ents[0]]);
+        assert_eq!(index.oversized, vec![ents[0]]);
+    }
+
+    #[test]
+    fn a_huge_region_still_finds_small_objects() {
+        let ents = entities(1);
+        let mut index = SpatialIndex::with_cell_size(1.0);
+        index.insert(
+            ents[0],
+            BoundingBox::new(Point::new(5.0, 5.0), Point::new(6.0, 6.0)),
+        );
+
+        let got = index.query_region(BoundingBox::new(
+            Point::new(-1000.0, -1000.0),
+            Point::new(1000.0, 1000.0),
+        ));
+
+        assert_eq!(got, vec![ents[0]]);
+    }
+
+    #[test]
+    fn reinserting_moves_an_entity() {
+        let ents = entities(1);
+        let mut index = SpatialIndex::with_cell_size(10.0);
+        index.insert(
+            ents[0],
+            BoundingBox::new(Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+        );
+
+        index.insert(
+            ents[0],
+            BoundingBox::new(Point::new(100.0, 100.0), Point::new(101.0, 101.0)),
+        );
+
+        assert!(index.query_point(Point::new(0.0, 0.0), 1.0).is_empty());
+        assert_eq!(
+            index.query_point(Point::new(100.0, 100.0), 1.0),
+            vec![ents[0]]
+        );
+        assert_eq!(index.bounds.len(), 1);
+    }
+
+    #[test]
+    fn shapes_are_indexed_instead_of_their_parts() {
+        let mut world = World::new();
+        crate::components::register(&mut world);
+        let mut system = SyncSpatialIndex::default();
+        System::setup(&mut system, &mut world);
+        let layer = world.create_entity().with(Layer::default()).build();
+        let vertices = vec![
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(10.0, 10.0),
+        ];
+        let shape = world
+            .create_entity()
+            .with(Shape {
+                geometry: ShapeGeometry::Polyline(Polyline::new(vertices, false)),
+                layer,
+            })
+            .build();
+        world
+            .create_entity()
+            .with(DrawingObject {
+                geometry: Geometry::Line(Line::new(Point::new(0.0, 0.0), Point::new(10.0, 0.0))),
+                layer,
+            })
+            .with(ShapePart { shape })
+            .build();
+
+        system.run_now(&world);
+
+        assert_eq!(
+            entities_near(&world, Point::new(5.0, 0.5), 1.0),
+            vec![shape]
+        );
+        assert_eq!(
+            entities_near(&world, Point::new(10.5, 5.0), 1.0),
+            vec![shape]
+        );
+        assert!(entities_near(&world, Point::new(5.0, 5.0), 1.0).is_empty());
+        let region = BoundingBox::new(Point::new(-1.0, -1.0), Point::new(11.0, 11.0));
+        assert_eq!(entities_in_region(&world, region, true), vec![shape]);
+    }
+}
@@ -0,0 +1,530 @@
+//! Exporting a drawing as an SVG image.
+//!
+//! Like the DXF exporter this works from a [`Document`], drawing each
+//! visible layer from back to front. Drawing units map directly to SVG user
+//! units, so the image is scaled using the viewport's zoom level and anything
+//! measured in [`Dimension::Pixels`] comes out the same size as on screen.
+
+use crate::{
+    document::{Document, GeometryDoc, LayerDoc, ObjectDoc},
+    ellipse::Ellipse,
+    geometry::{self, BoundingBox},
+    shapes::ShapeGeometry,
+};
+use arcs::{
+    components::{Dimension, LineStyle, PointStyle},
+    euclid::{Angle, Size2D},
+    piet::Color,
+    primitives::Arc,
+    CanvasSpace, Point, Vector,
+};
+use std::fmt::Write;
+
+/// Which part of the drawing should end up in the image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Crop {
+    /// Only what is currently visible on the canvas.
+    Viewport,
+    /// Everything on a visible layer.
+    Extents,
+}
+
+/// Extra space left around the drawing when cropping to its extents, in
+/// pixels.
+const EXTENTS_MARGIN: f64 = 10.0;
+
+/// Render a [`Document`] as SVG.
+///
+/// The `canvas_size` is needed to work out what is visible when cropping to
+/// the [`Crop::Viewport`]. A drawing with nothing on it falls back to the
+/// viewport when cropping to its extents.
+pub fn export(document: &Document, crop: Crop, canvas_size: Size2D<f64, CanvasSpace>) -> String {
+    let ppdu = document.viewport.pixels_per_drawing_unit;
+    let centre = Point::from(document.viewport.centre);
+
+    let mut layers: Vec<&LayerDoc> = document.layers.iter().filter(|l| l.visible).collect();
+    layers.sort_by_key(|layer| layer.z_level);
+
+    let region = match crop {
+        Crop::Extents => extents(document, &layers, ppdu),
+        Crop::Viewport => None,
+    }
+    .unwrap_or_else(|| {
+        let half_size = Vector::new(canvas_size.width, canvas_size.height) / (2.0 * ppdu);
+        BoundingBox::new(centre - half_size, centre + half_size)
+    });
+
+    let size = region.size();
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        size.width * ppdu,
+        size.height * ppdu,
+        region.min.x,
+        flip_y(region.max.y),
+        size.width,
+        size.height,
+    )
+    .unwrap();
+
+    for layer in layers {
+        writeln!(svg, r#"  <g data-layer="{}">"#, escape(&layer.name)).unwrap();
+
+        for object in document.objects.iter().filter(|obj| obj.layer == layer.id) {
+            write_object(&mut svg, object, layer, ppdu);
+        }
+
+        writeln!(svg, "  </g>").unwrap();
+    }
+
+    writeln!(svg, "</svg>").unwrap();
+    svg
+}
+
+/// The area covered by every object on the visible layers, padded by a small
+/// margin so thick lines and points aren't clipped.
+fn extents(document: &Document, layers: &[&LayerDoc], ppdu: f64) -> Option<BoundingBox> {
+    let visible = |obj: &&ObjectDoc| layers.iter().any(|l| l.id == obj.layer);
+
+    let mut bounds: Option<BoundingBox> = None;
+    let mut padding = EXTENTS_MARGIN / ppdu;
+
+    for object in document.objects.iter().filter(visible) {
+        let b = match object.geometry.bounding_box() {
+            Some(b) => b,
+            None => continue,
+        };
+        bounds = Some(bounds.map_or(b, |previous| previous.union(&b)));
+
+        let layer = layers.iter().find(|l| l.id == object.layer).unwrap();
+        let size = match object.geometry {
+            GeometryDoc::Point { .. } => resolve(&point_style(object, layer).radius, ppdu),
+            _ => resolve(&line_style(object, layer).width, ppdu) / 2.0,
+        };
+        padding = padding.max(size + EXTENTS_MARGIN / ppdu);
+    }
+
+    bounds.map(|b| b.inflate(padding, padding))
+}
+
+fn write_object(svg: &mut String, object: &ObjectDoc, layer: &LayerDoc, ppdu: f64) {
+    match object.geometry {
+        GeometryDoc::Point { location } => {
+            let style = point_style(object, layer);
+            writeln!(
+                svg,
+                r#"    <circle cx="{}" cy="{}" r="{}" {}/>"#,
+                location.x,
+                flip_y(location.y),
+                resolve(&style.radius, ppdu),
+                paint("fill", &style.colour),
+            )
+            .unwrap();
+        }
+        GeometryDoc::Line { start, end } => {
+            let style = line_style(object, layer);
+            writeln!(
+                svg,
+                r#"    <line x1="{}" y1="{}" x2="{}" y2="{}" {}/>"#,
+                start.x,
+                flip_y(start.y),
+                end.x,
+                flip_y(end.y),
+                stroke(&style, ppdu),
+            )
+            .unwrap();
+        }
+        GeometryDoc::Arc {
+            centre,
+            radius,
+            start_angle,
+            sweep_angle,
+        } => {
+            let style = line_style(object, layer);
+
+            if sweep_angle.abs() >= Angle::<f64>::two_pi().radians {
+                writeln!(
+                    svg,
+                    r#"    <circle cx="{}" cy="{}" r="{}" fill="none" {}/>"#,
+                    centre.x,
+                    flip_y(centre.y),
+                    radius,
+                    stroke(&style, ppdu),
+                )
+                .unwrap();
+                return;
+            }
+
+            let arc = Arc::from_centre_radius(
+                centre.into(),
+                radius,
+                Angle::radians(start_angle),
+                Angle::radians(sweep_angle),
+            );
+            let start = geometry::arc_start(&arc);
+            let end = geometry::arc_end(&arc);
+            let large_arc = sweep_angle.abs() > std::f64::consts::PI;
+            // flipping the y axis means anticlockwise on the drawing is
+            // clockwise in SVG, which is the "positive" sweep direction
+            let sweep_flag = sweep_angle > 0.0;
+
+            writeln!(
+                svg,
+                r#"    <path d="M {} {} A {} {} 0 {} {} {} {}" fill="none" {}/>"#,
+                start.x,
+                flip_y(start.y),
+                radius,
+                radius,
+                large_arc as u8,
+                sweep_flag as u8,
+                end.x,
+                flip_y(end.y),
+                stroke(&style, ppdu),
+            )
+            .unwrap();
+        }
+        GeometryDoc::Polyline {
+            ref vertices,
+            closed,
+        } => {
+            let style = line_style(object, layer);
+            let points: Vec<String> = vertices
+                .iter()
+                .map(|v| format!("{},{}", v.x, flip_y(v.y)))
+                .collect();
+
+            writeln!(
+                svg,
+                r#"    <{} points="{}" fill="none" {}/>"#,
+                if closed { "polygon" } else { "polyline" },
+                points.join(" "),
+                stroke(&style, ppdu),
+            )
+            .unwrap();
+        }
+        GeometryDoc::Ellipse { .. } => {
+            if let Some(ShapeGeometry::Ellipse(ellipse)) = object.geometry.to_shape() {
+                write_ellipse(svg, &ellipse, &line_style(object, layer), ppdu);
+            }
+        }
+    }
+}
+
+fn write_ellipse(svg: &mut String, ellipse: &Ellipse, style: &LineStyle, ppdu: f64) {
+    let rx = ellipse.major_axis.length();
+    let ry = rx * ellipse.ratio;
+    // flipping the y axis turns anticlockwise rotations into clockwise ones
+    let rotation = -ellipse
+        .major_axis
+        .y
+        .atan2(ellipse.major_axis.x)
+        .to_degrees();
+
+    if ellipse.is_closed() {
+        let (cx, cy) = (ellipse.centre.x, flip_y(ellipse.centre.y));
+        let transform = format!(r#"transform="rotate({} {} {})""#, rotation, cx, cy);
+        writeln!(
+            svg,
+            r#"    <ellipse cx="{}" cy="{}" rx="{}" ry="{}" {} fill="none" {}/>"#,
+            cx,
+            cy,
+            rx,
+            ry,
+            transform,
+            stroke(style, ppdu),
+        )
+        .unwrap();
+        return;
+    }
+
+    let start = ellipse.start_point();
+    let end = ellipse.end_point();
+    let large_arc = ellipse.sweep().radians > std::f64::consts::PI;
+
+    // elliptical arcs always go anticlockwise, which is SVG's positive sweep
+    // direction once the y axis has been flipped
+    writeln!(
+        svg,
+        r#"    <path d="M {} {} A {} {} {} {} 1 {} {}" fill="none" {}/>"#,
+        start.x,
+        flip_y(start.y),
+        rx,
+        ry,
+        rotation,
+        large_arc as u8,
+        end.x,
+        flip_y(end.y),
+        stroke(style, ppdu),
+    )
+    .unwrap();
+}
+
+/// The drawing's y axis points up while SVG's points down.
+///
+/// Subtracting instead of negating means we never write out `-0`.
+fn flip_y(y: f64) -> f64 {
+    0.0 - y
+}
+
+/// An object's own style, falling back to its layer's.
+fn point_style(object: &ObjectDoc, layer: &LayerDoc) -> PointStyle {
+    object
+        .point_style
+        .as_ref()
+        .or_else(|| layer.point_style.as_ref())
+        .map(PointStyle::from)
+        .unwrap_or_default()
+}
+
+fn line_style(object: &ObjectDoc, layer: &LayerDoc) -> LineStyle {
+    object
+        .line_style
+        .as_ref()
+        .or_else(|| layer.line_style.as_ref())
+        .map(LineStyle::from)
+        .unwrap_or_default()
+}
+
+/// Convert a [`Dimension`] to drawing units.
+fn resolve(dimension: &Dimension, pixels_per_drawing_unit: f64) -> f64 {
+    match dimension {
+        Dimension::Pixels(pixels) => pixels / pixels_per_drawing_unit,
+        Dimension::DrawingUnits(length) => length.get(),
+    }
+}
+
+fn stroke(style: &LineStyle, ppdu: f64) -> String {
+    format!(
+        r#"{} stroke-width="{}" stroke-linecap="round""#,
+        paint("stroke", &style.stroke),
+        resolve(&style.width, ppdu)
+    )
+}
+
+/// Set a colour attribute, only mentioning the opacity when it isn't opaque.
+fn paint(attribute: &str, colour: &Color) -> String {
+    let rgba = colour.as_rgba_u32();
+    let alpha = rgba & 0xff;
+    let mut attributes = format!(r##"{}="#{:06x}""##, attribute, rgba >> 8);
+
+    if alpha != 0xff {
+        write!(
+            attributes,
+            r#" {}-opacity="{}""#,
+            attribute,
+            f64::from(alpha) / 255.0
+        )
+        .unwrap();
+    }
+
+    attributes
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{
+        DimensionDoc, LineStyleDoc, PointDoc, PointStyleDoc, ViewportDoc, CURRENT_VERSION,
+    };
+
+    fn layer(id: usize, name: &str, z_level: usize, visible: bool) -> LayerDoc {
+        LayerDoc {
+            id,
+            name: name.to_string(),
+            z_level,
+            visible,
+            point_style: None,
+            line_style: Some(LineStyleDoc {
+                stroke: String::from("#ff0000ff"),
+                width: DimensionDoc::Pixels(2.0),
+            }),
+        }
+    }
+
+    fn line(layer: usize, start: (f64, f64), end: (f64, f64)) -> ObjectDoc {
+        ObjectDoc {
+            layer,
+            geometry: GeometryDoc::Line {
+                start: PointDoc {
+                    x: start.0,
+                    y: start.1,
+                },
+                end: PointDoc { x: end.0, y: end.1 },
+            },
+            point_style: None,
+            line_style: None,
+        }
+    }
+
+    fn document(layers: Vec<LayerDoc>, objects: Vec<ObjectDoc>) -> Document {
+        Document {
+            version: CURRENT_VERSION,
+            viewport: ViewportDoc {
+                centre: PointDoc { x: 0.0, y: 0.0 },
+                pixels_per_drawing_unit: 2.0,
+            },
+            layers,
+            objects,
+        }
+    }
+
+    fn canvas() -> Size2D<f64, CanvasSpace> {
+        Size2D::new(200.0, 100.0)
+    }
+
+    #[test]
+    fn layers_are_drawn_back_to_front_and_hidden_ones_skipped() {
+        let doc = document(
+            vec![
+                layer(0, "top", 5, true),
+                layer(1, "bottom", 1, true),
+                layer(2, "hidden", 3, false),
+            ],
+            vec![line(2, (0.0, 0.0), (1.0, 1.0))],
+        );
+
+        let got = export(&doc, Crop::Viewport, canvas());
+
+        let bottom = got.find(r#"data-layer="bottom""#).unwrap();
+        let top = got.find(r#"data-layer="top""#).unwrap();
+        assert!(bottom < top);
+        assert!(!got.contains("hidden"));
+        assert!(!got.contains("<line"));
+    }
+
+    #[test]
+    fn the_viewport_crop_matches_the_canvas() {
+        let doc = document(vec![layer(0, "default", 0, true)], Vec::new());
+
+        let got = export(&doc, Crop::Viewport, canvas());
+
+        assert!(got.contains(r#"width="200" height="100" viewBox="-50 -25 100 50""#));
+    }
+
+    #[test]
+    fn pixel_widths_are_converted_to_drawing_units() {
+        let mut doc = document(
+            vec![layer(0, "default", 0, true)],
+            vec![line(0, (0.0, 0.0), (10.0, 5.0))],
+        );
+        doc.objects[0].line_style = Some(LineStyleDoc {
+            stroke: String::from("#00ff0080"),
+            width: DimensionDoc::DrawingUnits(3.0),
+        });
+        doc.objects.push(line(0, (1.0, 1.0), (2.0, 2.0)));
+
+        let got = export(&doc, Crop::Viewport, canvas());
+
+        assert!(got.contains(
+            r##"<line x1="0" y1="0" x2="10" y2="-5" stroke="#00ff00" stroke-opacity="0.5019607843137255" stroke-width="3""##
+        ));
+        // the layer's 2px lines are 1 drawing unit wide at 2 pixels per unit
+        assert!(got.contains(r##"stroke="#ff0000" stroke-width="1""##));
+    }
+
+    #[test]
+    fn crop_to_the_extents_of_the_drawing() {
+        let mut doc = document(
+            vec![layer(0, "default", 0, true)],
+            vec![ObjectDoc {
+                layer: 0,
+                geometry: GeometryDoc::Point {
+                    location: PointDoc { x: 100.0, y: 50.0 },
+                },
+                point_style: Some(PointStyleDoc {
+                    colour: String::from("#000000ff"),
+                    radius: DimensionDoc::Pixels(2.0),
+                }),
+                line_style: None,
+            }],
+        );
+        doc.objects.push(line(0, (0.0, 0.0), (100.0, 0.0)));
+
+        let got = export(&doc, Crop::Extents, canvas());
+
+        // 10px margin plus the 1 unit point radius, at 2 pixels per unit
+        assert!(got.contains(r#"viewBox="-6 -56 112 62""#));
+        assert!(got.contains(r#"<circle cx="100" cy="-50" r="1""#));
+    }
+
+    #[test]
+    fn anticlockwise_arcs_use_the_positive_sweep_flag() {
+        let doc = document(
+            vec![layer(0, "default", 0, true)],
+            vec![ObjectDoc {
+                layer: 0,
+                geometry: GeometryDoc::Arc {
+                    centre: PointDoc { x: 0.0, y: 0.0 },
+                    radius: 10.0,
+                    start_angle: 0.0,
+                    sweep_angle: 1.5 * std::f64::consts::PI,
+                },
+                point_style: None,
+                line_style: None,
+            }],
+        );
+
+        let got = export(&doc, Crop::Viewport, canvas());
+
+        assert!(got.contains(r#"<path d="M 10 0 A 10 10 0 1 1 "#));
+    }
+
+    #[test]
+    fn closed_polylines_are_drawn_as_polygons() {
+        let polyline = |closed| ObjectDoc {
+            layer: 0,
+            geometry: GeometryDoc::Polyline {
+                vertices: vec![
+                    PointDoc { x: 0.0, y: 0.0 },
+                    PointDoc { x: 10.0, y: 0.0 },
+                    PointDoc { x: 10.0, y: 5.0 },
+                ],
+                closed,
+            },
+            point_style: None,
+            line_style: None,
+        };
+        let doc = document(
+            vec![layer(0, "default", 0, true)],
+            vec![polyline(false), polyline(true)],
+        );
+
+        let got = export(&doc, Crop::Viewport, canvas());
+
+        assert!(got.contains(r#"<polyline points="0,0 10,0 10,-5" fill="none" "#));
+        assert!(got.contains(r#"<polygon points="0,0 10,0 10,-5" fill="none" "#));
+    }
+
+    #[test]
+    fn rotated_ellipses_are_turned_the_other_way() {
+        let doc = document(
+            vec![layer(0, "default", 0, true)],
+            vec![ObjectDoc {
+                layer: 0,
+                geometry: GeometryDoc::Ellipse {
+                    centre: PointDoc { x: 1.0, y: 2.0 },
+                    major_axis: PointDoc { x: 0.0, y: 4.0 },
+                    ratio: 0.5,
+                    start_parameter: 0.0,
+                    end_parameter: 2.0 * std::f64::consts::PI,
+                },
+                point_style: None,
+                line_style: None,
+            }],
+        );
+
+        let got = export(&doc, Crop::Viewport, canvas());
+
+        assert!(got.contains(
+            r#"<ellipse cx="1" cy="-2" rx="4" ry="2" transform="rotate(-90 1 -2)" fill="none" "#
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+//! Ortho mode and polar tracking, which keep the end of a line on a ray
+//! leaving its start point at a "nice" angle.
+//!
+//! Like the [object snaps](crate::snaps), a [`State`](crate::modes::State)
+//! calls [`update()`] as the mouse moves and the [`ApplyTracking`] system
+//! moves the [`CursorPosition`] onto the active ray once everything else
+//! has had a go.
+
+use crate::{settings, snaps::ObjectSnap};
+use arcs::{
+    components::{CursorPosition, Dimension, DrawingObject, Geometry, LineStyle},
+    euclid::Angle,
+    piet::Color,
+    primitives::Line,
+    specs::prelude::*,
+    Point, Vector,
+};
+use serde::{Deserialize, Serialize};
+
+/// How far (in pixels) the cursor can stray from a tracking angle before
+/// polar tracking lets go.
+pub const TRACKING_TOLERANCE: f64 = 10.0;
+/// How long the alignment guide is, in pixels.
+const GUIDE_LENGTH: f64 = 5000.0;
+
+/// The angle increments polar tracking snaps to.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolarTracking {
+    Off,
+    Every15Degrees,
+    Every30Degrees,
+    Every45Degrees,
+}
+
+impl PolarTracking {
+    pub fn increment(self) -> Option<Angle<f64>> {
+        match self {
+            PolarTracking::Off => None,
+            PolarTracking::Every15Degrees => Some(Angle::degrees(15.0)),
+            PolarTracking::Every30Degrees => Some(Angle::degrees(30.0)),
+            PolarTracking::Every45Degrees => Some(Angle::degrees(45.0)),
+        }
+    }
+
+    /// The next setting, for cycling through them with a single button.
+    pub fn next(self) -> PolarTracking {
+        match self {
+            PolarTracking::Off => PolarTracking::Every15Degrees,
+            PolarTracking::Every15Degrees => PolarTracking::Every30Degrees,
+            PolarTracking::Every30Degrees => PolarTracking::Every45Degrees,
+            PolarTracking::Every45Degrees => PolarTracking::Off,
+        }
+    }
+}
+
+impl Default for PolarTracking {
+    fn default() -> PolarTracking {
+        PolarTracking::Off
+    }
+}
+
+/// A half-line the cursor is being constrained to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Point,
+    /// A unit vector pointing along the ray.
+    pub direction: Vector,
+}
+
+impl Ray {
+    pub fn at_angle(origin: Point, angle: Angle<f64>) -> Ray {
+        let (sin, cos) = angle.sin_cos();
+        Ray {
+            origin,
+            direction: Vector::new(cos, sin),
+        }
+    }
+
+    /// The closest point on the ray to a location.
+    pub fn project(&self, location: Point) -> Point {
+        let distance = (location - self.origin).dot(self.direction).max(0.0);
+        self.origin + self.direction * distance
+    }
+}
+
+/// Figure out which [`Ray`] (if any) the cursor should be constrained to
+/// when drawing from `start`.
+///
+/// Ortho mode always picks the closest horizontal or vertical ray, whereas
+/// polar tracking only kicks in when the cursor is within `tolerance` of one
+/// of the tracking angles.
+pub fn tracking_ray(
+    start: Point,
+    cursor: Point,
+    ortho: bool,
+    polar: PolarTracking,
+    tolerance: f64,
+) -> Option<Ray> {
+    let displacement = cursor - start;
+    if displacement.square_length() == 0.0 {
+        return None;
+    }
+
+    let increment = if ortho {
+        Angle::frac_pi_2()
+    } else {
+        polar.increment()?
+    };
+
+    let angle = displacement.angle_from_x_axis();
+    let steps = (angle.radians / increment.radians).round();
+    let ray = Ray::at_angle(start, Angle::radians(steps * increment.radians));
+
+    if ortho || (ray.project(cursor) - cursor).length() <= tolerance {
+        Some(ray)
+    } else {
+        None
+    }
+}
+
+/// A resource holding the active [`Ray`] and the entities used to draw its
+/// alignment guide.
+#[derive(Debug, Default)]
+pub struct Tracking {
+    ray: Option<Ray>,
+    guide: Vec<Entity>,
+}
+
+/// Work out where the end of a line starting at `start` should go, updating
+/// the alignment guide if the tracking ray changed.
+///
+/// Holding shift turns on ortho mode temporarily. Object snaps take
+/// precedence, so there is no tracking while one is active.
+///
+/// Returns `true` when the canvas needs to be redrawn.
+pub fn update(
+    world: &mut World,
+    system_layer: Entity,
+    start: Point,
+    location: Point,
+    shift_pressed: bool,
+    pixels_per_drawing_unit: f64,
+) -> bool {
+    let settings = settings::current(world);
+    let snapped = world
+        .try_fetch::<ObjectSnap>()
+        .map_or(false, |object_snap| object_snap.active().is_some());
+
+    let ray = if snapped {
+        None
+    } else {
+        tracking_ray(
+            start,
+            location,
+            settings.ortho || shift_pressed,
+            settings.polar_tracking,
+            TRACKING_TOLERANCE / pixels_per_drawing_unit,
+        )
+    };
+
+    let previous = world
+        .entry::<Tracking>()
+        .or_insert_with(Tracking::default)
+        .ray;
+    if previous == ray {
+        return false;
+    }
+
+    clear(world);
+
+    let guide = match ray {
+        Some(ray) => {
+            let end = ray.origin + ray.direction * (GUIDE_LENGTH / pixels_per_drawing_unit);
+            let entity = world
+                .create_entity()
+                .with(DrawingObject {
+                    geometry: Geometry::Line(Line::new(ray.origin, end)),
+                    layer: system_layer,
+                })
+                .with(LineStyle {
+                    stroke: Color::rgb8(0x00, 0xa0, 0xa0),
+                    width: Dimension::Pixels(0.5),
+                })
+                .build();
+            vec![entity]
+        }
+        None => Vec::new(),
+    };
+
+    let mut tracking = world.write_resource::<Tracking>();
+    tracking.ray = ray;
+    tracking.guide = guide;
+
+    true
+}
+
+/// Stop tracking and remove the alignment guide.
+pub fn clear(world: &mut World) {
+    let guide = match world.try_fetch_mut::<Tracking>() {
+        Some(mut tracking) => {
+            tracking.ray = None;
+            std::mem::take(&mut tracking.guide)
+        }
+        None => return,
+    };
+
+    for entity in guide {
+        if let Err(e) = world.delete_entity(entity) {
+            log::warn!("Unable to delete the tracking guide: {}", e);
+        }
+    }
+}
+
+/// Moves the [`CursorPosition`] onto the active tracking [`Ray`].
+///
+/// This runs after the grid and object snaps so a grid-snapped cursor still
+/// ends up on the ray.
+#[derive(Debug, Default)]
+pub struct ApplyTracking;
+
+impl<'a> System<'a> for ApplyTracking {
+    type SystemData = (Read<'a, Tracking>, Write<'a, CursorPosition>);
+
+    fn run(&mut self, (tracking, mut cursor_position): Self::SystemData) {
+        if let Some(ray) = tracking.ray {
+            cursor_position.location = ray.project(cursor_position.location);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(left: Point, right: Point) {
+        assert!((left - right).length() < 1e-9, "{:?} != {:?}", left, right);
+    }
+
+    #[test]
+    fn ortho_picks_the_closest_axis() {
+        let start = Point::new(1.0, 1.0);
+
+        let ray =
+            tracking_ray(start, Point::new(3.0, 10.0), true, PolarTracking::Off, 0.0).unwrap();
+
+        assert_close(ray.project(Point::new(3.0, 10.0)), Point::new(1.0, 10.0));
+    }
+
+    #[test]
+    fn polar_tracking_snaps_onto_the_nearest_increment() {
+        let start = Point::zero();
+        let cursor = Point::new(10.0, 9.5);
+
+        let ray = tracking_ray(start, cursor, false, PolarTracking::Every45Degrees, 1.0).unwrap();
+
+        let got = ray.project(cursor);
+        assert_close(got, Point::new(9.75, 9.75));
+    }
+
+    #[test]
+    fn polar_tracking_lets_go_outside_the_tolerance() {
+        let got = tracking_ray(
+            Point::zero(),
+            Point::new(10.0, 5.0),
+            false,
+            PolarTracking::Every45Degrees,
+            1.0,
+        );
+
+        assert_eq!(got, None);
+    }
+
+    #[test]
+    fn no_tracking_when_everything_is_off() {
+        let got = tracking_ray(
+            Point::zero(),
+            Point::new(10.0, 0.1),
+            false,
+            PolarTracking::Off,
+            1.0,
+        );
+
+        assert_eq!(got, None);
+    }
+
+    #[test]
+    fn the_cursor_never_goes_behind_the_start() {
+        let ray = Ray::at_angle(Point::zero(), Angle::zero());
+
+        assert_close(ray.project(Point::new(-5.0, 2.0)), Point::zero());
+    }
+}
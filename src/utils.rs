@@ -1,3 +1,5 @@
+use wasm_bindgen::{JsCast, JsValue};
+
 #[allow(dead_code)]
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
@@ -9,3 +11,40 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/// Get the browser's `localStorage`, if we're allowed to use it.
+pub fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+pub fn load_from_local_storage(key: &str) -> Option<String> {
+    local_storage()?.get_item(key).ok()?
+}
+
+pub fn save_to_local_storage(key: &str, value: &str) {
+    let stored = local_storage().map(|storage| storage.set_item(key, value));
+
+    if !matches!(stored, Some(Ok(_))) {
+        log::warn!("Unable to save \"{}\" to local storage", key);
+    }
+}
+
+/// Prompt the browser to download some text as a file.
+pub fn download(filename: &str, mime_type: &str, contents: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(
+        &parts,
+        web_sys::BlobPropertyBag::new().type_(mime_type),
+    )?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| JsValue::from_str("No document"))?;
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url)
+}